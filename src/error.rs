@@ -0,0 +1,179 @@
+//! Structured error types for the LLM and git layers
+//!
+//! Library consumers can match on these to handle specific failures (e.g. back off on
+//! rate limiting) instead of inspecting error strings. The CLI converts them into `eyre`
+//! reports at the top level.
+
+use crate::message::Violation;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Errors returned when talking to the OpenAI-compatible API
+#[derive(Debug, Error)]
+pub enum ApiError {
+    /// The request could not be sent (connection refused, DNS failure, ...)
+    #[error("Failed to send request to API at {endpoint}: {source}")]
+    Request {
+        endpoint: String,
+        #[source]
+        source: reqwest::Error,
+    },
+
+    /// The request timed out
+    #[error("API request to {endpoint} timed out")]
+    Timeout { endpoint: String },
+
+    /// The API rejected the token (HTTP 401/403)
+    #[error("API request failed ({status}): {body}")]
+    Unauthorized {
+        status: reqwest::StatusCode,
+        body: String,
+    },
+
+    /// The API is rate limiting us (HTTP 429)
+    #[error("API request failed ({status}): rate limited{}: {body}", retry_after_hint(.retry_after))]
+    RateLimited {
+        status: reqwest::StatusCode,
+        retry_after: Option<Duration>,
+        body: String,
+    },
+
+    /// The request was larger than the model's context window (HTTP 400)
+    #[error("API request failed ({status}): context length exceeded: {body}")]
+    ContextLengthExceeded {
+        status: reqwest::StatusCode,
+        body: String,
+    },
+
+    /// Any other non-success HTTP status
+    #[error("API request failed ({status}): {body}")]
+    Status {
+        status: reqwest::StatusCode,
+        body: String,
+    },
+
+    /// The response body could not be parsed
+    #[error("Failed to parse API response: {source} (response: {snippet})")]
+    InvalidResponse {
+        #[source]
+        source: serde_json::Error,
+        snippet: String,
+    },
+
+    /// The response wasn't JSON at all, e.g. a proxy or captive-portal HTML page
+    #[error("Expected a JSON response from the API, got {content_type} (response: {snippet})")]
+    NotJson { content_type: String, snippet: String },
+
+    /// The `ca_cert_path` certificate could not be read or parsed
+    #[error("Failed to load the CA certificate {path}: {reason}")]
+    CaCert { path: String, reason: String },
+
+    /// The response contained no choices
+    #[error("No response from API")]
+    EmptyResponse,
+
+    /// There were no commit messages to combine
+    #[error("No commit messages to combine")]
+    NoMessages,
+}
+
+fn retry_after_hint(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(duration) => format!(", retry after {}s", duration.as_secs()),
+        None => String::new(),
+    }
+}
+
+/// A message was not committed because it broke the configured rules
+#[derive(Debug, Error)]
+#[error("The commit message was not committed: {}", list_violations(.violations))]
+pub struct InvalidMessage {
+    pub violations: Vec<Violation>,
+}
+
+fn list_violations(violations: &[Violation]) -> String {
+    violations
+        .iter()
+        .map(Violation::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Errors returned when fetching an issue from GitHub or GitLab
+#[derive(Debug, Error)]
+pub enum IssueError {
+    /// The remote isn't hosted on GitHub or GitLab
+    #[error("Remote {0} is not a GitHub or GitLab repository")]
+    UnsupportedRemote(String),
+
+    /// The request failed or the response could not be parsed
+    #[error("Failed to fetch issue from {url}: {source}")]
+    Request {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+
+    /// The tracker returned a non-success status (e.g. missing issue, bad token)
+    #[error("Failed to fetch issue from {url}: {status}")]
+    Status {
+        url: String,
+        status: reqwest::StatusCode,
+    },
+
+    /// The branch or remote could not be read
+    #[error(transparent)]
+    Git(#[from] GitError),
+}
+
+/// Errors returned by git operations
+#[derive(Debug, Error)]
+pub enum GitError {
+    /// The current directory is not inside a git repository
+    #[error("Not a git repository: {stderr}")]
+    NotARepo { stderr: String },
+
+    /// The git executable is not on `PATH`
+    #[error("git is not installed or not on PATH. Install it from https://git-scm.com/downloads (or with your package manager) and try again")]
+    NotInstalled,
+
+    /// The git executable could not be run
+    #[error("Failed to execute git {command}: {source}")]
+    Spawn {
+        command: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// A git command exited with a non-zero status
+    #[error("git {command} failed: {stderr}")]
+    CommandFailed { command: String, stderr: String },
+
+    /// A `git config` value needed for the commit identity is not set
+    #[error("Cannot sign off: git config {key} is not set. Set it with 'git config --global {key} <value>'")]
+    MissingIdentity { key: &'static str },
+
+    /// HEAD is not on a branch
+    #[error("Cannot push: HEAD is detached, not on a branch")]
+    DetachedHead,
+
+    /// HEAD and the base branch share no history
+    #[error("HEAD has no common ancestor with '{base}'. Pass the branch it forked from with --base")]
+    NoMergeBase { base: String },
+
+    /// The current branch has no upstream and setting one was disabled
+    #[error("Branch '{branch}' has no upstream branch. Push it with 'git push --set-upstream <remote> {branch}' or enable push.set_upstream")]
+    NoUpstream { branch: String },
+
+    /// Pushing to some of several remotes failed
+    #[error("Failed to push to {}", .remotes.join(", "))]
+    PushFailed { remotes: Vec<String> },
+
+    /// A large diff could not be split into small enough chunks
+    #[error("Unable to split diff into manageable chunks after {attempts} attempts")]
+    SplitFailed { attempts: usize },
+
+    /// A diff could not be split with a particular strategy
+    #[error("No {0} found in diff")]
+    NothingToSplit(&'static str),
+}
@@ -0,0 +1,199 @@
+//! Commit message backends
+//!
+//! The pipeline talks to the model through [`CommitGenerator`], so a different backend (or a
+//! fake returning canned messages in tests) can be swapped in for the OpenAI-compatible API.
+//! [`MockGenerator`] is such a backend that never touches the network.
+
+use crate::config::Config;
+use crate::emotes;
+use crate::git::{self, DiffChunk};
+use crate::llm::{self, Message, Reply, RequestSettings};
+use crate::message;
+use crate::pipeline;
+use crate::prompt::{self, PromptContext};
+use async_trait::async_trait;
+use std::sync::OnceLock;
+use color_eyre::eyre::Result;
+
+/// A backend that writes commit messages
+#[async_trait]
+pub trait CommitGenerator: Send + Sync {
+    /// Generate a commit message for one chunk of a diff
+    ///
+    /// Returning [`ApiError::ContextLengthExceeded`](crate::error::ApiError) makes the
+    /// pipeline split the chunk further and try again.
+    async fn generate(&self, chunk: &DiffChunk, context: &PromptContext) -> Result<String>;
+
+    /// Combine the messages generated for the chunks of a split diff into one
+    ///
+    /// `descriptions` holds the description of each message's chunk (e.g. "File: src/auth.rs"),
+    /// so the files can be credited in the combined message.
+    async fn combine(&self, messages: Vec<String>, descriptions: &[String]) -> Result<String>;
+
+    /// Like [`generate`](Self::generate), with the token usage when the backend reports it
+    async fn generate_reply(&self, chunk: &DiffChunk, context: &PromptContext) -> Result<Reply> {
+        Ok(Reply {
+            content: self.generate(chunk, context).await?,
+            usage: None,
+        })
+    }
+
+    /// Like [`combine`](Self::combine), with the token usage when the backend reports it
+    async fn combine_reply(&self, messages: Vec<String>, descriptions: &[String]) -> Result<Reply> {
+        Ok(Reply {
+            content: self.combine(messages, descriptions).await?,
+            usage: None,
+        })
+    }
+
+    /// Rewrite a message following `instruction`, e.g. to reformat it as a conventional commit
+    ///
+    /// Backends that can't rewrite return the message unchanged.
+    async fn revise(&self, message: &str, _instruction: &str) -> Result<String> {
+        Ok(message.to_string())
+    }
+}
+
+/// The OpenAI-compatible chat completions API configured in [`Config`]
+#[derive(Debug, Clone)]
+pub struct OpenAiGenerator {
+    config: Config,
+    /// Built by the first request, so that every request shares one HTTP client
+    settings: OnceLock<RequestSettings>,
+}
+
+impl OpenAiGenerator {
+    /// Create a generator for a configuration; the API token is only checked on first use
+    pub fn new(config: &Config) -> Self {
+        Self {
+            config: config.clone(),
+            settings: OnceLock::new(),
+        }
+    }
+
+    /// The request settings of the configuration
+    fn settings(&self) -> Result<&RequestSettings> {
+        if let Some(settings) = self.settings.get() {
+            return Ok(settings);
+        }
+        let settings = pipeline::request_settings(&self.config)?;
+        Ok(self.settings.get_or_init(|| settings))
+    }
+}
+
+#[async_trait]
+impl CommitGenerator for OpenAiGenerator {
+    async fn generate(&self, chunk: &DiffChunk, context: &PromptContext) -> Result<String> {
+        Ok(self.generate_reply(chunk, context).await?.content)
+    }
+
+    async fn combine(&self, messages: Vec<String>, descriptions: &[String]) -> Result<String> {
+        Ok(self.combine_reply(messages, descriptions).await?.content)
+    }
+
+    async fn generate_reply(&self, chunk: &DiffChunk, context: &PromptContext) -> Result<Reply> {
+        let settings = self.settings()?;
+        let system_prompt = prompt::build_system_prompt(&self.config);
+        let user_prompt = prompt::build_user_prompt(self.config.get_user_prompt(), context, &chunk.content);
+        Ok(llm::send_prompt_with_usage(&system_prompt, user_prompt, settings).await?)
+    }
+
+    async fn combine_reply(&self, messages: Vec<String>, descriptions: &[String]) -> Result<Reply> {
+        let settings = self.settings()?;
+        let system_prompt = prompt::build_combine_system_prompt(&self.config);
+        Ok(llm::combine_commit_messages_with_usage(messages, descriptions, &system_prompt, settings).await?)
+    }
+
+    async fn revise(&self, message: &str, instruction: &str) -> Result<String> {
+        let settings = self.settings()?;
+        let messages = vec![
+            Message::system(&prompt::build_system_prompt(&self.config)),
+            Message::user(&format!("{}\n\n{}", instruction, message)),
+        ];
+        Ok(llm::chat(messages, settings).await?)
+    }
+}
+
+/// Writes messages from the diff alone, without a model or network (`--offline`)
+///
+/// The subject names the type suggested by the changed paths (`chore` when they don't agree)
+/// and the changed files; the body lists each file with its line counts. Combining lists the
+/// messages under one subject, like `combine.strategy = "concat"`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MockGenerator;
+
+#[async_trait]
+impl CommitGenerator for MockGenerator {
+    async fn generate(&self, chunk: &DiffChunk, _context: &PromptContext) -> Result<String> {
+        Ok(mock_message(chunk))
+    }
+
+    async fn combine(&self, messages: Vec<String>, _descriptions: &[String]) -> Result<String> {
+        Ok(message::concat_messages(&messages))
+    }
+}
+
+/// The deterministic message [`MockGenerator`] writes for a chunk
+fn mock_message(chunk: &DiffChunk) -> String {
+    let files = git::split_into_files(&chunk.content);
+    let paths: Vec<&str> = files
+        .iter()
+        .filter_map(|file| file.paths.first().map(String::as_str))
+        .collect();
+    let kind = emotes::categorize_paths(&paths)
+        .and_then(|category| category.conventional_type())
+        .unwrap_or("chore");
+
+    let subject = match paths.as_slice() {
+        [] => format!("{}: update {}", kind, chunk.description),
+        [path] => format!("{}: update {}", kind, path),
+        paths => format!("{}: update {} files", kind, paths.len()),
+    };
+    let body: Vec<String> = files
+        .iter()
+        .zip(&paths)
+        .map(|(file, path)| {
+            let count = |prefix: char, header: &str| {
+                file.content
+                    .lines()
+                    .filter(|line| line.starts_with(prefix) && !line.starts_with(header))
+                    .count()
+            };
+            format!("- {} (+{}/-{})", path, count('+', "+++"), count('-', "---"))
+        })
+        .collect();
+
+    if body.is_empty() {
+        subject
+    } else {
+        format!("{}\n\n{}", subject, body.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_generator() {
+        let chunk = DiffChunk {
+            content: "diff --git a/docs/usage.md b/docs/usage.md\n--- a/docs/usage.md\n+++ b/docs/usage.md\n\
+                      @@ -1,2 +1,2 @@\n-old\n+new\n+more\n\
+                      diff --git a/README.md b/README.md\n--- a/README.md\n+++ b/README.md\n@@ -1 +1 @@\n-a\n+b\n"
+                .to_string(),
+            description: "Files: docs/usage.md, README.md".to_string(),
+        };
+        let message = MockGenerator.generate(&chunk, &PromptContext::new()).await.unwrap();
+        assert_eq!(
+            message,
+            "docs: update 2 files\n\n- README.md (+1/-1)\n- docs/usage.md (+2/-1)"
+        );
+
+        let chunk = DiffChunk {
+            content: "diff --git a/src/main.rs b/src/main.rs\n+fn main() {}\n".to_string(),
+            description: "File: src/main.rs".to_string(),
+        };
+        let message = MockGenerator.generate(&chunk, &PromptContext::new()).await.unwrap();
+        assert_eq!(message, "chore: update src/main.rs\n\n- src/main.rs (+1/-0)");
+    }
+}
@@ -0,0 +1,217 @@
+//! `.git-narratorignore`: paths whose diff is never sent to the model
+//!
+//! The file lives in the repository root and uses the `.gitignore` syntax: one glob per
+//! line, `#` comments, `!` to re-include, a trailing `/` to match directories only and a
+//! leading (or inner) `/` to anchor the pattern at the root. `*` and `?` don't cross `/`,
+//! `**` does.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Name of the ignore file in the repository root
+pub const IGNORE_FILE: &str = ".git-narratorignore";
+
+/// The patterns of an ignore file, in file order
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreFile {
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    glob: Vec<char>,
+    /// `!pattern`: paths matching it are included again
+    negated: bool,
+    /// `pattern/`: only matches directories
+    dir_only: bool,
+    /// Contains a `/`, so it is matched against the whole path instead of the file name
+    anchored: bool,
+}
+
+impl IgnoreFile {
+    /// Parse the content of an ignore file
+    pub fn parse(content: &str) -> Self {
+        let patterns = content.lines().filter_map(Pattern::parse).collect();
+        Self { patterns }
+    }
+
+    /// Read `.git-narratorignore` from the repository root, `None` when there is none
+    pub fn load(root: &Path) -> io::Result<Option<Self>> {
+        match fs::read_to_string(root.join(IGNORE_FILE)) {
+            Ok(content) => Ok(Some(Self::parse(&content))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Whether a file (path relative to the repository root) is ignored
+    ///
+    /// As in `.gitignore`, the last matching pattern decides, and a pattern matching one of
+    /// the file's directories matches the file.
+    pub fn is_ignored(&self, path: &str) -> bool {
+        let mut ignored = false;
+        for pattern in &self.patterns {
+            if pattern.matches(path) {
+                ignored = !pattern.negated;
+            }
+        }
+        ignored
+    }
+}
+
+impl Pattern {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        // `\#` and `\!` stay escaped in the glob, where they match literally
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            glob: line.chars().collect(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// Whether the pattern matches the file or one of its directories
+    fn matches(&self, path: &str) -> bool {
+        let mut candidates: Vec<(&str, bool)> = path
+            .match_indices('/')
+            .map(|(index, _)| (&path[..index], true))
+            .collect();
+        candidates.push((path, false));
+
+        candidates.into_iter().any(|(candidate, is_dir)| {
+            if self.dir_only && !is_dir {
+                return false;
+            }
+            let text = if self.anchored {
+                candidate
+            } else {
+                candidate.rsplit('/').next().unwrap_or(candidate)
+            };
+            glob_match(&self.glob, &text.chars().collect::<Vec<_>>())
+        })
+    }
+}
+
+/// Match a path against a glob: `*` and `?` stay within a path component, `**` spans any
+/// number of them and `[...]` is a character class
+fn glob_match(glob: &[char], text: &[char]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` also matches no directory at all
+            if let ['/', after @ ..] = rest {
+                if glob_match(after, text) {
+                    return true;
+                }
+            }
+            (0..=text.len()).any(|skip| glob_match(rest, &text[skip..]))
+        }
+        ['*', rest @ ..] => {
+            let component = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=component).any(|skip| glob_match(rest, &text[skip..]))
+        }
+        ['?', rest @ ..] => matches!(text, [c, ..] if *c != '/') && glob_match(rest, &text[1..]),
+        ['[', class @ ..] => match class.iter().skip(1).position(|&c| c == ']') {
+            Some(end) => {
+                let (class, rest) = (&class[..end + 1], &class[end + 2..]);
+                matches!(text, [c, ..] if *c != '/' && class_matches(class, *c)) && glob_match(rest, &text[1..])
+            }
+            None => matches!(text, ['[', ..]) && glob_match(class, &text[1..]),
+        },
+        ['\\', literal, rest @ ..] => matches!(text, [c, ..] if c == literal) && glob_match(rest, &text[1..]),
+        [literal, rest @ ..] => matches!(text, [c, ..] if c == literal) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// Whether a character is in a `[...]` class (without the brackets), e.g. `a-z_` or `!0-9`
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class {
+        ['!' | '^', rest @ ..] => (true, rest),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ignored() {
+        let ignore = IgnoreFile::parse(
+            "# build output\n\
+             dist/\n\
+             *.min.js\n\
+             /fixtures/**/*.json\n\
+             !fixtures/keep/*.json\n\
+             vendor\n\
+             \n\
+             snapshot-[0-9].txt\n",
+        );
+
+        assert!(ignore.is_ignored("dist/app.js"));
+        assert!(ignore.is_ignored("web/dist/app.js"));
+        assert!(!ignore.is_ignored("dist"));
+        assert!(ignore.is_ignored("static/lib.min.js"));
+        assert!(ignore.is_ignored("fixtures/users.json"));
+        assert!(ignore.is_ignored("fixtures/api/v1/users.json"));
+        assert!(!ignore.is_ignored("fixtures/keep/users.json"));
+        assert!(!ignore.is_ignored("src/fixtures/users.json"));
+        assert!(ignore.is_ignored("vendor/lib/mod.rs"));
+        assert!(ignore.is_ignored("third_party/vendor"));
+        assert!(ignore.is_ignored("tests/snapshot-3.txt"));
+        assert!(!ignore.is_ignored("tests/snapshot-x.txt"));
+        assert!(!ignore.is_ignored("src/main.rs"));
+        assert!(!ignore.is_ignored("README.md"));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        let matches = |glob: &str, text: &str| {
+            glob_match(&glob.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>())
+        };
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "src/main.rs"));
+        assert!(matches("src/**", "src/a/b.rs"));
+        assert!(matches("**/b.rs", "b.rs"));
+        assert!(matches("a/**/b", "a/x/y/b"));
+        assert!(matches("a/**/b", "a/b"));
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(!matches("file?.txt", "file/.txt"));
+        assert!(matches("[!a]b", "cb"));
+        assert!(!matches("[!a]b", "ab"));
+        assert!(matches("\\*literal", "*literal"));
+        assert!(!matches("\\*literal", "xliteral"));
+    }
+}
@@ -0,0 +1,248 @@
+//! Issue tracker integration
+//!
+//! When the branch name references an issue (`feature/123-login`), the issue's title and body
+//! are fetched from GitHub or GitLab and added to the prompt, giving the model the intent
+//! behind a change that the diff alone doesn't show.
+
+use crate::config::Config;
+use crate::error::{GitError, IssueError};
+use crate::git;
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// GitHub REST API used for repositories on github.com
+pub const GITHUB_API_URL: &str = "https://api.github.com";
+
+/// How long to wait for the issue tracker before giving up
+const ISSUE_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The issue tracker hosting a repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueTracker {
+    GitHub { owner: String, repo: String },
+    GitLab { host: String, project: String },
+}
+
+/// An issue fetched from the tracker
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Issue {
+    /// `number` on GitHub, `iid` on GitLab
+    #[serde(alias = "iid")]
+    pub number: u64,
+    pub title: String,
+    /// `body` on GitHub, `description` on GitLab; either can be null
+    #[serde(default, alias = "description")]
+    pub body: Option<String>,
+}
+
+impl IssueTracker {
+    /// Detect the tracker from a remote URL (`https://`, `ssh://` or `git@host:owner/repo`)
+    pub fn from_remote_url(url: &str) -> Option<Self> {
+        let url = url.trim().trim_end_matches('/');
+        let url = url.strip_suffix(".git").unwrap_or(url);
+
+        let (host, path) = match url.split_once("://") {
+            Some((_, rest)) => {
+                let (authority, path) = rest.split_once('/')?;
+                let host = authority.rsplit('@').next()?;
+                (host.split(':').next()?, path)
+            }
+            None => {
+                let (authority, path) = url.split_once(':')?;
+                (authority.rsplit('@').next()?, path)
+            }
+        };
+
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+        if segments.len() < 2 {
+            return None;
+        }
+
+        if host.eq_ignore_ascii_case("github.com") && segments.len() == 2 {
+            Some(Self::GitHub {
+                owner: segments[0].to_string(),
+                repo: segments[1].to_string(),
+            })
+        } else if host.to_lowercase().contains("gitlab") {
+            Some(Self::GitLab {
+                host: host.to_string(),
+                project: segments.join("/"),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// API endpoint of an issue, against `api_url` when given (e.g. GitHub Enterprise)
+    pub fn issue_url(&self, number: u64, api_url: Option<&str>) -> String {
+        match self {
+            Self::GitHub { owner, repo } => format!(
+                "{}/repos/{}/{}/issues/{}",
+                api_url.unwrap_or(GITHUB_API_URL).trim_end_matches('/'),
+                owner,
+                repo,
+                number
+            ),
+            Self::GitLab { host, project } => {
+                let default_url = format!("https://{}/api/v4", host);
+                format!(
+                    "{}/projects/{}/issues/{}",
+                    api_url.unwrap_or(&default_url).trim_end_matches('/'),
+                    project.replace('/', "%2F"),
+                    number
+                )
+            }
+        }
+    }
+}
+
+/// Find the issue number referenced in a branch name
+///
+/// The number must stand on its own between `/`, `-`, `_` or `#` separators, so
+/// `feature/123-login`, `fix-42` and `GH-7` match but `oauth2-support` doesn't.
+pub fn issue_number_from_branch(branch: &str) -> Option<u64> {
+    branch
+        .split(['/', '-', '_', '#'])
+        .find(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+        .and_then(|part| part.parse().ok())
+}
+
+/// Fetch an issue, authenticating with `token` when one is configured
+pub async fn fetch_issue(
+    tracker: &IssueTracker,
+    number: u64,
+    token: Option<&str>,
+    api_url: Option<&str>,
+) -> Result<Issue, IssueError> {
+    let url = tracker.issue_url(number, api_url);
+    let client = Client::builder()
+        .timeout(ISSUE_REQUEST_TIMEOUT)
+        .build()
+        .map_err(|source| IssueError::Request {
+            url: url.clone(),
+            source,
+        })?;
+
+    let request = client.get(&url).header("User-Agent", "git-narrator");
+    let request = match (tracker, token) {
+        (IssueTracker::GitHub { .. }, Some(token)) => request
+            .header("Accept", "application/vnd.github+json")
+            .header("Authorization", format!("Bearer {}", token)),
+        (IssueTracker::GitHub { .. }, None) => request.header("Accept", "application/vnd.github+json"),
+        (IssueTracker::GitLab { .. }, Some(token)) => request.header("PRIVATE-TOKEN", token),
+        (IssueTracker::GitLab { .. }, None) => request,
+    };
+
+    let response = request.send().await.map_err(|source| IssueError::Request {
+        url: url.clone(),
+        source,
+    })?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(IssueError::Status { url, status });
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|source| IssueError::Request { url, source })
+}
+
+/// Fetch the issue referenced by the current branch name from the `origin` remote's tracker
+///
+/// Returns `None` when HEAD is detached, the branch references no issue or there is no
+/// `origin` remote.
+pub async fn referenced_issue(config: &Config) -> Result<Option<Issue>, IssueError> {
+    let branch = match git::current_branch() {
+        Ok(branch) => branch,
+        Err(GitError::DetachedHead) => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let Some(number) = issue_number_from_branch(&branch) else {
+        return Ok(None);
+    };
+    let Some(remote_url) = git::remote_url(git::DEFAULT_REMOTE)? else {
+        return Ok(None);
+    };
+    let tracker = IssueTracker::from_remote_url(&remote_url)
+        .ok_or(IssueError::UnsupportedRemote(remote_url))?;
+
+    let token = match tracker {
+        IssueTracker::GitHub { .. } => config.get_github_token(),
+        IssueTracker::GitLab { .. } => config.get_gitlab_token(),
+    };
+    fetch_issue(&tracker, number, token, config.get_issue_api_url())
+        .await
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::eyre::Result;
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[test]
+    fn test_tracker_from_remote_url() {
+        let github = IssueTracker::GitHub {
+            owner: "mi4uu".to_string(),
+            repo: "git-narrator".to_string(),
+        };
+        assert_eq!(IssueTracker::from_remote_url("https://github.com/mi4uu/git-narrator.git"), Some(github.clone()));
+        assert_eq!(IssueTracker::from_remote_url("git@github.com:mi4uu/git-narrator.git"), Some(github.clone()));
+        assert_eq!(IssueTracker::from_remote_url("ssh://git@github.com:22/mi4uu/git-narrator"), Some(github));
+
+        assert_eq!(
+            IssueTracker::from_remote_url("git@gitlab.com:group/sub/project.git"),
+            Some(IssueTracker::GitLab {
+                host: "gitlab.com".to_string(),
+                project: "group/sub/project".to_string(),
+            })
+        );
+        assert_eq!(IssueTracker::from_remote_url("https://example.com/owner/repo.git"), None);
+        assert_eq!(IssueTracker::from_remote_url("/srv/git/repo.git"), None);
+    }
+
+    #[test]
+    fn test_issue_number_from_branch() {
+        assert_eq!(issue_number_from_branch("feature/123-login"), Some(123));
+        assert_eq!(issue_number_from_branch("fix-42"), Some(42));
+        assert_eq!(issue_number_from_branch("GH-7_retry"), Some(7));
+        assert_eq!(issue_number_from_branch("oauth2-support"), None);
+        assert_eq!(issue_number_from_branch("main"), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_issue() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/repos/mi4uu/git-narrator/issues/12"))
+            .and(header("Authorization", "Bearer gh_token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "number": 12,
+                "title": "Login fails with expired tokens",
+                "body": null
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let tracker = IssueTracker::GitHub {
+            owner: "mi4uu".to_string(),
+            repo: "git-narrator".to_string(),
+        };
+        let issue = fetch_issue(&tracker, 12, Some("gh_token"), Some(&mock_server.uri())).await?;
+        assert_eq!(issue.title, "Login fails with expired tokens");
+        assert_eq!(issue.body, None);
+
+        // A missing issue is an error the caller can warn about
+        let result = fetch_issue(&tracker, 13, Some("gh_token"), Some(&mock_server.uri())).await;
+        assert!(matches!(result, Err(IssueError::Status { .. })));
+
+        Ok(())
+    }
+}
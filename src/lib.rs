@@ -0,0 +1,38 @@
+//! AI-powered commit message generation
+//!
+//! The `git-narrator` binary is a thin CLI over this library. Other tools can use it to
+//! generate commit messages without shelling out:
+//!
+//! ```no_run
+//! # async fn example() -> color_eyre::eyre::Result<()> {
+//! let config = git_narrator::Config::load()?;
+//! let diff = git_narrator::get_diff()?;
+//! let message = git_narrator::generate(&config, &diff).await?;
+//! println!("{}", message);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod cache;
+pub mod cli;
+pub mod commands;
+pub mod config;
+pub mod emotes;
+pub mod error;
+pub mod generator;
+pub mod git;
+pub mod ignore;
+pub mod integrations;
+pub mod llm;
+pub mod message;
+pub mod pipeline;
+pub mod prompt;
+pub mod trailers;
+pub mod ui;
+
+pub use config::Config;
+pub use error::{ApiError, GitError, InvalidMessage, IssueError};
+pub use generator::{CommitGenerator, MockGenerator, OpenAiGenerator};
+pub use git::{get_diff, split_large_diff, DiffChunk, SplitDiffResult};
+pub use llm::{combine_commit_messages, generate_commit_message, RequestSettings};
+pub use pipeline::{generate, generate_with_generator, generate_with_progress, Progress};
@@ -1,20 +1,74 @@
-use color_eyre::eyre::{eyre, Context, Result};
-use reqwest::Client;
+use color_eyre::eyre::Result;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Certificate, Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
-use color_eyre::Help;
-use color_eyre::eyre::ContextCompat;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::time::Instant;
 use crate::emotes;
+use crate::error::ApiError;
+use crate::message;
+use crate::prompt::{self, PromptContext};
 
-#[derive(Serialize, Deserialize)]
-struct Message {
-    role: String,
-    content: String,
+/// A single chat message in a conversation with the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+}
+
+impl Message {
+    pub fn new(role: &str, content: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    pub fn system(content: &str) -> Self {
+        Self::new("system", content)
+    }
+
+    pub fn user(content: &str) -> Self {
+        Self::new("user", content)
+    }
+
+    pub fn assistant(content: &str) -> Self {
+        Self::new("assistant", content)
+    }
 }
 
 #[derive(Serialize)]
 struct OpenAIRequest {
     model: String,
     messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<ResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+/// Seed sent in deterministic mode, so repeated requests sample identically
+pub const DETERMINISTIC_SEED: u64 = 42;
+
+#[derive(Serialize)]
+struct ResponseFormat {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl ResponseFormat {
+    fn json_object() -> Self {
+        Self {
+            kind: "json_object".to_string(),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -25,65 +79,176 @@ struct Choice {
 #[derive(Deserialize)]
 struct OpenAIResponse {
     choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+/// Response of the models endpoint (`GET /v1/models`)
+#[derive(Deserialize)]
+struct ModelsResponse {
+    data: Vec<ModelEntry>,
+}
+
+#[derive(Deserialize)]
+struct ModelEntry {
+    id: String,
+}
+
+/// Token counts reported by the API for one request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+}
+
+impl Usage {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// A processed message along with the token usage of the request, when the API reported it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reply {
+    pub content: String,
+    pub usage: Option<Usage>,
+}
+
+/// The JSON object requested with structured output
+#[derive(Deserialize)]
+struct StructuredMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    scope: Option<String>,
+    subject: String,
+    body: Option<String>,
+}
+
+/// Added to the system prompt when structured output is requested
+const STRUCTURED_OUTPUT_INSTRUCTION: &str = "Respond with a single JSON object and nothing else, \
+using this schema: {\"type\": conventional commit type, \"scope\": scope or null, \
+\"subject\": imperative description without the type prefix, \"body\": body text or null}.";
+
+/// Built-in patterns for lines that are meta-commentary rather than commit content
+///
+/// A line is dropped when it starts with one of these (case-insensitive, ignoring list markers).
+pub const DEFAULT_FILTER_PATTERNS: &[&str] = &[
+    // Direct analysis phrases that typically start unwanted lines
+    "the `diff`",
+    "diff indicates",
+    "here's a breakdown",
+    "analysis of the changes",
+    "based on the diff",
+    "looking at the changes",
+    "the changes show",
+    "i can see",
+    "from the diff",
+    "the diff shows",
+    "analyzing the diff",
+    "examining the changes",
+    "reviewing the diff",
+    "summary:",
+    "explanation:",
+    "breakdown:",
+    "analysis:",
+    "here's what",
+    "let me analyze",
+    "looking at this",
+    "from what i can see",
+    "the code changes",
+    "in this diff",
+    "this diff shows",
+    "the following changes",
+    "changes made:",
+    "modifications:",
+    "updates:",
+    "alterations:",
+    "revisions:",
+    "commit message",
+    "sure,",
+    "here's the",
+    // Common LLM meta-commentary starters
+    "based on",
+    "according to",
+    "it appears",
+    "it seems",
+    "it looks like",
+    "from the context",
+    "given the",
+    "considering the",
+    "taking into account",
+];
+
+/// Settings for filtering meta-commentary lines out of model output
+#[derive(Debug, Clone)]
+pub struct LineFilter {
+    /// When false, model output is passed through unfiltered
+    pub enabled: bool,
+    /// Lowercase line-start patterns to drop
+    pub patterns: Vec<String>,
+    /// Drop an untagged reasoning preamble, see [`strip_leading_reasoning`]
+    pub strip_leading_reasoning: bool,
+}
+
+impl Default for LineFilter {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            patterns: DEFAULT_FILTER_PATTERNS.iter().map(|p| p.to_string()).collect(),
+            strip_leading_reasoning: false,
+        }
+    }
+}
+
+/// Whether a line hedges about what "this commit" does (`This commit appears to add ...`)
+fn is_meta_commit_line(line: &str) -> bool {
+    let Some(rest) = line.strip_prefix("this commit") else {
+        return false;
+    };
+    ["appears to", "seems to", "based on", "analysis"]
+        .iter()
+        .any(|phrase| rest.contains(phrase))
+}
+
+/// Drop reasoning a model wrote before the message without `<think>` tags: everything before
+/// the first conventional-commit line, when a blank line separates the two
+///
+/// Output that starts with the message, or has no conventional line after a blank one, is
+/// returned unchanged.
+fn strip_leading_reasoning(content: &str) -> &str {
+    let mut offset = 0;
+    let mut previous_blank = false;
+    let mut preamble = false;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if emotes::is_conventional_commit(trimmed) {
+            // A reply that starts with the message has no preamble
+            return if preamble && previous_blank { &content[offset..] } else { content };
+        }
+        preamble |= !trimmed.is_empty();
+        previous_blank = trimmed.is_empty();
+        offset += line.len();
+    }
+    content
 }
 
 /// Filter out unwanted lines from LLM output that are meta-commentary rather than actual commit content
-fn filter_unwanted_lines(content: &str) -> String {
-    let unwanted_start_patterns = [
-        // Direct analysis phrases that typically start unwanted lines
-        "the `diff`",
-        "diff indicates",
-        "here's a breakdown",
-        "analysis of the changes",
-        "based on the diff",
-        "looking at the changes",
-        "the changes show",
-        "i can see",
-        "from the diff",
-        "the diff shows",
-        "analyzing the diff",
-        "examining the changes",
-        "reviewing the diff",
-        "summary:",
-        "explanation:",
-        "breakdown:",
-        "analysis:",
-        "here's what",
-        "let me analyze",
-        "looking at this",
-        "from what i can see",
-        "the code changes",
-        "in this diff",
-        "this diff shows",
-        "the following changes",
-        "changes made:",
-        "modifications:",
-        "updates:",
-        "alterations:",
-        "revisions:",
-        "commit message",
-        "sure,",
-        "here's the",
-        "```diff",
-        "```",
-        "this commit",
-        // Common LLM meta-commentary starters
-        "based on",
-        "according to",
-        "it appears",
-        "it seems",
-        "it looks like",
-        "from the context",
-        "given the",
-        "considering the",
-        "taking into account",
-    ];
+fn filter_unwanted_lines(content: &str, filter: &LineFilter) -> String {
+    if !filter.enabled {
+        return content.trim().to_string();
+    }
 
     let lines: Vec<&str> = content.lines().collect();
     let mut filtered_lines = Vec::new();
     
     for line in lines {
         let line_trimmed = line.trim().to_lowercase();
+        // Ignore list markers so bulleted meta-commentary is caught as well
+        let line_content = line_trimmed
+            .trim_start_matches(['-', '*', '•'])
+            .trim_start();
         
         // Skip empty lines at the beginning but preserve them in the middle/end
         if line_trimmed.is_empty() {
@@ -94,15 +259,14 @@ fn filter_unwanted_lines(content: &str) -> String {
         }
         
         // Check if line starts with unwanted patterns (conservative approach)
-        let should_filter = unwanted_start_patterns.iter().any(|pattern| {
+        let should_filter = filter.patterns.iter().any(|pattern| {
             // Check if line starts with the pattern (case insensitive)
-            line_trimmed.contains(pattern) 
+            line_content.starts_with(pattern)
         });
         
-        // Special case: filter "this commit" lines that contain meta-commentary
-        let is_meta_commit_line = line_trimmed.starts_with("this commit") &&
-            (line_trimmed.contains("appears") || line_trimmed.contains("seems") ||
-             line_trimmed.contains("based on") || line_trimmed.contains("analysis"));
+        // Special case: a leading "this commit appears to ..." line describes the diff rather
+        // than the change; later "this commit" lines are body content and are kept
+        let is_meta_commit_line = filtered_lines.is_empty() && is_meta_commit_line(&line_trimmed);
         
         if !should_filter && !is_meta_commit_line {
             filtered_lines.push(line);
@@ -113,184 +277,596 @@ fn filter_unwanted_lines(content: &str) -> String {
     filtered_lines.join("\n").trim().to_string()
 }
 
-/// Generate a commit message based on the git diff
-pub async fn generate_commit_message(
-    diff: &str,
-    system_prompt: &str,
-    user_prompt: &str,
-    api_token: &str,
-    api_base_url: &str,
-    model: &str,
-) -> Result<String> {
-    let client = Client::new();
-
-    // Prepare the request to OpenAI API
-    let request = OpenAIRequest {
-        model: model.to_string(),
-        messages: vec![
-            Message {
-                role: "system".to_string(),
-                content: system_prompt.to_string(),
-            },
-            Message {
-                role: "user".to_string(),
-                content: user_prompt.replace("{}", diff),
-            },
-        ],
-    };
+/// Send a chat completion request and return the content of the first choice and the usage
+async fn send_chat_request(
+    client: &Client,
+    request: &OpenAIRequest,
+    settings: &RequestSettings,
+) -> Result<(String, Option<Usage>), ApiError> {
+    // A page from a proxy or captive portal in place of the API is often transient
+    match send_chat_request_once(client, request, settings).await {
+        Err(ApiError::NotJson { .. }) => send_chat_request_once(client, request, settings).await,
+        result => result,
+    }
+}
+
+/// Send a chat completion request once, see [`send_chat_request`]
+async fn send_chat_request_once(
+    client: &Client,
+    request: &OpenAIRequest,
+    settings: &RequestSettings,
+) -> Result<(String, Option<Usage>), ApiError> {
+    if let Some(interval) = settings.min_request_interval {
+        pace_requests(interval).await;
+    }
 
     // Construct the full API endpoint URL
-    let endpoint = format!("{}/v1/chat/completions", api_base_url.trim_end_matches('/'));
+    let endpoint = format!(
+        "{}/v1/chat/completions",
+        settings.api_base_url.trim_end_matches('/')
+    );
 
     // Send the request to the API
-    let response = client
-        .post(&endpoint)
-        .header("Authorization", format!("Bearer {}", api_token))
+    let response = authorize(client.post(&endpoint), settings)
         .header("Content-Type", "application/json")
-        .json(&request)
+        .json(request)
         .send()
         .await
-        .context(format!("Failed to send request to API at {}", endpoint))?;
+        .map_err(|source| request_error(&endpoint, source))?;
 
     // Parse the response
     let response_status = response.status();
-    let response_text = response.text().await?;
+    let retry_after = retry_after(&response);
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let response_text = response
+        .text()
+        .await
+        .map_err(|source| request_error(&endpoint, source))?;
 
     if !response_status.is_success() {
-        return Err(eyre!(
-            "API request failed ({}): {}",
-            response_status,
-            response_text
-        ));
+        return Err(status_error(response_status, retry_after, response_text));
     }
 
-    let response: OpenAIResponse = serde_json::from_str(&response_text).context("Failed to parse API response").note(format!("response: {}  ... {}",
-        &response_text[0..100.min(response_text.len())].to_string(),
-        &response_text[response_text.len() - 100.min(response_text.len())..].to_string(),
-    ))?;
-    // Extract the commit message
-    let commit_message = response
+    if is_not_json(content_type.as_deref(), &response_text)
+        && serde_json::from_str::<serde_json::Value>(&response_text).is_err()
+    {
+        return Err(ApiError::NotJson {
+            content_type: content_type.unwrap_or_else(|| "no content type".to_string()),
+            snippet: response_text.trim().chars().take(NOT_JSON_SNIPPET_CHARS).collect(),
+        });
+    }
+
+    let response: OpenAIResponse =
+        serde_json::from_str(&response_text).map_err(|source| ApiError::InvalidResponse {
+            source,
+            snippet: response_snippet(&response_text),
+        })?;
+
+    // Extract the message content
+    let content = response
         .choices
-        .first()
-        .context("No response from API")?
-        .message
-        .content
-        .clone();
-    // First, handle </think> tags (existing logic)
-    let mut commit_message_o: Vec<&str> = commit_message.split("</think>").collect();
-    let commit_message_t = commit_message_o.pop();
-    let commit_message = match commit_message_t {
-        Some(m) => m.to_string(),
-        None => commit_message
+        .into_iter()
+        .next()
+        .map(|choice| choice.message.content)
+        .ok_or(ApiError::EmptyResponse)?;
+    Ok((content, response.usage))
+}
+
+/// Add the API token to a request, as configured
+///
+/// Local servers don't need a token, so none is sent when it's empty.
+fn authorize(request_builder: RequestBuilder, settings: &RequestSettings) -> RequestBuilder {
+    let api_token = &settings.api_token;
+    match settings.auth {
+        _ if api_token.is_empty() => request_builder,
+        AuthScheme::Bearer => request_builder.header("Authorization", format!("Bearer {}", api_token)),
+        AuthScheme::ApiKey => request_builder.header("api-key", api_token),
+    }
+}
+
+/// When the last request of this process was started, for `pace_requests`
+static LAST_REQUEST: tokio::sync::Mutex<Option<Instant>> = tokio::sync::Mutex::const_new(None);
+
+/// Wait until at least `interval` has passed since the previous request was started
+///
+/// The lock is held while waiting, so concurrent callers are paced one after another.
+async fn pace_requests(interval: Duration) {
+    let mut last_request = LAST_REQUEST.lock().await;
+    if let Some(last) = *last_request {
+        tokio::time::sleep_until(last + interval).await;
+    }
+    *last_request = Some(Instant::now());
+}
+
+/// How much of a non-JSON response body is shown in the error
+const NOT_JSON_SNIPPET_CHARS: usize = 200;
+
+/// How many characters of each end of an unparsable response are shown in the error
+const INVALID_RESPONSE_SNIPPET_CHARS: usize = 100;
+
+/// The start and the end of an unparsable response body, for the error
+///
+/// Counted in characters rather than bytes, so multibyte text is never cut in the middle of
+/// a character.
+fn response_snippet(text: &str) -> String {
+    let head: String = text.chars().take(INVALID_RESPONSE_SNIPPET_CHARS).collect();
+    let mut tail: Vec<char> = text.chars().rev().take(INVALID_RESPONSE_SNIPPET_CHARS).collect();
+    tail.reverse();
+    format!("{}  ... {}", head, tail.into_iter().collect::<String>())
+}
+
+/// Whether a response is something other than JSON, judging by its content type or, when
+/// it has none, by a body that starts like markup
+fn is_not_json(content_type: Option<&str>, body: &str) -> bool {
+    match content_type {
+        Some(content_type) => !content_type.to_lowercase().contains("json"),
+        None => body.trim_start().starts_with('<'),
+    }
+}
+
+/// Build the HTTP client for the configured timeouts, HTTP version and extra CA certificate
+fn build_http_client(settings: &RequestSettings) -> Result<Client, ApiError> {
+    let mut builder = Client::builder();
+    if let Some(timeout) = settings.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if settings.http2_only {
+        builder = builder.http2_prior_knowledge();
+    }
+    if let Some(timeout) = settings.pool_idle_timeout {
+        builder = builder.pool_idle_timeout(timeout);
+    }
+    if let Some(cert) = &settings.ca_cert {
+        builder = builder.add_root_certificate(cert.clone());
+    }
+    builder
+        .build()
+        .map_err(|source| request_error(&settings.api_base_url, source))
+}
+
+/// Read a PEM (or DER) CA certificate to trust in addition to the system's, e.g. for a
+/// self-signed internal endpoint or a TLS-intercepting proxy
+pub fn load_ca_cert(path: &Path) -> Result<Certificate, ApiError> {
+    let error = |reason: String| ApiError::CaCert {
+        path: path.display().to_string(),
+        reason,
     };
-    
-    // Apply comprehensive filtering to remove unwanted LLM meta-commentary
-    let filtered_message = filter_unwanted_lines(&commit_message);
-    
-    Ok(filtered_message)
+    let bytes = fs::read(path).map_err(|err| error(err.to_string()))?;
+    let is_pem = bytes.windows(10).any(|window| window == b"-----BEGIN");
+    let cert = if is_pem {
+        Certificate::from_pem(&bytes)
+    } else {
+        Certificate::from_der(&bytes)
+    };
+    cert.map_err(|err| error(format!("not a valid PEM or DER certificate ({})", err)))
+}
+
+/// Map a reqwest error to an API error, distinguishing timeouts
+fn request_error(endpoint: &str, source: reqwest::Error) -> ApiError {
+    if source.is_timeout() {
+        ApiError::Timeout {
+            endpoint: endpoint.to_string(),
+        }
+    } else {
+        ApiError::Request {
+            endpoint: endpoint.to_string(),
+            source,
+        }
+    }
+}
+
+/// Map a non-success HTTP status to an API error
+fn status_error(status: StatusCode, retry_after: Option<Duration>, body: String) -> ApiError {
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ApiError::Unauthorized { status, body },
+        StatusCode::TOO_MANY_REQUESTS => ApiError::RateLimited {
+            status,
+            retry_after,
+            body,
+        },
+        StatusCode::BAD_REQUEST if is_context_length_error(&body) => {
+            ApiError::ContextLengthExceeded { status, body }
+        }
+        _ => ApiError::Status { status, body },
+    }
+}
+
+/// Whether an error body reports a prompt longer than the model's context window
+///
+/// Providers word this differently: OpenAI uses the `context_length_exceeded` code, other
+/// servers mention the context length or a token limit in the message.
+fn is_context_length_error(body: &str) -> bool {
+    let body = body.to_lowercase();
+    [
+        "context_length_exceeded",
+        "context length",
+        "context window",
+        "maximum context",
+        "too many tokens",
+    ]
+    .iter()
+    .any(|marker| body.contains(marker))
+}
+
+/// Parse the Retry-After header (in seconds) of a response
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// How the API token is sent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AuthScheme {
+    /// `Authorization: Bearer <token>`, used by OpenAI and compatible servers
+    #[default]
+    Bearer,
+    /// `api-key: <token>`, used by Azure OpenAI
+    ApiKey,
+}
+
+/// Line put before each message in the combine prompt; `{n}` is the message's number
+pub const DEFAULT_COMBINE_SEPARATOR: &str = "--- Commit {n} ---";
+
+/// Connection and request settings shared by all chat requests
+#[derive(Debug, Clone)]
+pub struct RequestSettings {
+    pub api_token: String,
+    pub api_base_url: String,
+    pub model: String,
+    /// Filtering of meta-commentary lines from the output
+    pub filter: LineFilter,
+    /// Request a JSON object (`response_format`) and assemble the message from its fields
+    pub structured_output: bool,
+    /// Send temperature 0, top_p 1 and a fixed seed for reproducible output
+    pub deterministic: bool,
+    /// How the API token is sent
+    pub auth: AuthScheme,
+    /// Timeout for a whole request, `None` waiting indefinitely
+    pub timeout: Option<Duration>,
+    /// Least time between the starts of two requests, to stay under a provider's rate limit
+    pub min_request_interval: Option<Duration>,
+    /// Speak HTTP/2 without negotiating it first (prior knowledge)
+    pub http2_only: bool,
+    /// How long idle connections are kept for reuse, `None` for reqwest's default
+    pub pool_idle_timeout: Option<Duration>,
+    /// Extra root certificate to trust, see [`load_ca_cert`]
+    pub ca_cert: Option<Certificate>,
+    /// Line put before each message when combining messages, `{n}` being its number
+    pub combine_separator: String,
+    /// The HTTP client, shared by every request made with these settings
+    pub client: SharedClient,
+}
+
+/// An HTTP client built on first use and then reused, also by clones, so that the requests
+/// of a run share one connection pool
+#[derive(Debug, Clone, Default)]
+pub struct SharedClient(OnceLock<Client>);
+
+impl RequestSettings {
+    /// Settings with the default line filter and plain-text output
+    pub fn new(api_token: &str, api_base_url: &str, model: &str) -> Self {
+        Self {
+            api_token: api_token.to_string(),
+            api_base_url: api_base_url.to_string(),
+            model: model.to_string(),
+            filter: LineFilter::default(),
+            structured_output: false,
+            deterministic: false,
+            auth: AuthScheme::Bearer,
+            timeout: None,
+            min_request_interval: None,
+            http2_only: false,
+            pool_idle_timeout: None,
+            ca_cert: None,
+            combine_separator: DEFAULT_COMBINE_SEPARATOR.to_string(),
+            client: SharedClient::default(),
+        }
+    }
+
+    /// The HTTP client for these settings, built by the first request
+    fn client(&self) -> Result<&Client, ApiError> {
+        if let Some(client) = self.client.0.get() {
+            return Ok(client);
+        }
+        let client = build_http_client(self)?;
+        Ok(self.client.0.get_or_init(|| client))
+    }
+}
+
+/// Send a system + user prompt and post-process the returned message
+///
+/// With structured output, the JSON fields are assembled into the message. Providers that
+/// reject `response_format` or `seed` (HTTP 400) are retried without them, and a response
+/// that isn't the expected JSON falls back to plain-text processing.
+async fn request_message(
+    client: &Client,
+    system_prompt: &str,
+    user_content: String,
+    settings: &RequestSettings,
+) -> Result<Reply, ApiError> {
+    let messages = vec![Message::system(system_prompt), Message::user(&user_content)];
+    complete(client, messages, settings).await
+}
+
+/// Send a conversation and post-process the returned message (see `request_message`)
+async fn complete(
+    client: &Client,
+    mut messages: Vec<Message>,
+    settings: &RequestSettings,
+) -> Result<Reply, ApiError> {
+    if settings.structured_output {
+        if let Some(system) = messages.iter_mut().find(|message| message.role == "system") {
+            system.content = format!("{}\n\n{}", system.content, STRUCTURED_OUTPUT_INSTRUCTION);
+        }
+    }
+
+    let mut request = OpenAIRequest {
+        model: settings.model.clone(),
+        messages,
+        response_format: settings
+            .structured_output
+            .then(ResponseFormat::json_object),
+        temperature: settings.deterministic.then_some(0.0),
+        top_p: settings.deterministic.then_some(1.0),
+        seed: settings.deterministic.then_some(DETERMINISTIC_SEED),
+    };
+
+    let (content, usage) = match send_chat_request(client, &request, settings).await {
+        Err(ApiError::Status { status, .. })
+            if status == StatusCode::BAD_REQUEST
+                && (request.response_format.is_some() || request.seed.is_some()) =>
+        {
+            request.response_format = None;
+            request.seed = None;
+            send_chat_request(client, &request, settings).await?
+        }
+        result => result?,
+    };
+
+    // Drop any reasoning the model emitted before </think>, or without tags if configured
+    let content = content.rsplit("</think>").next().unwrap_or_default();
+    let content = if settings.filter.strip_leading_reasoning {
+        strip_leading_reasoning(content)
+    } else {
+        content
+    };
+
+    if settings.structured_output {
+        if let Some(content) = parse_structured_message(content) {
+            return Ok(Reply { content, usage });
+        }
+    }
+
+    // Apply comprehensive filtering to remove unwanted LLM meta-commentary, then unwrap a
+    // message the model enclosed entirely in a code fence
+    let filtered_message = filter_unwanted_lines(content, &settings.filter);
+    Ok(Reply {
+        content: message::unwrap_code_fence(&filtered_message),
+        usage,
+    })
+}
+
+/// Assemble a conventional-commit message from a structured JSON response, `None` if the
+/// content isn't such an object
+fn parse_structured_message(content: &str) -> Option<String> {
+    let json = message::unwrap_code_fence(content);
+    let structured: StructuredMessage = serde_json::from_str(json.trim()).ok()?;
+
+    let kind = structured.kind.trim();
+    let subject = structured.subject.trim();
+    if kind.is_empty() || subject.is_empty() {
+        return None;
+    }
+
+    let header = match structured.scope.as_deref().map(str::trim) {
+        Some(scope) if !scope.is_empty() => format!("{}({}): {}", kind, scope, subject),
+        _ => format!("{}: {}", kind, subject),
+    };
+
+    match structured.body.as_deref().map(str::trim) {
+        Some(body) if !body.is_empty() => Some(format!("{}\n\n{}", header, body)),
+        _ => Some(header),
+    }
+}
+
+/// Continue a conversation, e.g. to refine a previously generated message, and return the
+/// post-processed reply
+pub async fn chat(messages: Vec<Message>, settings: &RequestSettings) -> Result<String, ApiError> {
+    Ok(complete(settings.client()?, messages, settings).await?.content)
+}
+
+/// List the models the API serves (`GET /v1/models` of OpenAI-compatible APIs)
+pub async fn list_models(settings: &RequestSettings) -> Result<Vec<String>, ApiError> {
+    let client = settings.client()?;
+    let endpoint = format!("{}/v1/models", settings.api_base_url.trim_end_matches('/'));
+    let response = authorize(client.get(&endpoint), settings)
+        .send()
+        .await
+        .map_err(|source| request_error(&endpoint, source))?;
+
+    let response_status = response.status();
+    let retry_after = retry_after(&response);
+    let response_text = response
+        .text()
+        .await
+        .map_err(|source| request_error(&endpoint, source))?;
+    if !response_status.is_success() {
+        return Err(status_error(response_status, retry_after, response_text));
+    }
+
+    let models: ModelsResponse =
+        serde_json::from_str(&response_text).map_err(|source| ApiError::InvalidResponse {
+            source,
+            snippet: response_text.trim().chars().take(NOT_JSON_SNIPPET_CHARS).collect(),
+        })?;
+    Ok(models.data.into_iter().map(|model| model.id).collect())
+}
+
+/// Whether `model` is among the listed models, Ollama's implicit `:latest` tag included
+pub fn model_listed(models: &[String], model: &str) -> bool {
+    let model = model.trim();
+    models
+        .iter()
+        .any(|listed| listed == model || listed.strip_suffix(":latest") == Some(model))
+}
+
+/// Generate a commit message based on the git diff
+pub async fn generate_commit_message(
+    diff: &str,
+    system_prompt: &str,
+    user_prompt: &str,
+    settings: &RequestSettings,
+) -> Result<String, ApiError> {
+    Ok(generate_commit_message_with_usage(diff, system_prompt, user_prompt, settings)
+        .await?
+        .content)
+}
+
+/// Generate a commit message based on the git diff, along with the request's token usage
+pub async fn generate_commit_message_with_usage(
+    diff: &str,
+    system_prompt: &str,
+    user_prompt: &str,
+    settings: &RequestSettings,
+) -> Result<Reply, ApiError> {
+    let user_content = prompt::build_user_prompt(user_prompt, &PromptContext::new(), diff);
+    send_prompt_with_usage(system_prompt, user_content, settings).await
+}
+
+/// Send a system prompt and a complete user message (see [`prompt::build_user_prompt`]),
+/// returning the post-processed commit message along with the request's token usage
+pub async fn send_prompt_with_usage(
+    system_prompt: &str,
+    user_content: String,
+    settings: &RequestSettings,
+) -> Result<Reply, ApiError> {
+    let client = settings.client()?;
+    request_message(client, system_prompt, user_content, settings).await
+}
+
+/// Send a system + user prompt and return the reply as plain text, for prose such as
+/// release notes rather than a commit message
+///
+/// None of the commit message post-processing is applied: no structured output, line
+/// filter or code fence unwrapping. Only reasoning the model wrapped in `<think>` tags is
+/// dropped.
+pub async fn generate_text(
+    content: &str,
+    system_prompt: &str,
+    user_prompt: &str,
+    settings: &RequestSettings,
+) -> Result<String, ApiError> {
+    let client = settings.client()?;
+    let mut request = OpenAIRequest {
+        model: settings.model.clone(),
+        messages: vec![
+            Message::system(system_prompt),
+            Message::user(&prompt::build_user_prompt(user_prompt, &PromptContext::new(), content)),
+        ],
+        response_format: None,
+        temperature: settings.deterministic.then_some(0.0),
+        top_p: settings.deterministic.then_some(1.0),
+        seed: settings.deterministic.then_some(DETERMINISTIC_SEED),
+    };
+
+    let (text, _) = match send_chat_request(client, &request, settings).await {
+        Err(ApiError::Status { status, .. }) if status == StatusCode::BAD_REQUEST && request.seed.is_some() => {
+            request.seed = None;
+            send_chat_request(client, &request, settings).await?
+        }
+        result => result?,
+    };
+    Ok(text.rsplit("</think>").next().unwrap_or_default().trim().to_string())
 }
 
 /// Combine multiple commit messages into a single coherent message
+///
+/// Each message is put below a separator line (`settings.combine_separator`), followed by
+/// the description of the chunk it was written for from `descriptions` (e.g. "File:
+/// src/auth.rs"), which may be shorter than `messages`, or empty.
 pub async fn combine_commit_messages(
     messages: Vec<String>,
+    descriptions: &[String],
+    system_prompt: &str,
+    settings: &RequestSettings,
+) -> Result<String, ApiError> {
+    Ok(combine_commit_messages_with_usage(messages, descriptions, system_prompt, settings)
+        .await?
+        .content)
+}
+
+/// Combine multiple commit messages into a single coherent message, along with the token
+/// usage of the request (none when there was nothing to combine)
+pub async fn combine_commit_messages_with_usage(
+    messages: Vec<String>,
+    descriptions: &[String],
     system_prompt: &str,
-    api_token: &str,
-    api_base_url: &str,
-    model: &str,
-) -> Result<String> {
+    settings: &RequestSettings,
+) -> Result<Reply, ApiError> {
     if messages.is_empty() {
-        return Err(eyre!("No commit messages to combine"));
+        return Err(ApiError::NoMessages);
     }
     
     if messages.len() == 1 {
-        return Ok(messages[0].clone());
+        return Ok(Reply {
+            content: messages[0].clone(),
+            usage: None,
+        });
     }
 
-    let client = Client::new();
+    let client = settings.client()?;
     
-    // Create a prompt for combining messages
+    // Create a prompt for combining messages, without the emotes of already-processed
+    // messages: the combined message gets a single one below
     let combined_messages = messages.iter()
         .enumerate()
-        .map(|(i, msg)| format!("Message {}: {}", i + 1, msg))
+        .map(|(i, msg)| {
+            let separator = settings.combine_separator.replace("{n}", &(i + 1).to_string());
+            match descriptions.get(i).filter(|description| !description.is_empty()) {
+                Some(description) => format!("{}\n({})\n{}", separator, description, emotes::strip_leading_emotes(msg)),
+                None => format!("{}\n{}", separator, emotes::strip_leading_emotes(msg)),
+            }
+        })
         .collect::<Vec<_>>()
         .join("\n\n");
     
     let combination_prompt = format!(
         "Combine the following {} commit messages into a single, coherent commit message. \
+        Each message starts after a separator line, followed by the files it covers in parentheses when known. \
         Remove any redundancy and create a unified message that captures all the changes. \
         Follow conventional commit format and best practices:\n\n{}",
         messages.len(),
         combined_messages
     );
 
-    let request = OpenAIRequest {
-        model: model.to_string(),
-        messages: vec![
-            Message {
-                role: "system".to_string(),
-                content: system_prompt.to_string(),
-            },
-            Message {
-                role: "user".to_string(),
-                content: combination_prompt,
-            },
-        ],
-    };
-
-    let endpoint = format!("{}/v1/chat/completions", api_base_url.trim_end_matches('/'));
-
-    let response = client
-        .post(&endpoint)
-        .header("Authorization", format!("Bearer {}", api_token))
-        .header("Content-Type", "application/json")
-        .json(&request)
-        .send()
-        .await
-        .context(format!("Failed to send combination request to API at {}", endpoint))?;
-
-    let response_status = response.status();
-    let response_text = response.text().await?;
-
-    if !response_status.is_success() {
-        return Err(eyre!(
-            "API request failed ({}): {}",
-            response_status,
-            response_text
-        ));
-    }
-
-    let response: OpenAIResponse = serde_json::from_str(&response_text)
-        .context("Failed to parse API response for message combination")?;
-    
-    let combined_message = response
-        .choices
-        .first()
-        .context("No response from API for message combination")?
-        .message
-        .content
-        .clone();
-
-    // Apply the same filtering as individual messages
-    let mut combined_message_o: Vec<&str> = combined_message.split("</think>").collect();
-    let combined_message_t = combined_message_o.pop();
-    let combined_message = match combined_message_t {
-        Some(m) => m.to_string(),
-        None => combined_message
-    };
-    
-    let filtered_message = filter_unwanted_lines(&combined_message);
+    // Apply the same processing as individual messages
+    let reply = request_message(client, system_prompt, combination_prompt, settings).await?;
     
     // Add emote to the combined message based on categorization
-    let message_with_emote = emotes::process_commit_message(&filtered_message);
+    let message_with_emote = emotes::process_commit_message(&reply.content);
     
-    Ok(message_with_emote)
+    Ok(Reply {
+        content: message_with_emote,
+        usage: reply.usage,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use wiremock::{
-        matchers::{header, method, path},
+        matchers::{body_partial_json, header, method, path},
         Mock, MockServer, ResponseTemplate,
     };
 
@@ -345,9 +921,7 @@ mod tests {
             diff,
             system_prompt,
             user_prompt,
-            "test_token",
-            &mock_server.uri(),
-            model,
+            &RequestSettings::new("test_token", &mock_server.uri(), model),
         )
         .await?;
 
@@ -377,14 +951,12 @@ mod tests {
             "some diff",
             "system prompt",
             "user prompt",
-            "invalid_token",
-            &mock_server.uri(),
-            "gpt-3.5-turbo",
+            &RequestSettings::new("invalid_token", &mock_server.uri(), "gpt-3.5-turbo"),
         )
         .await;
 
         // Verify that we get an error
-        assert!(result.is_err());
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
         let err = result.unwrap_err().to_string();
         assert!(err.contains("API request failed"));
         assert!(err.contains("401"));
@@ -393,72 +965,541 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_generate_commit_message_fenced() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "Here's the commit message:\n```commit\nfeat: add login\n\nImplement OAuth2.\n```"
+                }
+            }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .mount(&mock_server)
+            .await;
+
+        let commit_message = generate_commit_message(
+            "some diff",
+            "system prompt",
+            "user prompt",
+            &RequestSettings::new("test_token", &mock_server.uri(), "gpt-3.5-turbo"),
+        )
+        .await?;
+
+        assert_eq!(commit_message, "feat: add login\n\nImplement OAuth2.");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_commit_message_html_response() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let settings = RequestSettings::new("test_token", &mock_server.uri(), "gpt-3.5-turbo");
+        let portal = ResponseTemplate::new(200)
+            .set_body_raw("<html><body>Please log in to the Wi-Fi</body></html>", "text/html");
+
+        // A portal page that keeps coming back fails with a clear error after one retry
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(portal.clone())
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let result = generate_commit_message("some diff", "system prompt", "user prompt", &settings).await;
+        match result {
+            Err(ApiError::NotJson { content_type, snippet }) => {
+                assert_eq!(content_type, "text/html");
+                assert!(snippet.contains("Please log in"));
+            }
+            other => panic!("expected NotJson, got {:?}", other),
+        }
+        mock_server.verify().await;
+        mock_server.reset().await;
+
+        // A one-off portal page is retried
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(portal)
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&mock_server)
+            .await;
+        let mock_response = serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": "fix: handle portal pages"}}]
+        });
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .mount(&mock_server)
+            .await;
+
+        let commit_message = generate_commit_message("some diff", "system prompt", "user prompt", &settings).await?;
+        assert_eq!(commit_message, "fix: handle portal pages");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_min_request_interval() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let mock_response = serde_json::json!({
+            "choices": [{"message": {"role": "assistant", "content": "fix: pace requests"}}]
+        });
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let mut settings = RequestSettings::new("test_token", &mock_server.uri(), "gpt-3.5-turbo");
+        settings.min_request_interval = Some(Duration::from_millis(150));
+
+        let started = Instant::now();
+        generate_commit_message("diff", "system prompt", "user prompt", &settings).await?;
+        let (first, second) = tokio::join!(
+            generate_commit_message("diff", "system prompt", "user prompt", &settings),
+            combine_commit_messages(vec!["a".to_string(), "b".to_string()], &[], "system prompt", &settings),
+        );
+        first?;
+        second?;
+
+        // Three requests, the later two each waiting for the interval, even concurrently
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_not_json() {
+        assert!(is_not_json(Some("text/html; charset=utf-8"), "<html></html>"));
+        assert!(!is_not_json(Some("application/json"), "{}"));
+        assert!(!is_not_json(Some("application/vnd.api+json"), "{}"));
+        assert!(is_not_json(None, "  <!DOCTYPE html>"));
+        assert!(!is_not_json(None, "{\"choices\": []}"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_response_with_multibyte_text() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        // The 100th byte from either end falls inside a two-byte character
+        let body = format!("{{\"error\": \"{}{}\" }}", "x".repeat(88), "é".repeat(100));
+        assert!(!body.is_char_boundary(100) && !body.is_char_boundary(body.len() - 100));
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+            .mount(&mock_server)
+            .await;
+
+        let result = generate_commit_message(
+            "some diff",
+            "system prompt",
+            "user prompt",
+            &RequestSettings::new("test_token", &mock_server.uri(), "gpt-3.5-turbo"),
+        )
+        .await;
+
+        match result {
+            Err(ApiError::InvalidResponse { snippet, .. }) => {
+                assert!(snippet.starts_with("{\"error\": \"xxx"));
+                assert!(snippet.ends_with("éé\" }"));
+            }
+            other => panic!("expected an invalid response error, got {:?}", other),
+        }
+
+        assert_eq!(response_snippet("héllo"), "héllo  ... héllo");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_commit_message_rate_limited() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("Retry-After", "30")
+                    .set_body_string("Too Many Requests"),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let result = generate_commit_message(
+            "some diff",
+            "system prompt",
+            "user prompt",
+            &RequestSettings::new("test_token", &mock_server.uri(), "gpt-3.5-turbo"),
+        )
+        .await;
+
+        match result {
+            Err(ApiError::RateLimited { retry_after, .. }) => {
+                assert_eq!(retry_after, Some(Duration::from_secs(30)));
+            }
+            _ => panic!("Expected ApiError::RateLimited"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_commit_message_structured() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "{\"type\": \"feat\", \"scope\": \"auth\", \"subject\": \"add login\", \"body\": \"Implement OAuth2.\"}"
+                }
+            }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "response_format": { "type": "json_object" }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let settings = RequestSettings {
+            structured_output: true,
+            ..RequestSettings::new("test_token", &mock_server.uri(), "gpt-4o")
+        };
+        let commit_message =
+            generate_commit_message("some diff", "system prompt", "user prompt", &settings).await?;
+
+        assert_eq!(commit_message, "feat(auth): add login\n\nImplement OAuth2.");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_commit_message_api_key_auth() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{
+                "message": { "role": "assistant", "content": "fix: handle empty config" }
+            }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(header("api-key", "azure_key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let settings = RequestSettings {
+            auth: AuthScheme::ApiKey,
+            timeout: Some(Duration::from_secs(5)),
+            ..RequestSettings::new("azure_key", &mock_server.uri(), "gpt-4o")
+        };
+        let commit_message =
+            generate_commit_message("some diff", "system prompt", "user prompt", &settings).await?;
+
+        assert_eq!(commit_message, "fix: handle empty config");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_models() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .and(header("Authorization", "Bearer test_token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "object": "list",
+                "data": [
+                    { "id": "gpt-4o-mini", "object": "model" },
+                    { "id": "llama3.2:latest", "object": "model" }
+                ]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let settings = RequestSettings::new("test_token", &mock_server.uri(), "gpt-4o-mini");
+        let models = list_models(&settings).await?;
+        assert_eq!(models, vec!["gpt-4o-mini", "llama3.2:latest"]);
+
+        assert!(model_listed(&models, "gpt-4o-mini"));
+        assert!(model_listed(&models, "llama3.2"));
+        assert!(!model_listed(&models, "gpt-4o-mni"));
+        assert!(!model_listed(&models, "gpt-4o"));
+
+        // A server without the endpoint
+        let settings = RequestSettings::new("test_token", "http://127.0.0.1:9", "gpt-4o-mini");
+        assert!(list_models(&settings).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_commit_message_deterministic() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{
+                "message": { "role": "assistant", "content": "fix: handle empty config" }
+            }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "temperature": 0.0,
+                "top_p": 1.0,
+                "seed": DETERMINISTIC_SEED
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let settings = RequestSettings {
+            deterministic: true,
+            ..RequestSettings::new("test_token", &mock_server.uri(), "gpt-4o")
+        };
+        let commit_message =
+            generate_commit_message("some diff", "system prompt", "user prompt", &settings).await?;
+
+        assert_eq!(commit_message, "fix: handle empty config");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_commit_message_structured_fallback() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        // The provider rejects response_format, the retry returns plain text
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "response_format": { "type": "json_object" }
+            })))
+            .respond_with(ResponseTemplate::new(400).set_body_string("response_format not supported"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{
+                "message": { "role": "assistant", "content": "fix: handle empty config" }
+            }]
+        });
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let settings = RequestSettings {
+            structured_output: true,
+            ..RequestSettings::new("test_token", &mock_server.uri(), "local-model")
+        };
+        let commit_message =
+            generate_commit_message("some diff", "system prompt", "user prompt", &settings).await?;
+
+        assert_eq!(commit_message, "fix: handle empty config");
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_length_error() {
+        let body = r#"{"error":{"code":"context_length_exceeded"}}"#.to_string();
+        assert!(matches!(
+            status_error(StatusCode::BAD_REQUEST, None, body),
+            ApiError::ContextLengthExceeded { .. }
+        ));
+        assert!(matches!(
+            status_error(StatusCode::BAD_REQUEST, None, "invalid model".to_string()),
+            ApiError::Status { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_structured_message() {
+        assert_eq!(
+            parse_structured_message("```json\n{\"type\": \"docs\", \"scope\": null, \"subject\": \"fix typo\", \"body\": \"\"}\n```"),
+            Some("docs: fix typo".to_string())
+        );
+        assert_eq!(parse_structured_message("docs: fix typo"), None);
+        assert_eq!(
+            parse_structured_message("{\"type\": \"\", \"subject\": \"fix typo\"}"),
+            None
+        );
+    }
+
     #[test]
     fn test_filter_unwanted_lines() {
         // Test filtering of common LLM meta-commentary that starts lines
         let input = "Based on the diff, this commit adds a new feature.\nfeat: add user authentication\n\nThis commit implements login functionality.";
         let expected = "feat: add user authentication\n\nThis commit implements login functionality.";
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
 
         // Test filtering of analysis phrases that start lines
         let input = "Here's a breakdown of the changes:\n- feat: add new component\n- Updated styling\n\nThe diff shows significant improvements.";
         let expected = "- feat: add new component\n- Updated styling";
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
 
         // Test preservation of valid commit content
         let input = "feat: implement user dashboard\n\nAdd comprehensive user dashboard with:\n- Profile management\n- Settings panel\n- Activity history";
         let expected = input; // Should remain unchanged
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
 
         // Test filtering of multiple unwanted patterns
         let input = "Looking at the changes, I can see that this diff indicates:\nfix: resolve authentication bug\n\nAnalysis of the changes shows this fixes the login issue.";
         let expected = "fix: resolve authentication bug";
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
 
         // Test handling of empty lines and whitespace
         let input = "\n\nBased on the diff:\nfeat: add new feature\n\n";
         let expected = "feat: add new feature";
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
 
         // Test case insensitive filtering
         let input = "the diff shows that we need:\nfeat: update configuration\nFrom what I can see, this is important.";
         let expected = "feat: update configuration";
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
 
         // Test filtering with bullet points
         let input = "- Based on the diff, this adds features\n- feat: implement search\n- The changes show improvements";
         let expected = "- feat: implement search";
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
 
         // Test that valid content with similar words is preserved
         let input = "feat: add diff viewer component\n\nImplements a new diff viewer that shows changes clearly.";
         let expected = input; // Should remain unchanged as "diff" here is part of valid content
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
 
         // Test filtering of meta-commentary about commits
         let input = "This commit appears to be adding a new feature based on the analysis.\nfeat: add search functionality\n\nImplement search with filters.";
         let expected = "feat: add search functionality\n\nImplement search with filters.";
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
+
+        // A "this commit" sentence in the body is content, even when it says "seems"
+        let input = "fix: lock the session cache\n\nThis commit seems small but fixes a real race.";
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), input);
+        let input = "This commit seems small but fixes a real race.\n\nfix: lock the session cache";
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), input);
+
+        // Only a leading hedge about what the commit does is dropped
+        let input = "This commit appears to add retries.\nfix: retry failed uploads";
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), "fix: retry failed uploads");
+        let input = "fix: retry failed uploads\n\nThis commit appears to add retries.";
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), input);
     }
 
     #[test]
     fn test_filter_unwanted_lines_edge_cases() {
         // Test empty input
-        assert_eq!(filter_unwanted_lines(""), "");
+        assert_eq!(filter_unwanted_lines("", &LineFilter::default()), "");
 
         // Test input with only unwanted content
         let input = "Based on the diff, here's what I can see from the analysis.";
-        assert_eq!(filter_unwanted_lines(input), "");
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), "");
 
         // Test input with mixed valid and invalid content
         let input = "The diff indicates:\nfeat: add authentication\n\nBased on the changes:\nfix: resolve bug\n\nLooking at this diff:";
         let expected = "The diff indicates:\nfeat: add authentication\n\nfix: resolve bug";
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
 
         // Test very short lines that shouldn't be filtered
         let input = "fix: bug\nThe fix works.";
         let expected = input; // Should remain unchanged
-        assert_eq!(filter_unwanted_lines(input), expected);
+        assert_eq!(filter_unwanted_lines(input, &LineFilter::default()), expected);
+    }
+
+    #[test]
+    fn test_filter_unwanted_lines_custom_patterns() {
+        let input = "feat: add retries\n\nBased on user feedback, retry failed uploads.\nNote: internal";
+        let filter = LineFilter {
+            enabled: true,
+            patterns: vec!["note:".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            filter_unwanted_lines(input, &filter),
+            "feat: add retries\n\nBased on user feedback, retry failed uploads."
+        );
+
+        let disabled = LineFilter {
+            enabled: false,
+            ..Default::default()
+        };
+        assert_eq!(filter_unwanted_lines(input, &disabled), input);
+    }
+
+    #[test]
+    fn test_strip_leading_reasoning() {
+        let reply = "Okay, the diff touches the upload client and adds a retry loop around the\n\
+                     request. The tests cover the backoff, so this is a new feature.\n\
+                     \n\
+                     feat(upload): retry failed uploads\n\
+                     \n\
+                     Retry up to three times with exponential backoff.";
+        assert_eq!(
+            strip_leading_reasoning(reply),
+            "feat(upload): retry failed uploads\n\nRetry up to three times with exponential backoff."
+        );
+
+        // A message that starts right away keeps its conventional-looking body lines
+        let message = "feat: add retries\n\nfix: the old loop never stopped";
+        assert_eq!(strip_leading_reasoning(message), message);
+
+        // Without a blank line before it, or without any conventional line, nothing is dropped
+        let unseparated = "Thinking about it\nfeat: add retries";
+        assert_eq!(strip_leading_reasoning(unseparated), unseparated);
+        let plain = "Add retries\n\nRetry failed uploads.";
+        assert_eq!(strip_leading_reasoning(plain), plain);
+    }
+
+    #[test]
+    fn test_load_ca_cert() {
+        const CERT: &str = "-----BEGIN CERTIFICATE-----\n\
+                            MIIBkDCCATWgAwIBAgIUJQJx5oTVRnMbH38Joib/b585bZQwCgYIKoZIzj0EAwIw\n\
+                            HDEaMBgGA1UEAwwRZ2l0LW5hcnJhdG9yIHRlc3QwIBcNMjYxMDE2MTAzNTI1WhgP\n\
+                            MjEyNjA5MjIxMDM1MjVaMBwxGjAYBgNVBAMMEWdpdC1uYXJyYXRvciB0ZXN0MFkw\n\
+                            EwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEIeLLl2P5EHjELqrfMmAq1vYxR+ntNpEr\n\
+                            Ym4kLHWesqCIzceY7xSIMQUxrpMqCu1/6UXggqRr6fCSHJrH+vffF6NTMFEwHQYD\n\
+                            VR0OBBYEFAC0fUMpHhwnE6Vnw4dcUcfYUH0FMB8GA1UdIwQYMBaAFAC0fUMpHhwn\n\
+                            E6Vnw4dcUcfYUH0FMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSQAwRgIh\n\
+                            ALrux4jr4+3wZIUeqigF4F7vLO8DU5nqgntlgkbvIWgVAiEA0X+x9UP02GCzJeds\n\
+                            s/hOSVNt4Z/IqElOG/noX1+NC7c=\n\
+                            -----END CERTIFICATE-----\n";
+        let dir = tempfile::Builder::new().prefix("test_load_ca_cert").tempdir().unwrap();
+        let cert_path = dir.path().join("ca.pem");
+        fs::write(&cert_path, CERT).unwrap();
+        let settings = RequestSettings {
+            http2_only: true,
+            pool_idle_timeout: Some(Duration::from_secs(30)),
+            ca_cert: Some(load_ca_cert(&cert_path).unwrap()),
+            ..RequestSettings::new("token", "https://gateway.internal", "model")
+        };
+        let client = settings.client().unwrap();
+        assert!(std::ptr::eq(client, settings.client().unwrap()));
+
+        let garbage_path = dir.path().join("garbage.pem");
+        fs::write(&garbage_path, "-----BEGIN CERTIFICATE-----\nnot base64\n-----END CERTIFICATE-----\n").unwrap();
+        let err = load_ca_cert(&garbage_path).unwrap_err();
+        assert!(matches!(err, ApiError::CaCert { .. }));
+        assert!(err.to_string().contains("not a valid PEM or DER certificate"));
+
+        let missing_path = dir.path().join("missing.pem");
+        let err = load_ca_cert(&missing_path).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("Failed to load the CA certificate {}", missing_path.display())));
     }
 
     #[tokio::test]
@@ -492,10 +1533,9 @@ mod tests {
 
         let combined_message = combine_commit_messages(
             messages,
+            &[],
             "You are a helpful assistant.",
-            "test_token",
-            &mock_server.uri(),
-            "gpt-3.5-turbo",
+            &RequestSettings::new("test_token", &mock_server.uri(), "gpt-3.5-turbo"),
         )
         .await?;
 
@@ -507,16 +1547,80 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_combine_commit_messages_strips_emotes() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let mock_response = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "feat: add a\n\n- feat: a\n- fix: b"
+                }
+            }]
+        });
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .mount(&mock_server)
+            .await;
+
+        let combined_message = combine_commit_messages(
+            vec!["✨ feat: a".to_string(), "🐛 fix: b".to_string()],
+            &["File: src/a.rs".to_string(), "File: src/b.rs".to_string()],
+            "You are a helpful assistant.",
+            &RequestSettings::new("test_token", &mock_server.uri(), "gpt-3.5-turbo"),
+        )
+        .await?;
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let prompt = String::from_utf8_lossy(&requests[0].body).to_string();
+        assert!(prompt.contains("--- Commit 1 ---\\n(File: src/a.rs)\\nfeat: a\\n\\n--- Commit 2 ---"));
+        assert!(prompt.contains("--- Commit 2 ---\\n(File: src/b.rs)\\nfix: b"));
+        assert!(!prompt.contains('✨') && !prompt.contains('🐛'));
+
+        let (subject, body) = combined_message.split_once("\n\n").unwrap();
+        assert_eq!(subject, "✨ feat: add a");
+        assert!(!body.contains('✨') && !body.contains('🐛'));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_combine_commit_messages_separator() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{ "message": { "role": "assistant", "content": "feat: add a and b" } }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let settings = RequestSettings {
+            combine_separator: "=== Message {n} of the split diff ===".to_string(),
+            ..RequestSettings::new("test_token", &mock_server.uri(), "gpt-3.5-turbo")
+        };
+        let messages = vec![
+            "feat: add a\n\nMessage body mentioning Message 2".to_string(),
+            "feat: add b".to_string(),
+        ];
+        combine_commit_messages(messages, &[], "system prompt", &settings).await?;
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let prompt = String::from_utf8_lossy(&requests[0].body).to_string();
+        assert!(prompt.contains("=== Message 1 of the split diff ===\\nfeat: add a\\n\\nMessage body mentioning Message 2"));
+        assert!(prompt.contains("\\n\\n=== Message 2 of the split diff ===\\nfeat: add b"));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_combine_commit_messages_single_message() -> Result<()> {
         let messages = vec!["feat: add new feature".to_string()];
         
         let result = combine_commit_messages(
             messages.clone(),
+            &[],
             "system prompt",
-            "token",
-            "http://example.com",
-            "model",
+            &RequestSettings::new("token", "http://example.com", "model"),
         )
         .await?;
 
@@ -530,10 +1634,9 @@ mod tests {
         
         let result = combine_commit_messages(
             messages,
+            &[],
             "system prompt",
-            "token",
-            "http://example.com",
-            "model",
+            &RequestSettings::new("token", "http://example.com", "model"),
         )
         .await;
 
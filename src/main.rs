@@ -1,36 +1,54 @@
-mod cli;
-mod commands;
-mod config;
-mod emotes;
-mod git;
-mod llm;
-mod ui;
-
 use color_eyre::eyre::Result;
 
-use cli::parse_args;
-use config::Config;
+use git_narrator::cli::parse_args;
+use git_narrator::commands;
+use git_narrator::git;
+use git_narrator::Config;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     color_eyre::install()?;
 
-    // Load configuration
-    let config = Config::load()?;
-
     // Parse CLI arguments
     let cli = parse_args();
 
-    // Process commands or default behavior
-    match &cli.command {
-        Some(command) => {
-            commands::handle_commands(command, &config).await?;
-        }
-        None => {
-            // No subcommand provided, default to generate behavior using cli directly
-            commands::generate_commit(&config, cli.auto_add, cli.auto_commit, cli.auto_push)
-                .await?;
+    // Load configuration, from exactly one file when --config is given
+    let config = match &cli.config {
+        Some(path) => Config::load_from(path)?,
+        None => Config::load()?,
+    };
+
+    // Fail early with an install hint when git is missing, instead of on the first git call
+    let git_check = if commands::needs_git(cli.command.as_ref()) {
+        git::check_installed()
+    } else {
+        Ok(())
+    };
+    let result = if let Err(err) = git_check {
+        Err(err.into())
+    } else {
+        // Process commands or default behavior
+        match &cli.command {
+            // Top-level flags such as --offline apply to subcommands too
+            Some(command) => match commands::subcommand_config(&cli, &config) {
+                Ok(config) => commands::handle_commands(command, &config).await,
+                Err(err) => Err(err),
+            },
+            None => {
+                // No subcommand provided, default to generate behavior using cli directly
+                let options = commands::GenerateOptions::from_cli(&cli);
+                commands::generate_commit(&config, &options).await
+            }
         }
+    };
+
+    // Distinct exit codes let scripts branch on the failure reason
+    let code = commands::exit_code(&result);
+    if let Err(err) = result {
+        eprintln!("Error: {:?}", err);
+    }
+    if code != 0 {
+        std::process::exit(code);
     }
 
     Ok(())
@@ -0,0 +1,532 @@
+//! Post-processing of generated commit messages
+//!
+//! These helpers reshape the text returned by the model (after meta-commentary has been
+//! filtered out) according to user settings.
+
+use crate::emotes;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::fmt;
+use std::str::FromStr;
+
+/// Marker appended to a body that was truncated
+pub const TRUNCATION_MARKER: &str = "…";
+
+/// How the body of a commit message should be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyStyle {
+    /// Each body line is a `- ` bullet
+    Bullets,
+    /// Prose paragraphs
+    Paragraph,
+}
+
+impl FromStr for BodyStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bullets" => Ok(Self::Bullets),
+            "paragraph" => Ok(Self::Paragraph),
+            other => Err(format!("unknown body style '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for BodyStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bullets => write!(f, "bullets"),
+            Self::Paragraph => write!(f, "paragraph"),
+        }
+    }
+}
+
+/// How the messages generated for the chunks of a split diff become one message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CombineStrategy {
+    /// Ask the model to merge them into one message
+    #[default]
+    Llm,
+    /// List them verbatim under one subject, without an API call
+    Concat,
+}
+
+impl FromStr for CombineStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "llm" => Ok(Self::Llm),
+            "concat" => Ok(Self::Concat),
+            other => Err(format!("unknown combine strategy '{}' (expected llm or concat)", other)),
+        }
+    }
+}
+
+impl fmt::Display for CombineStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Llm => write!(f, "llm"),
+            Self::Concat => write!(f, "concat"),
+        }
+    }
+}
+
+/// A rule that a finished commit message breaks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// Nothing but the emote is left after filtering
+    Empty,
+    /// The message is shorter than `min_message_length`, not counting the emote
+    TooShort { length: usize, min: usize },
+    /// The message is a template placeholder such as `[commit message]`
+    Placeholder,
+    /// The subject is longer than `max_subject_length`
+    SubjectTooLong { length: usize, max: usize },
+    /// The message isn't a conventional commit although `require_conventional` is set
+    NotConventional,
+    /// The subject's type isn't one of `conventional.allowed_types`
+    TypeNotAllowed { kind: String, allowed: Vec<String> },
+    /// The subject doesn't match `subject.pattern`
+    SubjectPattern { subject: String, pattern: String },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "the message is empty"),
+            Self::TooShort { length, min } => {
+                write!(f, "the message is {} characters long (min_message_length is {})", length, min)
+            }
+            Self::Placeholder => write!(f, "the message is a placeholder, not a description of the changes"),
+            Self::SubjectTooLong { length, max } => {
+                write!(f, "the subject is {} characters long (max_subject_length is {})", length, max)
+            }
+            Self::NotConventional => write!(
+                f,
+                "the message is not a conventional commit (type(scope): description) and require_conventional is set"
+            ),
+            Self::TypeNotAllowed { kind, allowed } => write!(
+                f,
+                "the type '{}' is not one of conventional.allowed_types ({})",
+                kind,
+                allowed.join(", ")
+            ),
+            Self::SubjectPattern { subject, pattern } => {
+                write!(f, "the subject '{}' does not match subject.pattern ({})", subject, pattern)
+            }
+        }
+    }
+}
+
+/// Placeholders models sometimes return instead of a message, compared in lowercase
+const PLACEHOLDERS: &[&str] = &[
+    "commit message here",
+    "your commit message",
+    "<type>",
+    "<description>",
+    "type(scope): description",
+    "type: description",
+];
+
+/// What makes a model reply unusable as a commit message: too few characters besides the
+/// emote, or a placeholder such as `[commit message]` or `<type>: <description>`
+pub fn unusable(message: &str, min_length: usize) -> Option<Violation> {
+    let text = emotes::strip_leading_emotes(message);
+    let text = text.trim();
+    let length = text.chars().count();
+    if length < min_length {
+        return Some(Violation::TooShort { length, min: min_length });
+    }
+
+    let lower = text.to_lowercase();
+    let bracketed = [('[', ']'), ('<', '>'), ('{', '}')]
+        .iter()
+        .any(|&(open, close)| lower.starts_with(open) && lower.ends_with(close) && !lower.contains('\n'));
+    (bracketed || PLACEHOLDERS.iter().any(|placeholder| lower.contains(placeholder))).then_some(Violation::Placeholder)
+}
+
+/// Add fixed text before the subject and after the message (`--prepend`/`--append`)
+///
+/// The prepended text goes at the very start, or right after a leading emote with
+/// `after_emote`. Text that is already there isn't added again.
+pub fn decorate(message: &str, prepend: Option<&str>, append: Option<&str>, after_emote: bool) -> String {
+    let mut message = message.to_string();
+    if let Some(prepend) = prepend.filter(|prepend| !prepend.is_empty()) {
+        message = match emotes::split_leading_emote(&message).filter(|_| after_emote) {
+            Some((emote, rest)) if !rest.starts_with(prepend) => format!("{} {}{}", emote, prepend, rest),
+            Some(_) => message,
+            None if message.starts_with(prepend) => message,
+            None => format!("{}{}", prepend, message),
+        };
+    }
+    if let Some(append) = append.filter(|append| !append.is_empty()) {
+        if !message.ends_with(append) {
+            message.push_str(append);
+        }
+    }
+    message
+}
+
+/// End a message with exactly one newline, or with none
+pub fn with_trailing_newline(message: &str, trailing_newline: bool) -> String {
+    let message = message.trim_end_matches(['\n', '\r']);
+    if trailing_newline {
+        format!("{}\n", message)
+    } else {
+        message.to_string()
+    }
+}
+
+/// Split a message into its subject (first non-empty line) and the remaining body lines
+pub fn split_subject(message: &str) -> (&str, Vec<&str>) {
+    let mut lines = message.lines().skip_while(|line| line.trim().is_empty());
+    let subject = lines.next().unwrap_or("");
+    (subject, lines.collect())
+}
+
+/// Keep only the subject line of a message, dropping any body
+pub fn subject_only(message: &str) -> String {
+    split_subject(message).0.trim().to_string()
+}
+
+/// Rewrite the body of a message as `- ` bullets
+///
+/// Existing list items (`-`, `*`, `•`, `1.`, `1)`) get a `- ` marker and indented lines
+/// are kept as continuations. Prose paragraphs are split into one bullet per sentence.
+pub fn format_body_as_bullets(message: &str) -> String {
+    let (subject, body) = split_subject(message);
+    let body = body.join("\n");
+
+    let mut bullets = Vec::new();
+    for paragraph in body.split("\n\n").map(str::trim_end).filter(|p| !p.trim().is_empty()) {
+        let lines: Vec<&str> = paragraph.lines().filter(|line| !line.trim().is_empty()).collect();
+        let is_list = lines.iter().enumerate().all(|(i, line)| {
+            list_item_text(line).is_some() || (i > 0 && line.starts_with(char::is_whitespace))
+        });
+
+        if is_list {
+            for line in lines {
+                match list_item_text(line) {
+                    Some(text) => bullets.push(format!("- {}", text)),
+                    None => bullets.push(format!("  {}", line.trim())),
+                }
+            }
+        } else {
+            let text = lines.iter().map(|line| line.trim()).collect::<Vec<_>>().join(" ");
+            bullets.extend(split_sentences(&text).into_iter().map(|s| format!("- {}", s)));
+        }
+    }
+
+    if bullets.is_empty() {
+        return subject.to_string();
+    }
+    format!("{}\n\n{}", subject, bullets.join("\n"))
+}
+
+/// The text of a list item line without its marker, or `None` if the line isn't one
+fn list_item_text(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    for marker in ["- ", "* ", "• "] {
+        if let Some(text) = line.strip_prefix(marker) {
+            return Some(text.trim());
+        }
+    }
+
+    // Numbered items: "1. text" or "1) text"
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 {
+        let rest = &line[digits..];
+        if let Some(text) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+            return Some(text.trim());
+        }
+    }
+    None
+}
+
+/// Split prose into sentences at `.`, `!` or `?` followed by a space and an uppercase letter
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        current.push(c);
+        if matches!(c, '.' | '!' | '?') {
+            let mut lookahead = chars.clone();
+            if lookahead.next() == Some(' ') && lookahead.next().is_some_and(char::is_uppercase) {
+                sentences.push(current.trim().to_string());
+                current.clear();
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        sentences.push(current.trim().to_string());
+    }
+    sentences
+}
+
+/// Truncate the body of a message to at most `max_lines` non-empty lines
+///
+/// The subject line is never counted. If lines were dropped, a truncation marker is
+/// appended; with `max_lines == 0` the body is removed entirely.
+pub fn truncate_body(message: &str, max_lines: usize) -> String {
+    let (subject, body) = split_subject(message);
+
+    let total_lines = body.iter().filter(|line| !line.trim().is_empty()).count();
+    if total_lines <= max_lines {
+        return message.to_string();
+    }
+
+    if max_lines == 0 {
+        return subject.to_string();
+    }
+
+    let mut kept = Vec::new();
+    let mut non_empty = 0;
+    for line in body {
+        if non_empty == max_lines {
+            break;
+        }
+        if !line.trim().is_empty() {
+            non_empty += 1;
+        }
+        kept.push(line);
+    }
+
+    format!(
+        "{}\n{}\n{}",
+        subject,
+        kept.join("\n").trim_end(),
+        TRUNCATION_MARKER
+    )
+}
+
+/// Combine chunk messages locally: each message's subject becomes a `- ` bullet, with its body
+/// lines indented below it
+///
+/// The subject is taken from the first message of the most common category, so a split
+/// `feat` change with one `docs` chunk is still a `feat` commit.
+pub fn concat_messages(messages: &[String]) -> String {
+    let categories: Vec<_> = messages
+        .iter()
+        .map(|message| emotes::categorize_commit_message(message))
+        .collect();
+    let count = |i: usize| categories.iter().filter(|&category| *category == categories[i]).count();
+    let Some(lead) = (0..messages.len()).max_by_key(|&i| (count(i), Reverse(i))) else {
+        return String::new();
+    };
+
+    let mut lines = vec![subject_only(&messages[lead]), String::new()];
+    for message in messages {
+        let (subject, body) = split_subject(message);
+        lines.push(format!("- {}", subject.trim()));
+        lines.extend(
+            body.iter()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| format!("  {}", line.trim())),
+        );
+    }
+    lines.join("\n")
+}
+
+/// Unwrap a message that is entirely enclosed in a Markdown code fence
+///
+/// Models sometimes wrap the whole message in ```` ``` ```` (optionally with an info string
+/// such as ```` ```commit ````). Only a single fence around the complete message is
+/// removed; code blocks inside a body are left untouched.
+pub fn unwrap_code_fence(message: &str) -> String {
+    let trimmed = message.trim();
+    let lines: Vec<&str> = trimmed.lines().collect();
+    if lines.len() < 2 {
+        return message.to_string();
+    }
+
+    // Opening fence: three or more backticks, optionally followed by an info string
+    let opening = lines[0].trim();
+    let fence_len = opening.chars().take_while(|&c| c == '`').count();
+    let info = &opening[fence_len..];
+    if fence_len < 3 || info.contains('`') || info.trim().contains(char::is_whitespace) {
+        return message.to_string();
+    }
+
+    // The first closing fence must be the last line, otherwise the fence only covers part
+    // of the message
+    let is_closing = |line: &str| {
+        let line = line.trim();
+        line.len() >= fence_len && line.chars().all(|c| c == '`')
+    };
+    match lines[1..].iter().position(|line| is_closing(line)) {
+        Some(position) if position + 1 == lines.len() - 1 => {
+            lines[1..lines.len() - 1].join("\n").trim().to_string()
+        }
+        _ => message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unwrap_code_fence() {
+        assert_eq!(unwrap_code_fence("```\nfeat: x\n```"), "feat: x");
+        assert_eq!(
+            unwrap_code_fence("```commit\nfeat: add login\n\nImplement OAuth2.\n```\n"),
+            "feat: add login\n\nImplement OAuth2."
+        );
+        assert_eq!(unwrap_code_fence("````\nfix: y\n````"), "fix: y");
+    }
+
+    #[test]
+    fn test_unwrap_code_fence_keeps_body_code_blocks() {
+        // A real code block inside the body must be preserved
+        let message = "feat: add config parser\n\nExample:\n```toml\nkey = 1\n```";
+        assert_eq!(unwrap_code_fence(message), message);
+
+        // Fenced subject followed by more content is not a single wrapped block
+        let message = "```\nfeat: x\n```\n\nSee ```code``` above\n```";
+        assert_eq!(unwrap_code_fence(message), message);
+
+        // Plain messages are unchanged
+        assert_eq!(unwrap_code_fence("fix: typo"), "fix: typo");
+    }
+
+    #[test]
+    fn test_split_subject() {
+        let (subject, body) = split_subject("\nfeat: add login\n\n- first\n- second");
+        assert_eq!(subject, "feat: add login");
+        assert_eq!(body, vec!["", "- first", "- second"]);
+
+        let (subject, body) = split_subject("");
+        assert_eq!(subject, "");
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn test_truncate_body() {
+        let body: Vec<String> = (1..=10).map(|i| format!("{}. change {}", i, i)).collect();
+        let message = format!("feat: add many things\n\n{}", body.join("\n"));
+
+        assert_eq!(
+            truncate_body(&message, 3),
+            "feat: add many things\n\n1. change 1\n2. change 2\n3. change 3\n…"
+        );
+    }
+
+    #[test]
+    fn test_truncate_body_within_limit() {
+        let message = "fix: typo\n\nCorrect a typo in the README.";
+        assert_eq!(truncate_body(message, 3), message);
+        assert_eq!(truncate_body("fix: typo", 0), "fix: typo");
+    }
+
+    #[test]
+    fn test_truncate_body_to_zero() {
+        let message = "fix: typo\n\nCorrect a typo in the README.";
+        assert_eq!(truncate_body(message, 0), "fix: typo");
+    }
+
+    #[test]
+    fn test_format_body_as_bullets_from_paragraph() {
+        let message = "feat(auth): add OAuth2 login\n\n\
+                       Add Google and GitHub providers. Store refresh tokens\n\
+                       securely, e.g. in the keychain.\n\n\
+                       Update the user model.";
+        assert_eq!(
+            format_body_as_bullets(message),
+            "feat(auth): add OAuth2 login\n\n\
+             - Add Google and GitHub providers.\n\
+             - Store refresh tokens securely, e.g. in the keychain.\n\
+             - Update the user model."
+        );
+    }
+
+    #[test]
+    fn test_format_body_as_bullets_from_list() {
+        let message = "refactor: split parser\n\n1. Move lexer\n   into its own module\n2) Add tests\n* Fix docs";
+        assert_eq!(
+            format_body_as_bullets(message),
+            "refactor: split parser\n\n- Move lexer\n  into its own module\n- Add tests\n- Fix docs"
+        );
+        assert_eq!(format_body_as_bullets("fix: typo"), "fix: typo");
+        assert_eq!("bullets".parse::<BodyStyle>(), Ok(BodyStyle::Bullets));
+        assert!("list".parse::<BodyStyle>().is_err());
+    }
+
+    #[test]
+    fn test_subject_only() {
+        assert_eq!(
+            subject_only("\n  feat: add login  \n\n- add form\n- add session"),
+            "feat: add login"
+        );
+        assert_eq!(subject_only("fix: typo"), "fix: typo");
+    }
+
+    #[test]
+    fn test_with_trailing_newline() {
+        assert_eq!(with_trailing_newline("fix: typo", true), "fix: typo\n");
+        assert_eq!(with_trailing_newline("fix: typo\n\n", true), "fix: typo\n");
+        assert_eq!(with_trailing_newline("fix: typo\r\n", false), "fix: typo");
+        assert_eq!(with_trailing_newline("fix: typo\n\nBody", false), "fix: typo\n\nBody");
+    }
+
+    #[test]
+    fn test_unusable() {
+        assert_eq!(unusable("✨", 5), Some(Violation::TooShort { length: 0, min: 5 }));
+        assert_eq!(unusable("✨ wip", 5), Some(Violation::TooShort { length: 3, min: 5 }));
+        assert_eq!(unusable("[commit message]", 5), Some(Violation::Placeholder));
+        assert_eq!(unusable("🔧 <type>: <description>", 5), Some(Violation::Placeholder));
+        assert_eq!(unusable("Your commit message here", 5), Some(Violation::Placeholder));
+
+        assert_eq!(unusable("fix: typo", 5), None);
+        assert_eq!(unusable("feat: add [WIP] prefix option", 5), None);
+        assert_eq!(unusable("wip", 0), None);
+    }
+
+    #[test]
+    fn test_decorate() {
+        let message = "✨ feat: add login\n\nBody";
+        assert_eq!(
+            decorate(message, Some("[WIP] "), Some("\n\nRefs #123"), false),
+            "[WIP] ✨ feat: add login\n\nBody\n\nRefs #123"
+        );
+        assert_eq!(
+            decorate(message, Some("[WIP] "), None, true),
+            "✨ [WIP] feat: add login\n\nBody"
+        );
+        assert_eq!(decorate("fix: typo", Some("[WIP] "), None, true), "[WIP] fix: typo");
+
+        // Applying the same decorations again changes nothing
+        let decorated = decorate(message, Some("[WIP] "), Some(" (draft)"), true);
+        assert_eq!(decorate(&decorated, Some("[WIP] "), Some(" (draft)"), true), decorated);
+        assert_eq!(decorate(message, None, None, false), message);
+    }
+
+    #[test]
+    fn test_concat_messages() {
+        let messages = vec![
+            "docs: describe the parser".to_string(),
+            "feat(parser): add expression parser\n\nSupports precedence climbing.".to_string(),
+            "feat(lexer): add tokenizer".to_string(),
+        ];
+        assert_eq!(
+            concat_messages(&messages),
+            "feat(parser): add expression parser\n\n\
+             - docs: describe the parser\n\
+             - feat(parser): add expression parser\n  \
+             Supports precedence climbing.\n\
+             - feat(lexer): add tokenizer"
+        );
+
+        // Ties go to the earliest message
+        let messages = vec!["fix: handle empty input".to_string(), "docs: add usage".to_string()];
+        assert!(concat_messages(&messages).starts_with("fix: handle empty input\n\n- fix:"));
+
+        assert_eq!("concat".parse::<CombineStrategy>(), Ok(CombineStrategy::Concat));
+        assert!("merge".parse::<CombineStrategy>().is_err());
+    }
+}
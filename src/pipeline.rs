@@ -0,0 +1,1979 @@
+//! High-level commit message generation pipeline
+//!
+//! Ties together diff splitting, per-chunk generation, combination and emote processing.
+//! Progress is reported through a callback so the CLI can print it while library users
+//! can stay silent.
+
+use crate::config::Config;
+use crate::emotes::{self, CommitCategory, EmoteMulti, EmoteOptions};
+use crate::error::ApiError;
+use crate::generator::{CommitGenerator, OpenAiGenerator};
+use crate::git::{self, BranchCommit, DiffChunk, SplitDiffResult, SubmoduleUpdate};
+use crate::llm::{self, LineFilter, Message, RequestSettings, Usage};
+use crate::message::{self, BodyStyle, CombineStrategy, Violation};
+use crate::prompt::{self, PromptContext};
+use color_eyre::eyre::{eyre, Context, Report, Result};
+use regex::Regex;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Size below which a chunk that exceeds the context length is not split any further
+const MIN_RESPLIT_SIZE: usize = 1000;
+
+/// Progress events emitted while generating a commit message
+#[derive(Debug)]
+pub enum Progress<'a> {
+    /// The diff is large and is about to be split
+    Splitting,
+    /// The diff was split into chunks
+    Split(&'a SplitDiffResult),
+    /// A message is being generated for the chunk at `index` (0-based)
+    Chunk { index: usize, description: &'a str },
+    /// A request finished: `description` is the chunk's, or "Combine" for the combine step
+    Completed {
+        description: &'a str,
+        latency: Duration,
+        usage: Option<Usage>,
+    },
+    /// A chunk exceeded the model's context length and was split into `chunks` pieces
+    Resplit { description: &'a str, chunks: usize },
+    /// Chunk messages are being combined into a single message
+    Combining,
+    /// A message is being generated for the whole diff
+    Generating,
+    /// The message wasn't a conventional commit and is being reformatted
+    Reformatting,
+    /// The message was a placeholder or too short and is being generated again
+    Retrying,
+}
+
+/// Latency and token usage of one API call, as shown by `--report`
+#[derive(Debug, Clone)]
+pub struct CallStats {
+    pub description: String,
+    pub latency: Duration,
+    pub usage: Option<Usage>,
+}
+
+/// The outcome of generating a message with one model, as compared by `bench`
+#[derive(Debug)]
+pub struct BenchRun {
+    pub model: String,
+    /// The message, or the error that stopped this model
+    pub message: Result<String, String>,
+    pub calls: Vec<CallStats>,
+}
+
+/// Generate a message for `diff` with each of `models` in turn, recording the API calls of
+/// each run
+///
+/// Every run uses the configuration with only the model changed. A model that fails doesn't
+/// stop the others; its error is kept in its run.
+pub async fn bench(config: &Config, models: &[String], diff: &str, context: &PromptContext) -> Vec<BenchRun> {
+    let mut runs = Vec::new();
+    for model in models {
+        let model_config = Config {
+            model: Some(model.clone()),
+            ..config.clone()
+        };
+        let mut calls = Vec::new();
+        let message = generate_with_progress(&model_config, diff, context, |progress| {
+            if let Progress::Completed { description, latency, usage } = progress {
+                calls.push(CallStats {
+                    description: description.to_string(),
+                    latency,
+                    usage,
+                });
+            }
+        })
+        .await
+        .map_err(|err| format!("{:#}", err));
+        runs.push(BenchRun {
+            model: model.clone(),
+            message,
+            calls,
+        });
+    }
+    runs
+}
+
+/// Request settings for the configured API, model and output handling
+pub fn request_settings(config: &Config) -> Result<RequestSettings> {
+    let api_token = if config.requires_api_token() {
+        config.get_api_token()?.clone()
+    } else {
+        config.api_token.clone().unwrap_or_default()
+    };
+
+    Ok(RequestSettings {
+        api_token,
+        api_base_url: config.get_api_base_url().to_string(),
+        model: config.get_model().to_string(),
+        filter: config.get_line_filter(),
+        structured_output: config.get_structured_output(),
+        deterministic: config.get_deterministic(),
+        auth: config.get_auth_scheme(),
+        timeout: config.get_request_timeout(),
+        min_request_interval: config.get_min_request_interval(),
+        http2_only: config.get_http2_only(),
+        pool_idle_timeout: config.get_pool_idle_timeout(),
+        ca_cert: config.ca_cert_path.as_deref().map(llm::load_ca_cert).transpose()?,
+        combine_separator: config.get_combine_separator().to_string(),
+        client: Default::default(),
+    })
+}
+
+/// Request settings for prose: plain-text replies without the commit message line filter
+fn prose_settings(config: &Config) -> Result<RequestSettings> {
+    let settings = request_settings(config)?;
+    Ok(RequestSettings {
+        structured_output: false,
+        filter: LineFilter {
+            enabled: false,
+            ..settings.filter.clone()
+        },
+        ..settings
+    })
+}
+
+/// Generate a commit message (with emote) for a diff
+pub async fn generate(config: &Config, diff: &str) -> Result<String> {
+    generate_with_progress(config, diff, &PromptContext::new(), |_| {}).await
+}
+
+/// Generate a commit message (with emote) for a diff, with extra prompt context, reporting
+/// progress to a callback
+pub async fn generate_with_progress<F>(
+    config: &Config,
+    diff: &str,
+    context: &PromptContext,
+    on_progress: F,
+) -> Result<String>
+where
+    F: FnMut(Progress),
+{
+    generate_with_generator(config, &OpenAiGenerator::new(config), diff, context, on_progress).await
+}
+
+/// Like [`generate_with_progress`], with messages written by `generator` instead of the
+/// configured API
+pub async fn generate_with_generator<F>(
+    config: &Config,
+    generator: &dyn CommitGenerator,
+    diff: &str,
+    context: &PromptContext,
+    on_progress: F,
+) -> Result<String>
+where
+    F: FnMut(Progress),
+{
+    generate_with_budget(config, generator, diff, context, &CallBudget::new(config), on_progress).await
+}
+
+/// Like [`generate_with_generator`], with the API calls counted against a budget shared with
+/// the rest of the run (e.g. the other files of `--per-file`)
+pub async fn generate_with_budget<F>(
+    config: &Config,
+    generator: &dyn CommitGenerator,
+    diff: &str,
+    context: &PromptContext,
+    budget: &CallBudget,
+    mut on_progress: F,
+) -> Result<String>
+where
+    F: FnMut(Progress),
+{
+    // Submodule pointer updates carry no code the model could describe
+    let submodules = git::submodule_updates(diff);
+    if !submodules.is_empty() && submodules.len() == git::split_into_files(diff).len() {
+        return Ok(finalize_message(config, &submodule_message(&submodules)));
+    }
+    let mut context = context.clone();
+    context.add_submodule_updates(&submodules);
+    let context = &context;
+
+    let combine_strategy = config.get_combine_strategy();
+    let ChunkMessages {
+        messages: mut chunk_messages,
+        descriptions: chunk_descriptions,
+        split,
+    } = generate_chunk_messages(
+        generator,
+        diff,
+        context,
+        budget,
+        combine_strategy == CombineStrategy::Llm,
+        &mut on_progress,
+    )
+    .await?;
+
+    // The categories of the parts, for an emote per category (`emote.multi`)
+    let chunk_categories: Vec<CommitCategory> = if chunk_messages.len() > 1 {
+        let options = config.get_emote_options();
+        chunk_messages
+            .iter()
+            .map(|message| emotes::categorize_with_options(message, &options))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let commit_message = if chunk_messages.len() == 1 {
+        chunk_messages.remove(0)
+    } else if combine_strategy == CombineStrategy::Concat {
+        // List the chunk messages under one subject without another request
+        message::concat_messages(&chunk_messages)
+    } else {
+        // Combine the messages
+        budget.take()?;
+        on_progress(Progress::Combining);
+        let started = Instant::now();
+        let reply = generator
+            .combine_reply(chunk_messages, &chunk_descriptions)
+            .await
+            .context("Failed to combine commit messages")?;
+        on_progress(Progress::Completed {
+            description: "Combine",
+            latency: started.elapsed(),
+            usage: reply.usage,
+        });
+        // The emote is chosen below, following the emote settings
+        emotes::strip_leading_emotes(&reply.content)
+    };
+
+    // Ask once more, firmly, when the model returned a placeholder or next to nothing, then
+    // once for a conventional commit when the model didn't follow the format and once for an
+    // allowed type, unless that would exceed max_api_calls
+    let commit_message =
+        if message::unusable(&commit_message, config.get_min_message_length()).is_some() && budget.take_if_allowed() {
+            on_progress(Progress::Retrying);
+            let (chunk, mut context) = (retry_chunk(diff, split), context.clone());
+            if split {
+                context.add_truncation_note();
+            }
+            context.add("Important", prompt::RETRY_INSTRUCTION);
+            let started = Instant::now();
+            let reply = generator
+                .generate_reply(&chunk, &context)
+                .await
+                .context("Failed to generate commit message again")?;
+            on_progress(Progress::Completed {
+                description: "Retry",
+                latency: started.elapsed(),
+                usage: reply.usage,
+            });
+            reply.content
+        } else {
+            commit_message
+        };
+    let commit_message = if config.get_require_conventional()
+        && !emotes::is_conventional_commit(&commit_message)
+        && budget.take_if_allowed()
+    {
+        on_progress(Progress::Reformatting);
+        generator
+            .revise(&commit_message, prompt::REFORMAT_CONVENTIONAL_INSTRUCTION)
+            .await
+            .context("Failed to reformat commit message")?
+    } else {
+        commit_message
+    };
+    let commit_message = match config.get_allowed_types() {
+        Some(allowed) if disallowed_type(allowed, &commit_message).is_some() && budget.take_if_allowed() => {
+            on_progress(Progress::Reformatting);
+            generator
+                .revise(&commit_message, &prompt::allowed_types_instruction(allowed))
+                .await
+                .context("Failed to reformat commit message")?
+        }
+        _ => commit_message,
+    };
+    let commit_message = match config.get_subject_pattern() {
+        Some(pattern) if mismatched_subject(&pattern, &commit_message).is_some() && budget.take_if_allowed() => {
+            on_progress(Progress::Reformatting);
+            generator
+                .revise(&commit_message, &prompt::subject_pattern_instruction(pattern.as_str()))
+                .await
+                .context("Failed to reformat commit message")?
+        }
+        _ => commit_message,
+    };
+
+    Ok(finalize_message_with_categories(config, &commit_message, &chunk_categories))
+}
+
+/// Generate a message (with emote) for each chunk of a diff without combining them, as
+/// `(chunk description, message)` pairs in chunk order
+///
+/// A diff that needs no splitting gives a single pair. The messages are not retried or
+/// reformatted like a combined message is.
+pub async fn generate_chunks_with_generator<F>(
+    config: &Config,
+    generator: &dyn CommitGenerator,
+    diff: &str,
+    context: &PromptContext,
+    mut on_progress: F,
+) -> Result<Vec<(String, String)>>
+where
+    F: FnMut(Progress),
+{
+    let submodules = git::submodule_updates(diff);
+    if !submodules.is_empty() && submodules.len() == git::split_into_files(diff).len() {
+        let message = finalize_message(config, &submodule_message(&submodules));
+        return Ok(vec![("Complete diff".to_string(), message)]);
+    }
+    let mut context = context.clone();
+    context.add_submodule_updates(&submodules);
+
+    let budget = CallBudget::new(config);
+    let chunks = generate_chunk_messages(generator, diff, &context, &budget, false, &mut on_progress).await?;
+    Ok(chunks
+        .descriptions
+        .into_iter()
+        .zip(chunks.messages)
+        .map(|(description, message)| (description, finalize_message(config, &message)))
+        .collect())
+}
+
+/// The messages written for the chunks of a diff, before they are combined
+struct ChunkMessages {
+    messages: Vec<String>,
+    /// The description of each message's chunk, e.g. "File: src/auth.rs"
+    descriptions: Vec<String>,
+    /// Whether the diff was split
+    split: bool,
+}
+
+/// Generate a message for each chunk of a diff, splitting a chunk further when it doesn't
+/// fit in the model's context
+///
+/// With `combine`, a call for combining the messages is reserved in the budget too.
+async fn generate_chunk_messages<F>(
+    generator: &dyn CommitGenerator,
+    diff: &str,
+    context: &PromptContext,
+    budget: &CallBudget,
+    combine: bool,
+    on_progress: &mut F,
+) -> Result<ChunkMessages>
+where
+    F: FnMut(Progress),
+{
+    // Check if diff needs splitting
+    let split = git::needs_splitting(diff);
+    let chunks = if split {
+        on_progress(Progress::Splitting);
+
+        // Split the diff
+        let split_result = git::split_large_diff(diff).context("Failed to split large diff")?;
+        on_progress(Progress::Split(&split_result));
+        split_result.chunks
+    } else {
+        on_progress(Progress::Generating);
+
+        // Generate commit message normally for small diffs
+        vec![DiffChunk {
+            content: diff.to_string(),
+            description: "Complete diff".to_string(),
+        }]
+    };
+
+    // Generate commit messages for each chunk, splitting a chunk further when it doesn't
+    // fit in the model's context
+    let mut pending: VecDeque<DiffChunk> = chunks.into();
+    let mut chunk_messages = Vec::new();
+    let mut chunk_descriptions = Vec::new();
+    while let Some(chunk) = pending.pop_front() {
+        // This chunk, the ones after it and the combine step still need a call each
+        let remaining = pending.len() + 1;
+        let combine = usize::from(combine && chunk_messages.len() + remaining > 1);
+        budget.check(remaining + combine)?;
+        budget.take()?;
+
+        if split {
+            on_progress(Progress::Chunk {
+                index: chunk_messages.len(),
+                description: &chunk.description,
+            });
+        }
+
+        let started = Instant::now();
+        match generator.generate_reply(&chunk, context).await {
+            Ok(reply) => {
+                on_progress(Progress::Completed {
+                    description: &chunk.description,
+                    latency: started.elapsed(),
+                    usage: reply.usage,
+                });
+                chunk_messages.push(reply.content);
+                chunk_descriptions.push(chunk.description);
+            }
+            Err(err) if exceeds_context_length(&err) && chunk.content.len() >= MIN_RESPLIT_SIZE => {
+                let smaller = git::split_smaller(&chunk.content).context("Failed to split chunk further")?;
+                on_progress(Progress::Resplit {
+                    description: &chunk.description,
+                    chunks: smaller.chunks.len(),
+                });
+                for piece in smaller.chunks.into_iter().rev() {
+                    pending.push_front(DiffChunk {
+                        content: piece.content,
+                        description: format!("{}, {}", chunk.description, piece.description),
+                    });
+                }
+            }
+            Err(err) if !split => return Err(err),
+            Err(err) => {
+                return Err(err).context(format!(
+                    "Failed to generate message for chunk {}",
+                    chunk_messages.len() + 1
+                ))
+            }
+        }
+    }
+
+    Ok(ChunkMessages {
+        messages: chunk_messages,
+        descriptions: chunk_descriptions,
+        split,
+    })
+}
+
+/// The message for a diff that only moves submodules to other commits, written without the
+/// model, e.g. `chore: bump libs/ui to 5d6e7f8`
+pub fn submodule_message(updates: &[SubmoduleUpdate]) -> String {
+    let describe = |update: &SubmoduleUpdate| match (&update.old, update.short_new()) {
+        (_, None) => format!("remove submodule {}", update.path),
+        (None, Some(new)) => format!("add submodule {} at {}", update.path, new),
+        (Some(_), Some(new)) => format!("bump {} to {}", update.path, new),
+    };
+    match updates {
+        [update] => format!("chore: {}", describe(update)),
+        _ => {
+            let lines: Vec<String> = updates.iter().map(|update| format!("- {}", describe(update))).collect();
+            format!("chore: update submodules\n\n{}", lines.join("\n"))
+        }
+    }
+}
+
+/// The diff sent when retrying after an unusable message: the whole diff in one request,
+/// truncated when it had to be split
+fn retry_chunk(diff: &str, split: bool) -> DiffChunk {
+    if split {
+        DiffChunk {
+            content: git::truncate_large_diff(diff),
+            description: "Truncated diff".to_string(),
+        }
+    } else {
+        DiffChunk {
+            content: diff.to_string(),
+            description: "Complete diff".to_string(),
+        }
+    }
+}
+
+/// The API calls of one run, counted against `max_api_calls` before every request
+#[derive(Debug, Default)]
+pub struct CallBudget {
+    max: Option<usize>,
+    used: AtomicUsize,
+    /// Calls kept for later work of the run, e.g. the files `--per-file` hasn't reached yet
+    reserved: AtomicUsize,
+}
+
+impl CallBudget {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            max: config.get_max_api_calls(),
+            ..Default::default()
+        }
+    }
+
+    /// Calls made so far
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Keep `calls` out of reach of optional calls (retries and reformatting)
+    pub fn reserve(&self, calls: usize) {
+        self.reserved.store(calls, Ordering::Relaxed);
+    }
+
+    /// Fail unless `calls` more calls fit next to the ones made and reserved
+    pub fn check(&self, calls: usize) -> Result<()> {
+        let needed = self.used() + self.reserved.load(Ordering::Relaxed) + calls;
+        match self.max {
+            Some(max) if needed > max => Err(eyre!(
+                "diff too large for max_api_calls={} ({} calls needed), raise the limit or stage less",
+                max,
+                needed
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Count a call, failing when it doesn't fit
+    pub fn take(&self) -> Result<()> {
+        self.check(1)?;
+        self.used.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Count an optional call if it fits, returning whether it may be made
+    pub fn take_if_allowed(&self) -> bool {
+        self.take().is_ok()
+    }
+}
+
+/// Fail when a run needs more API calls than `max_api_calls` allows
+pub fn check_api_calls(config: &Config, needed: usize) -> Result<()> {
+    match config.get_max_api_calls() {
+        Some(max) if needed > max => Err(eyre!(
+            "diff too large for max_api_calls={} ({} calls needed), raise the limit or stage less",
+            max,
+            needed
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Whether a generation error means the chunk didn't fit in the model's context
+fn exceeds_context_length(err: &Report) -> bool {
+    matches!(err.downcast_ref::<ApiError>(), Some(ApiError::ContextLengthExceeded { .. }))
+}
+
+/// The category implied by the current branch's prefix (see `branch_prefix_map`), if any
+pub fn branch_category(config: &Config) -> Option<CommitCategory> {
+    config.get_branch_category(&git::current_branch().ok()?)
+}
+
+/// Apply the configured post-processing (body style, length limits, emote) to a message
+/// returned by the model
+pub fn finalize_message(config: &Config, commit_message: &str) -> String {
+    finalize_message_with_categories(config, commit_message, &[])
+}
+
+/// Like [`finalize_message`], for a message combined from parts in `categories`, which get an
+/// emote each with `emote.multi = prefix_all`
+pub fn finalize_message_with_categories(
+    config: &Config,
+    commit_message: &str,
+    categories: &[CommitCategory],
+) -> String {
+    // Enforce the configured body style
+    let commit_message = match config.get_body_style() {
+        Some(BodyStyle::Bullets) => message::format_body_as_bullets(commit_message),
+        _ => commit_message.to_string(),
+    };
+
+    // Keep the body within the configured length
+    let commit_message = if config.get_subject_only() {
+        message::subject_only(&commit_message)
+    } else {
+        match config.get_max_body_lines() {
+            Some(max_lines) => message::truncate_body(&commit_message, max_lines),
+            None => commit_message,
+        }
+    };
+
+    // Add emote to the commit message based on categorization, biased by the branch
+    let emote_options = EmoteOptions {
+        prior: branch_category(config),
+        ..config.get_emote_options()
+    };
+    let commit_message = match config.get_emote_multi() {
+        EmoteMulti::PrefixAll if !categories.is_empty() => emotes::process_commit_message_with_categories(
+            &commit_message,
+            categories,
+            config.get_max_emotes(),
+            &emote_options,
+        ),
+        _ => emotes::process_commit_message_with_options(&commit_message, &emote_options),
+    };
+    // Give a scopeless subject its category's default scope
+    let default_scope = emotes::conventional_type(&commit_message)
+        .and_then(|kind| kind.parse().ok())
+        .and_then(|category| config.get_category_default_scope(&category));
+    let commit_message = match default_scope {
+        Some(scope) => emotes::add_default_scope(&commit_message, scope),
+        None => commit_message,
+    };
+    let commit_message = emotes::apply_scope_style(&commit_message, config.get_scope_style());
+    if config.get_replace_type_prefix() {
+        emotes::replace_type_prefix(&commit_message)
+    } else {
+        commit_message
+    }
+}
+
+/// The rules a finished message breaks: it must not be empty, too short or a placeholder, its
+/// subject must fit in `max_subject_length` and match `subject.pattern` and, with
+/// `require_conventional`, it must be a conventional commit
+pub fn check_message(config: &Config, commit_message: &str) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    if emotes::strip_leading_emotes(commit_message).trim().is_empty() {
+        violations.push(Violation::Empty);
+        return violations;
+    }
+    violations.extend(message::unusable(commit_message, config.get_min_message_length()));
+
+    let (subject, _) = message::split_subject(commit_message);
+    let length = subject.trim().chars().count();
+    if let Some(max) = config.get_max_subject_length().filter(|&max| length > max) {
+        violations.push(Violation::SubjectTooLong { length, max });
+    }
+    if let Some(pattern) = config.get_subject_pattern() {
+        if let Some(subject) = mismatched_subject(&pattern, commit_message) {
+            violations.push(Violation::SubjectPattern {
+                subject,
+                pattern: pattern.to_string(),
+            });
+        }
+    }
+
+    // With replace_type_prefix the prefix is dropped on purpose, after the generated
+    // message was checked (and reformatted if needed)
+    if config.get_replace_type_prefix() {
+        return violations;
+    }
+    if config.get_require_conventional() && !emotes::is_conventional_commit(commit_message) {
+        violations.push(Violation::NotConventional);
+    }
+    if let Some(allowed) = config.get_allowed_types() {
+        if let Some(kind) = disallowed_type(allowed, commit_message) {
+            violations.push(Violation::TypeNotAllowed {
+                kind,
+                allowed: allowed.to_vec(),
+            });
+        }
+    }
+    violations
+}
+
+/// The subject of a message when it doesn't match `pattern`, leading emotes left out
+fn mismatched_subject(pattern: &Regex, commit_message: &str) -> Option<String> {
+    let stripped = emotes::strip_leading_emotes(commit_message);
+    let (subject, _) = message::split_subject(&stripped);
+    let subject = subject.trim();
+    (!pattern.is_match(subject)).then(|| subject.to_string())
+}
+
+/// The type of a conventional subject when it isn't one of `allowed` (compared ignoring case)
+///
+/// A subject without a type passes; requiring one is up to `require_conventional`.
+fn disallowed_type(allowed: &[String], commit_message: &str) -> Option<String> {
+    emotes::conventional_type(commit_message)
+        .filter(|kind| !allowed.iter().any(|allowed| allowed.eq_ignore_ascii_case(kind)))
+}
+
+/// A conversation with the model that revises a generated message on request
+///
+/// The conversation starts with the original prompt and the generated message; every
+/// instruction is sent as a follow-up user message together with all prior turns.
+pub struct Refiner<'a> {
+    config: &'a Config,
+    settings: RequestSettings,
+    messages: Vec<Message>,
+}
+
+impl<'a> Refiner<'a> {
+    pub fn new(config: &'a Config, diff: &str, context: &PromptContext, message: &str) -> Result<Self> {
+        let settings = request_settings(config)?;
+
+        // A diff that had to be split doesn't fit in a single request either
+        let diff = if git::needs_splitting(diff) {
+            "(The diff is too large to include here; the message above was generated from it in parts.)"
+        } else {
+            diff
+        };
+        let user_prompt = prompt::build_user_prompt(config.get_user_prompt(), context, diff);
+
+        Ok(Self {
+            config,
+            settings,
+            messages: vec![
+                Message::system(&prompt::build_system_prompt(config)),
+                Message::user(&user_prompt),
+                Message::assistant(message),
+            ],
+        })
+    }
+
+    /// Ask the model to revise the latest message according to an instruction
+    pub async fn refine(&mut self, instruction: &str) -> Result<String> {
+        self.messages.push(Message::user(&format!(
+            "Revise the commit message: {}\nRespond with the complete revised commit message only.",
+            instruction.trim()
+        )));
+
+        let reply = llm::chat(self.messages.clone(), &self.settings)
+            .await
+            .context("Failed to refine commit message")?;
+        self.messages.push(Message::assistant(&reply));
+
+        Ok(finalize_message(self.config, &reply))
+    }
+}
+
+/// Whether a commit message marks work in progress: a `wip` subject, the 🚧 emote or a
+/// `fixup!`, `squash!` or `amend!` commit
+pub fn is_wip_message(commit_message: &str) -> bool {
+    let subject = commit_message.lines().next().unwrap_or("").trim();
+    if subject.starts_with(CommitCategory::Wip.emote()) {
+        return true;
+    }
+    let subject = subject.to_lowercase();
+    let first_word = subject
+        .split(|c: char| c.is_whitespace() || c == ':' || c == '(')
+        .next()
+        .unwrap_or("");
+    matches!(first_word, "wip" | "fixup!" | "squash!" | "amend!")
+}
+
+/// Write one message for the commits of a branch, to squash them into a single commit
+///
+/// Work-in-progress commits (see [`is_wip_message`]) get a message generated from their diff,
+/// the others keep their own. The messages are then combined like the messages written for
+/// the chunks of a split diff.
+pub async fn squash_message_with_generator<F>(
+    config: &Config,
+    generator: &dyn CommitGenerator,
+    commits: &[BranchCommit],
+    mut on_progress: F,
+) -> Result<String>
+where
+    F: FnMut(Progress),
+{
+    if commits.is_empty() {
+        return Err(eyre!("No commits to squash"));
+    }
+    let wip = commits.iter().filter(|commit| is_wip_message(&commit.message)).count();
+    check_api_calls(config, wip + usize::from(commits.len() > 1))?;
+
+    let mut messages = Vec::new();
+    let mut descriptions = Vec::new();
+    for (index, commit) in commits.iter().enumerate() {
+        let description = format!("Commit {}: {}", commit.short_hash(), commit.subject());
+        let message = if is_wip_message(&commit.message) {
+            on_progress(Progress::Chunk {
+                index,
+                description: &description,
+            });
+            let chunk = DiffChunk {
+                content: git::truncate_large_diff(&commit.diff),
+                description: description.clone(),
+            };
+            let started = Instant::now();
+            let reply = generator
+                .generate_reply(&chunk, &PromptContext::new())
+                .await
+                .context(format!("Failed to generate a message for commit {}", commit.short_hash()))?;
+            on_progress(Progress::Completed {
+                description: &description,
+                latency: started.elapsed(),
+                usage: reply.usage,
+            });
+            reply.content
+        } else {
+            commit.message.clone()
+        };
+        messages.push(emotes::strip_leading_emotes(&message));
+        descriptions.push(description);
+    }
+
+    let commit_message = if messages.len() == 1 {
+        messages.remove(0)
+    } else {
+        on_progress(Progress::Combining);
+        let started = Instant::now();
+        let reply = generator
+            .combine_reply(messages, &descriptions)
+            .await
+            .context("Failed to combine commit messages")?;
+        on_progress(Progress::Completed {
+            description: "Combine",
+            latency: started.elapsed(),
+            usage: reply.usage,
+        });
+        emotes::strip_leading_emotes(&reply.content)
+    };
+    Ok(finalize_message(config, &commit_message))
+}
+
+/// Summarize an aggregate diff (e.g. since the last tag) as release notes, reporting
+/// progress to a callback
+pub async fn summarize_with_progress<F>(config: &Config, diff: &str, on_progress: F) -> Result<String>
+where
+    F: FnMut(Progress),
+{
+    let prompts = ProsePrompts {
+        system: prompt::RELEASE_NOTES_SYSTEM_PROMPT,
+        user: prompt::RELEASE_NOTES_USER_PROMPT,
+        combine: prompt::RELEASE_NOTES_COMBINE_PROMPT,
+    };
+    write_prose(config, diff, &prompts, on_progress)
+        .await
+        .context("Failed to write release notes")
+}
+
+/// Explain a diff in plain-English prose, without any commit message post-processing,
+/// reporting progress to a callback
+pub async fn explain_with_progress<F>(config: &Config, diff: &str, on_progress: F) -> Result<String>
+where
+    F: FnMut(Progress),
+{
+    let prompts = ProsePrompts {
+        system: prompt::EXPLAIN_SYSTEM_PROMPT,
+        user: prompt::EXPLAIN_USER_PROMPT,
+        combine: prompt::EXPLAIN_COMBINE_PROMPT,
+    };
+    write_prose(config, diff, &prompts, on_progress)
+        .await
+        .context("Failed to explain the changes")
+}
+
+/// How the sections of a digest are grouped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DigestGrouping {
+    /// One section per changed file
+    #[default]
+    File,
+    /// One section per kind of change: features, fixes, tests, ...
+    Category,
+}
+
+impl FromStr for DigestGrouping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "file" => Ok(Self::File),
+            "category" => Ok(Self::Category),
+            other => Err(format!("unknown grouping '{}' (expected file or category)", other)),
+        }
+    }
+}
+
+impl fmt::Display for DigestGrouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File => write!(f, "file"),
+            Self::Category => write!(f, "category"),
+        }
+    }
+}
+
+/// Write a review-oriented Markdown digest of uncommitted work, with bullet points under a
+/// heading per file or per category, reporting progress to a callback
+pub async fn digest_with_progress<F>(
+    config: &Config,
+    diff: &str,
+    grouping: DigestGrouping,
+    on_progress: F,
+) -> Result<String>
+where
+    F: FnMut(Progress),
+{
+    let prompts = ProsePrompts {
+        system: prompt::DIGEST_SYSTEM_PROMPT,
+        user: match grouping {
+            DigestGrouping::File => prompt::DIGEST_BY_FILE_USER_PROMPT,
+            DigestGrouping::Category => prompt::DIGEST_BY_CATEGORY_USER_PROMPT,
+        },
+        combine: prompt::DIGEST_COMBINE_PROMPT,
+    };
+    write_prose(config, diff, &prompts, on_progress)
+        .await
+        .context("Failed to write the digest")
+}
+
+/// Write a Markdown pull request description (Summary, Changes and Testing sections) for
+/// a branch diff, reporting progress to a callback
+pub async fn describe_pr_with_progress<F>(config: &Config, diff: &str, on_progress: F) -> Result<String>
+where
+    F: FnMut(Progress),
+{
+    let prompts = ProsePrompts {
+        system: prompt::PR_SYSTEM_PROMPT,
+        user: prompt::PR_USER_PROMPT,
+        combine: prompt::PR_COMBINE_PROMPT,
+    };
+    write_prose(config, diff, &prompts, on_progress)
+        .await
+        .context("Failed to write the pull request description")
+}
+
+/// The prompts for writing prose about a diff rather than a commit message
+struct ProsePrompts {
+    system: &'static str,
+    user: &'static str,
+    /// Merges the texts written for the chunks of a split diff
+    combine: &'static str,
+}
+
+/// Write prose about a diff, one request per chunk plus the requests merging them when it
+/// is split
+async fn write_prose<F>(config: &Config, diff: &str, prompts: &ProsePrompts, mut on_progress: F) -> Result<String>
+where
+    F: FnMut(Progress),
+{
+    if config.is_offline() {
+        return Err(eyre!("Writing prose needs a model, which isn't used with provider = mock"));
+    }
+    let settings = &prose_settings(config)?;
+    let system_prompt = prompts.system;
+    let user_prompt = prompts.user;
+    let budget = CallBudget::new(config);
+
+    if !git::needs_splitting(diff) {
+        on_progress(Progress::Generating);
+        budget.take()?;
+        return Ok(llm::generate_text(diff, system_prompt, user_prompt, settings).await?);
+    }
+
+    on_progress(Progress::Splitting);
+    let split_result = git::split_large_diff(diff).context("Failed to split large diff")?;
+    on_progress(Progress::Split(&split_result));
+    // At least one merge; merging may take more rounds, each call is checked below
+    budget.check(split_result.chunks.len() + 1)?;
+
+    // Write about each chunk, then merge the parts
+    let mut summaries = Vec::new();
+    for (i, chunk) in split_result.chunks.iter().enumerate() {
+        on_progress(Progress::Chunk {
+            index: i,
+            description: &chunk.description,
+        });
+
+        budget.take()?;
+        let summary = llm::generate_text(&chunk.content, system_prompt, user_prompt, settings)
+            .await
+            .context(format!("Failed to process chunk {}", i + 1))?;
+        summaries.push(summary);
+    }
+
+    // Merge the parts in rounds of batches that fit in one request, until one is left
+    on_progress(Progress::Combining);
+    let mut parts = summaries;
+    while parts.len() > 1 {
+        let mut merged = Vec::new();
+        for batch in batch_parts(&parts, git::DIFF_SIZE_THRESHOLD) {
+            if let [part] = batch {
+                merged.push(part.clone());
+                continue;
+            }
+            let batch = batch
+                .iter()
+                .enumerate()
+                .map(|(i, part)| format!("Part {}:\n{}", i + 1, part))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            budget.take()?;
+            let text = llm::generate_text(&batch, system_prompt, prompts.combine, settings)
+                .await
+                .context("Failed to combine the parts")?;
+            merged.push(text);
+        }
+        parts = merged;
+    }
+    Ok(parts.remove(0))
+}
+
+/// Group consecutive parts into batches of at most `max_len` characters
+///
+/// A batch always takes at least two parts, even when that exceeds `max_len`, so that every
+/// round of merging shrinks the number of parts.
+fn batch_parts(parts: &[String], max_len: usize) -> Vec<&[String]> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    while start < parts.len() {
+        let mut end = start + 1;
+        let mut len = parts[start].len();
+        while end < parts.len() && (end - start < 2 || len + parts[end].len() <= max_len) {
+            len += parts[end].len();
+            end += 1;
+        }
+        batches.push(&parts[start..end]);
+        start = end;
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{body_partial_json, body_string_contains, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[test]
+    fn test_finalize_message_default_scope() {
+        let mut config = Config {
+            category_default_scope: Some(
+                [("docs", "docs"), ("ci", "ci")]
+                    .map(|(category, scope)| (category.to_string(), scope.to_string()))
+                    .into(),
+            ),
+            ..Default::default()
+        };
+        assert_eq!(finalize_message(&config, "docs: explain setup"), "📚 docs(docs): explain setup");
+        assert_eq!(finalize_message(&config, "documentation: explain setup"), "📚 documentation(docs): explain setup");
+        assert_eq!(finalize_message(&config, "docs(api): explain auth"), "📚 docs(api): explain auth");
+        assert_eq!(finalize_message(&config, "fix: handle nil"), "🐛 fix: handle nil");
+
+        config.scope_style = Some(crate::emotes::ScopeStyle::Brackets);
+        assert_eq!(finalize_message(&config, "ci: cache cargo"), "👷 ci[ci]: cache cargo");
+    }
+
+    #[test]
+    fn test_finalize_message_scope_style() {
+        let mut config = Config {
+            scope_style: Some(crate::emotes::ScopeStyle::Brackets),
+            ..Default::default()
+        };
+        assert_eq!(finalize_message(&config, "feat(auth): add login"), "✨ feat[auth]: add login");
+
+        config.emote.replace_type_prefix = Some(true);
+        assert_eq!(finalize_message(&config, "feat(auth): add login"), "✨ [auth] add login");
+
+        config.scope_style = None;
+        assert_eq!(finalize_message(&config, "fix[ui]: align button"), "🐛 (ui) align button");
+    }
+
+    #[tokio::test]
+    async fn test_generate() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "fix: handle missing config file"
+                }
+            }],
+            "usage": { "prompt_tokens": 120, "completion_tokens": 12, "total_tokens": 132 }
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            ..Default::default()
+        };
+
+        let mut events = Vec::new();
+        let message = generate_with_progress(
+            &config,
+            "diff --git a/x b/x",
+            &PromptContext::new(),
+            |progress| match progress {
+                Progress::Completed { description, usage, .. } => {
+                    events.push(format!("Completed {} {:?}", description, usage))
+                }
+                progress => events.push(format!("{:?}", progress)),
+            },
+        )
+        .await?;
+
+        assert_eq!(message, "🐛 fix: handle missing config file");
+        let usage = Usage {
+            prompt_tokens: 120,
+            completion_tokens: 12,
+        };
+        assert_eq!(
+            events,
+            vec![
+                "Generating".to_string(),
+                format!("Completed Complete diff {:?}", Some(usage))
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bench() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let response = |content: &str, prompt_tokens: u64| {
+            serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": content }
+                }],
+                "usage": { "prompt_tokens": prompt_tokens, "completion_tokens": 7 }
+            })
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("\"model\":\"small\""))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response("fix: handle nil", 100)))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("\"model\":\"large\""))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response("fix(parser): handle nil input", 120)))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("\"model\":\"missing\""))
+            .respond_with(ResponseTemplate::new(404).set_body_string("model not found"))
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            ..Default::default()
+        };
+        let models = ["small", "missing", "large"].map(String::from);
+        let runs = bench(&config, &models, "diff --git a/x b/x", &PromptContext::new()).await;
+
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[0].model, "small");
+        assert_eq!(runs[0].message.as_deref(), Ok("🐛 fix: handle nil"));
+        assert_eq!(runs[0].calls.len(), 1);
+        assert_eq!(runs[0].calls[0].usage.map(|usage| usage.prompt_tokens), Some(100));
+        assert!(runs[1].message.as_ref().unwrap_err().contains("404"));
+        assert_eq!(runs[2].message.as_deref(), Ok("🐛 fix(parser): handle nil input"));
+        assert_eq!(runs[2].calls[0].usage.map(|usage| usage.prompt_tokens), Some(120));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_reformats_non_conventional() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let response = |content: &str| {
+            serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": content }
+                }]
+            })
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response("Added a login form")))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response("feat: add login form")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            require_conventional: Some(true),
+            ..Default::default()
+        };
+
+        let mut events = Vec::new();
+        let message = generate_with_progress(
+            &config,
+            "diff --git a/x b/x",
+            &PromptContext::new(),
+            |progress| {
+                if !matches!(progress, Progress::Completed { .. }) {
+                    events.push(format!("{:?}", progress))
+                }
+            },
+        )
+        .await?;
+
+        assert_eq!(message, "✨ feat: add login form");
+        assert_eq!(events, vec!["Generating".to_string(), "Reformatting".to_string()]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_reprompts_for_allowed_type() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let response = |content: &str| {
+            serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": content }
+                }]
+            })
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response("perf(parser): cache tokens")))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("conventional commit types: feat, fix."))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response("fix(parser): cache tokens")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            ..Default::default()
+        };
+        config.conventional.allowed_types = Some(vec!["feat".to_string(), "fix".to_string()]);
+
+        let mut events = Vec::new();
+        let message = generate_with_progress(
+            &config,
+            "diff --git a/x b/x",
+            &PromptContext::new(),
+            |progress| {
+                if !matches!(progress, Progress::Completed { .. }) {
+                    events.push(format!("{:?}", progress))
+                }
+            },
+        )
+        .await?;
+
+        assert_eq!(message, "🐛 fix(parser): cache tokens");
+        assert_eq!(events, vec!["Generating".to_string(), "Reformatting".to_string()]);
+        assert!(check_message(&config, &message).is_empty());
+        assert_eq!(
+            check_message(&config, "⚡ perf(parser): cache tokens"),
+            vec![Violation::TypeNotAllowed {
+                kind: "perf".to_string(),
+                allowed: vec!["feat".to_string(), "fix".to_string()],
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_reprompts_for_subject_pattern() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let response = |content: &str| {
+            serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": content }
+                }]
+            })
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response("feat(auth): add login form")))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("matches the regular expression"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response("AUTH-12 feat(auth): add login form")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            ..Default::default()
+        };
+        config.subject.pattern = Some(r"^[A-Z]+-\d+ ".to_string());
+
+        let mut events = Vec::new();
+        let message = generate_with_progress(
+            &config,
+            "diff --git a/x b/x",
+            &PromptContext::new(),
+            |progress| {
+                if !matches!(progress, Progress::Completed { .. }) {
+                    events.push(format!("{:?}", progress))
+                }
+            },
+        )
+        .await?;
+
+        assert!(message.ends_with(" AUTH-12 feat(auth): add login form"));
+        assert_eq!(events, vec!["Generating".to_string(), "Reformatting".to_string()]);
+        assert!(check_message(&config, &message).is_empty());
+        assert_eq!(
+            check_message(&config, "✨ feat(auth): add login form"),
+            vec![Violation::SubjectPattern {
+                subject: "feat(auth): add login form".to_string(),
+                pattern: r"^[A-Z]+-\d+ ".to_string(),
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_retries_placeholder() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let response = |content: &str| {
+            serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": content }
+                }]
+            })
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("not a usable commit message"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response("fix(parser): handle empty input")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(response("✨")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            ..Default::default()
+        };
+
+        let mut events = Vec::new();
+        let message = generate_with_progress(
+            &config,
+            "diff --git a/x b/x",
+            &PromptContext::new(),
+            |progress| {
+                if !matches!(progress, Progress::Completed { .. }) {
+                    events.push(format!("{:?}", progress))
+                }
+            },
+        )
+        .await?;
+
+        assert_eq!(message, "🐛 fix(parser): handle empty input");
+        assert_eq!(events, vec!["Generating".to_string(), "Retrying".to_string()]);
+        assert!(check_message(&config, &message).is_empty());
+
+        // A placeholder that survives the retry fails the checks, so --commit doesn't commit it
+        assert_eq!(check_message(&config, "📝 [commit message]"), vec![Violation::Placeholder]);
+        assert_eq!(
+            check_message(&config, "✨ ok"),
+            vec![Violation::TooShort { length: 2, min: 5 }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_parts() {
+        let parts: Vec<String> = ["aaaa", "bb", "cc", "dddddd", "e"].iter().map(|s| s.to_string()).collect();
+        let batches = batch_parts(&parts, 8);
+        assert_eq!(batches, vec![&parts[0..3], &parts[3..5]]);
+
+        // Every batch but a trailing one takes two parts, even over the limit
+        let batches = batch_parts(&parts, 1);
+        assert_eq!(batches, vec![&parts[0..2], &parts[2..4], &parts[4..5]]);
+        assert!(batch_parts(&[], 8).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_summarize_skips_commit_post_processing() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let notes = "Summary: parsing is faster.\n\n```\ncache = true\n```\nHere's what changed for plugins: nothing.";
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("release notes"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": notes }
+                }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            structured_output: Some(true),
+            ..Default::default()
+        };
+
+        // Lines the commit message filter drops and the code fence are kept
+        let text = summarize_with_progress(&config, "diff --git a/x b/x", |_| {}).await?;
+        assert_eq!(text, notes);
+
+        let requests = mock_server.received_requests().await.unwrap_or_default();
+        let body = String::from_utf8_lossy(&requests[0].body);
+        assert!(!body.contains("response_format"));
+        assert!(!body.contains("single JSON object"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_explain() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let explanation = "This change makes the parser cache tokens, so that large files parse faster.";
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("Do not write a commit message"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": explanation }
+                }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            require_conventional: Some(true),
+            ..Default::default()
+        };
+
+        // No emote, reformatting or other commit message post-processing
+        let text = explain_with_progress(&config, "diff --git a/x b/x", |_| {}).await?;
+        assert_eq!(text, explanation);
+
+        let offline = Config {
+            provider: Some("mock".to_string()),
+            ..Default::default()
+        };
+        assert!(explain_with_progress(&offline, "diff --git a/x b/x", |_| {}).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_explain_skips_commit_post_processing() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let explanation = "The code changes the parser to cache tokens.\n\n\
+                           In this diff the cache is also cleared when a file is reloaded.";
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("Do not write a commit message"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": explanation }
+                }]
+            })))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            ..Default::default()
+        };
+        // Lines the commit message filter would drop are kept
+        let text = explain_with_progress(&config, "diff --git a/x b/x", |_| {}).await?;
+        assert_eq!(text, explanation);
+
+        // Structured output is for commit messages only
+        let config = Config {
+            structured_output: Some(true),
+            ..config
+        };
+        let text = explain_with_progress(&config, "diff --git a/x b/x", |_| {}).await?;
+        assert_eq!(text, explanation);
+        let requests = mock_server.received_requests().await.unwrap_or_default();
+        let body = String::from_utf8_lossy(&requests[1].body);
+        assert!(!body.contains("response_format"));
+        assert!(!body.contains("single JSON object"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_digest() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        let digest = "### Parser\n- Cache tokens between passes\n\n### Tests\n- Cover the cache";
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_string_contains("one heading per kind of change"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": digest }
+                }]
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            ..Default::default()
+        };
+
+        let text = digest_with_progress(&config, "diff --git a/x b/x", DigestGrouping::Category, |_| {}).await?;
+        assert_eq!(text, digest);
+        assert_eq!("category".parse(), Ok(DigestGrouping::Category));
+        assert!("folder".parse::<DigestGrouping>().is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_resplits_on_context_length() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": { "code": "context_length_exceeded", "message": "too long" }
+            })))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": "fix: update parser" }
+                }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            ..Default::default()
+        };
+
+        let body: String = (0..40).map(|i| format!("+let value_{i} = compute({i});\n")).collect();
+        let diff = format!(
+            "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1,0 +1,40 @@\n{body}\
+             diff --git a/b.rs b/b.rs\n--- a/b.rs\n+++ b/b.rs\n@@ -1,0 +1,40 @@\n{body}"
+        );
+        assert!(diff.len() >= MIN_RESPLIT_SIZE);
+
+        let mut events = Vec::new();
+        let message = generate_with_progress(&config, &diff, &PromptContext::new(), |progress| {
+            if !matches!(progress, Progress::Completed { .. }) {
+                events.push(format!("{:?}", progress))
+            }
+        })
+        .await?;
+
+        assert_eq!(message, "🐛 fix: update parser");
+        assert_eq!(events.first().map(String::as_str), Some("Generating"));
+        assert!(events[1].starts_with("Resplit"));
+        assert_eq!(events.last().map(String::as_str), Some("Combining"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_combines_with_combine_system_prompt() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let response = |content: &str| {
+            ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": content }
+                }]
+            }))
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [{"role": "system", "content": "Merge these."}, {"role": "user"}]
+            })))
+            .respond_with(response("feat: add parser and lexer"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(response("feat: add parser"))
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            combine_system_prompt: Some("Merge these.".to_string()),
+            ..Default::default()
+        };
+
+        // Two files over the size threshold are split and their messages combined
+        let file = |name: &str| {
+            let body: String = (0..2000).map(|i| format!("+let value_{i} = compute({i});\n")).collect();
+            format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n@@ -0,0 +1,2000 @@\n{body}")
+        };
+        let diff = file("parser.rs") + &file("lexer.rs");
+
+        let message = generate_with_progress(&config, &diff, &PromptContext::new(), |_| {}).await?;
+        assert_eq!(message, "✨ feat: add parser and lexer");
+
+        Ok(())
+    }
+
+    // Counts the chunks it was asked to generate a message for
+    #[derive(Default)]
+    struct CountingGenerator {
+        generated: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl CommitGenerator for CountingGenerator {
+        async fn generate(&self, _chunk: &DiffChunk, _context: &PromptContext) -> Result<String> {
+            self.generated.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok("feat: add module".to_string())
+        }
+
+        async fn combine(&self, _messages: Vec<String>, _descriptions: &[String]) -> Result<String> {
+            Ok("feat: add modules".to_string())
+        }
+    }
+
+    // Writes a message whose type follows the file in the chunk
+    struct CategoryGenerator;
+
+    #[async_trait::async_trait]
+    impl CommitGenerator for CategoryGenerator {
+        async fn generate(&self, chunk: &DiffChunk, _context: &PromptContext) -> Result<String> {
+            Ok(if chunk.content.contains("a/docs/") {
+                "docs: document export".to_string()
+            } else if chunk.content.contains("a/tests/") {
+                "test: cover export".to_string()
+            } else {
+                "feat: add export command".to_string()
+            })
+        }
+
+        async fn combine(&self, _messages: Vec<String>, _descriptions: &[String]) -> Result<String> {
+            Ok("✨ feat: add export command".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_prefixes_all_categories() -> Result<()> {
+        let file = |name: &str| {
+            let body: String = (0..1500).map(|i| format!("+let value_{i} = compute({i});\n")).collect();
+            format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n@@ -0,0 +1,1500 @@\n{body}")
+        };
+        let diff = file("docs/export.md") + &file("src/export.rs") + &file("tests/export.rs");
+
+        let mut config = Config::default();
+        let message = generate_with_generator(&config, &CategoryGenerator, &diff, &PromptContext::new(), |_| {}).await?;
+        assert_eq!(message, "✨ feat: add export command");
+
+        config.emote.multi = Some(EmoteMulti::PrefixAll);
+        config.categorize.priority = Some(vec!["test".to_string(), "docs".to_string()]);
+        let message = generate_with_generator(&config, &CategoryGenerator, &diff, &PromptContext::new(), |_| {}).await?;
+        assert_eq!(message, "✨🧪📚 feat: add export command");
+
+        config.emote.max_emotes = Some(2);
+        let message = generate_with_generator(&config, &CategoryGenerator, &diff, &PromptContext::new(), |_| {}).await?;
+        assert_eq!(message, "✨🧪 feat: add export command");
+
+        Ok(())
+    }
+
+    // Describes the diff it is given, and lists the messages it combines
+    struct SquashGenerator;
+
+    #[async_trait::async_trait]
+    impl CommitGenerator for SquashGenerator {
+        async fn generate(&self, chunk: &DiffChunk, _context: &PromptContext) -> Result<String> {
+            Ok(if chunk.content.contains("+retry") {
+                "feat(client): retry failed requests".to_string()
+            } else {
+                "test(client): cover retries".to_string()
+            })
+        }
+
+        async fn combine(&self, messages: Vec<String>, descriptions: &[String]) -> Result<String> {
+            assert_eq!(messages.len(), descriptions.len());
+            Ok(format!("feat(client): retry failed requests\n\n- {}", messages.join("\n- ")))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_squash_message() -> Result<()> {
+        let commit = |hash: &str, message: &str, diff: &str| BranchCommit {
+            hash: hash.to_string(),
+            message: message.to_string(),
+            diff: diff.to_string(),
+        };
+        let commits = [
+            commit("1111111aaaa", "wip", "diff --git a/c.rs b/c.rs\n+retry();\n"),
+            commit("2222222bbbb", "📝 docs: describe retries", "diff --git a/README.md b/README.md\n"),
+            commit("3333333cccc", "🚧 more", "diff --git a/t.rs b/t.rs\n+assert!(ok);\n"),
+        ];
+
+        let mut events = Vec::new();
+        let message = squash_message_with_generator(&Config::default(), &SquashGenerator, &commits, |progress| {
+            if !matches!(progress, Progress::Completed { .. }) {
+                events.push(format!("{:?}", progress))
+            }
+        })
+        .await?;
+        assert_eq!(
+            message,
+            "✨ feat(client): retry failed requests\n\n\
+             - feat(client): retry failed requests\n\
+             - docs: describe retries\n\
+             - test(client): cover retries"
+        );
+        assert_eq!(
+            events,
+            vec![
+                "Chunk { index: 0, description: \"Commit 1111111: wip\" }".to_string(),
+                "Chunk { index: 2, description: \"Commit 3333333: 🚧 more\" }".to_string(),
+                "Combining".to_string(),
+            ]
+        );
+
+        // A single finished commit keeps its message
+        let message = squash_message_with_generator(&Config::default(), &SquashGenerator, &commits[1..2], |_| {}).await?;
+        assert_eq!(message, "📚 docs: describe retries");
+
+        assert!(is_wip_message("WIP: half done"));
+        assert!(is_wip_message("fixup! feat: add retries"));
+        assert!(!is_wip_message("feat: wipe the cache"));
+        Ok(())
+    }
+
+    // Writes the prompt context it was given into the message
+    struct ContextGenerator;
+
+    #[async_trait::async_trait]
+    impl CommitGenerator for ContextGenerator {
+        async fn generate(&self, _chunk: &DiffChunk, context: &PromptContext) -> Result<String> {
+            Ok(format!("feat: use the new ui\n\n{}", context.render()))
+        }
+
+        async fn combine(&self, messages: Vec<String>, _descriptions: &[String]) -> Result<String> {
+            Ok(messages.join("\n"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generate_submodule_updates() -> Result<()> {
+        let submodule = |path: &str, old: &str, new: &str| {
+            format!(
+                "diff --git a/{path} b/{path}\nindex {old}..{new} 160000\n--- a/{path}\n+++ b/{path}\n\
+                 @@ -1 +1 @@\n-Subproject commit {old}{pad}\n+Subproject commit {new}{pad}\n",
+                pad = "0".repeat(33)
+            )
+        };
+        let config = Config::default();
+
+        // Only submodules: no request at all
+        let generator = CountingGenerator::default();
+        let diff = submodule("libs/ui", "1a2b3c4", "5d6e7f8");
+        let message = generate_with_generator(&config, &generator, &diff, &PromptContext::new(), |_| {}).await?;
+        assert_eq!(message, "🧹 chore: bump libs/ui to 5d6e7f8");
+        assert_eq!(generator.generated.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        let diff = submodule("libs/ui", "1a2b3c4", "5d6e7f8") + &submodule("vendor/lib", "9a8b7c6", "0f1e2d3");
+        let message = generate_with_generator(&config, &generator, &diff, &PromptContext::new(), |_| {}).await?;
+        assert_eq!(
+            message,
+            "🧹 chore: update submodules\n\n- bump libs/ui to 5d6e7f8\n- bump vendor/lib to 0f1e2d3"
+        );
+
+        // Mixed with code: the submodule section is pointed out to the model
+        let diff = submodule("libs/ui", "1a2b3c4", "5d6e7f8")
+            + "diff --git a/src/main.rs b/src/main.rs\n--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1 +1 @@\n-old\n+new\n";
+        let message = generate_with_generator(&config, &ContextGenerator, &diff, &PromptContext::new(), |_| {}).await?;
+        assert!(message.contains("Submodule pointer updates"));
+        assert!(message.contains("libs/ui: 1a2b3c4 -> 5d6e7f8"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_chunks_without_combining() -> Result<()> {
+        let file = |name: &str| {
+            let body: String = (0..1500).map(|i| format!("+let value_{i} = compute({i});\n")).collect();
+            format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n@@ -0,0 +1,1500 @@\n{body}")
+        };
+        let diff = file("docs/export.md") + &file("src/export.rs");
+
+        let mut events = Vec::new();
+        let chunks = generate_chunks_with_generator(&Config::default(), &CategoryGenerator, &diff, &PromptContext::new(), |progress| {
+            if matches!(progress, Progress::Combining) {
+                events.push("Combining")
+            }
+        })
+        .await?;
+        assert_eq!(
+            chunks,
+            vec![
+                ("File: docs/export.md".to_string(), "📚 docs: document export".to_string()),
+                ("File: src/export.rs".to_string(), "✨ feat: add export command".to_string()),
+            ]
+        );
+        assert!(events.is_empty());
+
+        // Without a combine step, max_api_calls only needs to cover the chunks
+        let config = Config {
+            max_api_calls: std::num::NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let chunks = generate_chunks_with_generator(&config, &CategoryGenerator, &diff, &PromptContext::new(), |_| {}).await?;
+        assert_eq!(chunks.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_respects_max_api_calls() -> Result<()> {
+        // Three files over the size threshold need three chunk calls and a combine call
+        let file = |name: &str| {
+            let body: String = (0..1500).map(|i| format!("+let value_{i} = compute({i});\n")).collect();
+            format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n@@ -0,0 +1,1500 @@\n{body}")
+        };
+        let diff = file("a.rs") + &file("b.rs") + &file("c.rs");
+
+        let mut config = Config {
+            max_api_calls: std::num::NonZeroUsize::new(3),
+            ..Default::default()
+        };
+        let generator = CountingGenerator::default();
+        let err = generate_with_generator(&config, &generator, &diff, &PromptContext::new(), |_| {})
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "diff too large for max_api_calls=3 (4 calls needed), raise the limit or stage less"
+        );
+        assert_eq!(generator.generated.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        config.max_api_calls = std::num::NonZeroUsize::new(4);
+        let message = generate_with_generator(&config, &generator, &diff, &PromptContext::new(), |_| {}).await?;
+        assert_eq!(message, "✨ feat: add modules");
+        assert_eq!(generator.generated.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        // Concatenating needs no combine call
+        config.max_api_calls = std::num::NonZeroUsize::new(3);
+        config.combine.strategy = Some(CombineStrategy::Concat);
+        let message = generate_with_generator(&config, &generator, &diff, &PromptContext::new(), |_| {}).await?;
+        assert_eq!(
+            message,
+            "✨ feat: add module\n\n- feat: add module\n- feat: add module\n- feat: add module"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_summarize_respects_max_api_calls_across_merge_rounds() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        // Parts this long merge two at a time, so three chunks take two rounds of merging
+        let part = "The parser caches tokens. ".repeat(git::DIFF_SIZE_THRESHOLD * 6 / 10 / 26);
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "choices": [{
+                    "message": { "role": "assistant", "content": part }
+                }]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let file = |name: &str| {
+            let body: String = (0..1500).map(|i| format!("+let value_{i} = compute({i});\n")).collect();
+            format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n@@ -0,0 +1,1500 @@\n{body}")
+        };
+        let diff = file("a.rs") + &file("b.rs") + &file("c.rs");
+
+        // Three chunk calls and two merge calls, one more than allowed
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            max_api_calls: std::num::NonZeroUsize::new(4),
+            ..Default::default()
+        };
+        let err = summarize_with_progress(&config, &diff, |_| {}).await.unwrap_err();
+        assert!(format!("{err:?}").contains("max_api_calls=4"));
+        let requests = mock_server.received_requests().await.unwrap_or_default();
+        assert_eq!(requests.len(), 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refiner_sends_conversation() -> Result<()> {
+        let mock_server = MockServer::start().await;
+
+        let mock_response = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "fix: handle missing config"
+                }
+            }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(body_partial_json(serde_json::json!({
+                "messages": [
+                    {"role": "system"},
+                    {"role": "user"},
+                    {"role": "assistant", "content": "fix: handle missing config file"},
+                    {"role": "user"}
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(mock_response))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let config = Config {
+            api_token: Some("test_token".to_string()),
+            api_base_url: Some(mock_server.uri()),
+            ..Default::default()
+        };
+
+        let mut refiner = Refiner::new(
+            &config,
+            "diff --git a/x b/x",
+            &PromptContext::new(),
+            "fix: handle missing config file",
+        )?;
+        let message = refiner.refine("make it shorter").await?;
+
+        assert_eq!(message, "🐛 fix: handle missing config");
+        assert_eq!(refiner.messages.len(), 5);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,534 @@
+//! Assembly of the user prompt sent to the model
+//!
+//! Extra context (author hints, repository details, ...) is inserted into the configured
+//! user prompt template right before the diff placeholder.
+
+use crate::config::Config;
+use crate::emotes::CommitCategory;
+use crate::git::{DiffStats, FileBlame, SubmoduleUpdate};
+use crate::integrations::Issue;
+use crate::message::BodyStyle;
+
+/// Placeholder in the user prompt template that is replaced with the diff
+pub const DIFF_PLACEHOLDER: &str = "{}";
+
+/// Longest issue body included in the prompt, in characters
+const MAX_ISSUE_BODY_CHARS: usize = 2000;
+
+/// Added to the system prompt when only a subject line is wanted
+const SUBJECT_ONLY_INSTRUCTION: &str = "Respond with a single conventional commit subject line only. \
+Do not include a body, bullet points or any other lines.";
+
+/// Sent with a generated message that isn't a conventional commit (`require_conventional`)
+pub const REFORMAT_CONVENTIONAL_INSTRUCTION: &str = "Reformat this commit message as a conventional commit: \
+the subject line must be `type(scope): description` or `type: description`, using a type such as feat, fix, \
+docs, style, refactor, test, perf, build, ci or chore. Keep the meaning and the body. Respond with the \
+reformatted commit message only.";
+
+/// Sent with a generated message whose subject doesn't match `subject.pattern`
+pub fn subject_pattern_instruction(pattern: &str) -> String {
+    format!(
+        "Rewrite this commit message so that its subject line matches the regular expression `{}`. \
+         Keep the meaning and the body. Respond with the rewritten commit message only.",
+        pattern
+    )
+}
+
+/// Sent with a generated message whose type isn't in `conventional.allowed_types`
+pub fn allowed_types_instruction(allowed_types: &[String]) -> String {
+    format!(
+        "Rewrite this commit message so that its subject uses one of these conventional commit types: {}. \
+         Pick the one that fits the change best and keep the scope, the meaning and the body. Respond with the \
+         rewritten commit message only.",
+        allowed_types.join(", ")
+    )
+}
+
+/// Added to the user prompt when the model's first reply was a placeholder or next to empty
+pub const RETRY_INSTRUCTION: &str = "Your previous reply was not a usable commit message: it was empty, \
+only an emote or a placeholder. Describe the actual changes in the diff below with a specific subject line. \
+Do not reply with placeholders such as [commit message] or <description>.";
+
+/// Added to the system prompt for `body_style = bullets`
+const BULLETS_INSTRUCTION: &str = "Write the body as a bulleted summary of the changed areas: \
+every body line must start with \"- \". Do not use numbered points or prose paragraphs.";
+
+/// Added to the system prompt for `body_style = paragraph`
+const PARAGRAPH_INSTRUCTION: &str = "Write the body as one or more prose paragraphs. \
+Do not use bullet or numbered points.";
+
+/// Added to the combine system prompt for `combine.include_files`
+const COMBINE_FILES_INSTRUCTION: &str = "Each message is labeled with the files it was written for. \
+End the body with a \"Files changed:\" section that lists each change with the files it touched, \
+e.g. \"- add the login form (src/auth.rs, src/views/login.rs)\".";
+
+/// Additional context sections to include in the user prompt
+#[derive(Debug, Default, Clone)]
+pub struct PromptContext {
+    sections: Vec<(String, String)>,
+}
+
+impl PromptContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a titled section of context
+    pub fn add(&mut self, title: &str, body: &str) {
+        let body = body.trim();
+        if !body.is_empty() {
+            self.sections.push((title.to_string(), body.to_string()));
+        }
+    }
+
+    /// Add author hints, one per line
+    pub fn add_hints(&mut self, hints: &[String]) {
+        let hints: Vec<&str> = hints
+            .iter()
+            .map(|hint| hint.trim())
+            .filter(|hint| !hint.is_empty())
+            .collect();
+        self.add("Additional context from the author", &hints.join("\n"));
+    }
+
+    /// Add a "new file" note for each untracked (not yet staged) file
+    pub fn add_untracked_files(&mut self, paths: &[String]) {
+        let notes: Vec<String> = paths.iter().map(|path| format!("new file: {}", path)).collect();
+        self.add("Untracked files (not staged, not in the diff)", &notes.join("\n"));
+    }
+
+    /// List the generated files whose diff was left out
+    pub fn add_generated_files(&mut self, paths: &[String]) {
+        self.add("Generated files (changed, diff omitted)", &paths.join("\n"));
+    }
+
+    /// List the files left out by `.git-narratorignore`, whose diff isn't sent
+    pub fn add_ignored_files(&mut self, paths: &[String]) {
+        self.add("Ignored files (changed, diff omitted)", &paths.join("\n"));
+    }
+
+    /// Mark the diff sections that only move a submodule to another commit
+    pub fn add_submodule_updates(&mut self, updates: &[SubmoduleUpdate]) {
+        let updates: Vec<String> = updates.iter().map(SubmoduleUpdate::describe).collect();
+        self.add(
+            "Submodule pointer updates (the submodule's code is not in the diff, only the commit it points to)",
+            &updates.join("\n"),
+        );
+    }
+
+    /// Add the branch being worked on and the repository's remote URL
+    pub fn add_repository(&mut self, branch: Option<&str>, remote_url: Option<&str>) {
+        let mut lines = Vec::new();
+        if let Some(branch) = branch {
+            lines.push(format!("branch: {}", branch));
+        }
+        if let Some(remote_url) = remote_url {
+            lines.push(format!("remote: {}", remote_url));
+        }
+        self.add("Repository (may hint at the scope)", &lines.join("\n"));
+    }
+
+    /// Add the commits that last touched the changed lines of each file
+    pub fn add_blame(&mut self, blame: &[FileBlame]) {
+        let files: Vec<String> = blame
+            .iter()
+            .map(|file| {
+                let commits: Vec<String> = file.commits.iter().map(|commit| format!("  {}", commit.describe())).collect();
+                format!("{}:\n{}", file.path, commits.join("\n"))
+            })
+            .collect();
+        self.add("Prior authors of the changed lines (git blame)", &files.join("\n"));
+    }
+
+    /// Add per-file insertion/deletion counts
+    pub fn add_numstat(&mut self, stats: &DiffStats) {
+        self.add(
+            "Changed files (insertions, deletions, path)",
+            &stats.to_numstat(),
+        );
+    }
+
+    /// List every changed file on one line, so the model knows the breadth of a diff that
+    /// it only sees part of
+    pub fn add_file_list(&mut self, paths: &[String]) {
+        self.add("Files changed", &paths.join(", "));
+    }
+
+    /// Tell the model that the diff below was shortened
+    pub fn add_truncation_note(&mut self) {
+        self.add(
+            "Note",
+            "The diff was truncated to fit the request: only the beginning of each file's changes \
+             is shown, and some files may be omitted. Describe the change as a whole, not just the \
+             visible lines.",
+        );
+    }
+
+    /// Suggest the commit type the changed paths point to, leaving the final call to the model
+    pub fn add_category_hint(&mut self, category: &CommitCategory) {
+        if let Some(kind) = category.conventional_type() {
+            self.add(
+                "Suggested commit type",
+                &format!(
+                    "These changes appear to be {}; use the `{}` type unless the diff clearly shows otherwise.",
+                    category.description().to_lowercase(),
+                    kind
+                ),
+            );
+        }
+    }
+
+    /// Add the title and body of the issue the branch references
+    pub fn add_issue(&mut self, issue: &Issue) {
+        let mut text = format!("#{}: {}", issue.number, issue.title.trim());
+        if let Some(body) = issue.body.as_deref().map(str::trim).filter(|body| !body.is_empty()) {
+            text.push_str("\n\n");
+            text.extend(body.chars().take(MAX_ISSUE_BODY_CHARS));
+            if body.chars().count() > MAX_ISSUE_BODY_CHARS {
+                text.push_str("...");
+            }
+        }
+        self.add("Referenced issue (the intent behind the change)", &text);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    /// Render all sections as prompt text
+    pub fn render(&self) -> String {
+        self.sections
+            .iter()
+            .map(|(title, body)| format!("{}:\n{}", title, body))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// System prompt for release-notes summaries
+pub const RELEASE_NOTES_SYSTEM_PROMPT: &str = "You are an expert at writing release notes. \
+Summarize code changes for the users of a project in clear, concise prose. \
+Group related changes, lead with the most important ones and mention breaking changes explicitly. \
+Do not list file names or describe implementation details unless they matter to users.";
+
+/// User prompt for release-notes summaries of a diff
+pub const RELEASE_NOTES_USER_PROMPT: &str = "Write release notes for the following changes, \
+made since the previous release. Use a short overview paragraph followed by sections \
+for new features, fixes and other changes where applicable.\n\n```diff\n{}\n```";
+
+/// User prompt for merging release notes written for parts of a large diff
+pub const RELEASE_NOTES_COMBINE_PROMPT: &str = "The following release notes were written for \
+separate parts of the same release. Merge them into a single set of release notes, removing \
+redundancy and keeping the same structure:\n\n{}";
+
+/// System prompt for plain-English explanations of a diff (`explain`)
+pub const EXPLAIN_SYSTEM_PROMPT: &str = "You are an experienced software engineer explaining a change \
+to a colleague who is about to review it. Describe what changed and, where the diff makes it clear, why, \
+in plain English prose. Do not write a commit message: no conventional-commit prefix, no subject line, \
+no bullet points and no emoji.";
+
+/// User prompt for explanations of a diff
+pub const EXPLAIN_USER_PROMPT: &str = "Explain the following changes in one or two paragraphs, \
+leading with the overall purpose and then the notable details:\n\n```diff\n{}\n```";
+
+/// User prompt for merging explanations written for parts of a large diff
+pub const EXPLAIN_COMBINE_PROMPT: &str = "The following explanations were written for separate \
+parts of the same change. Merge them into one explanation of one or two paragraphs, removing \
+redundancy:\n\n{}";
+
+/// System prompt for digests of uncommitted work (`digest`)
+pub const DIGEST_SYSTEM_PROMPT: &str = "You are an experienced software engineer helping a colleague \
+pick up work they left uncommitted. Review the changes and write a digest of everything that was \
+changed, so they can see where they stopped: what each change does, what looks unfinished (TODOs, \
+debug output, commented-out code) and anything worth a second look. Write Markdown bullet points \
+under short headings. Do not write a commit message.";
+
+/// User prompt for digests of uncommitted work, grouped by file
+pub const DIGEST_BY_FILE_USER_PROMPT: &str = "Write a digest of the following uncommitted changes, \
+with one heading per changed file and a bullet point per notable change:\n\n```diff\n{}\n```";
+
+/// User prompt for digests of uncommitted work, grouped by category
+pub const DIGEST_BY_CATEGORY_USER_PROMPT: &str = "Write a digest of the following uncommitted changes, \
+with one heading per kind of change (features, fixes, refactoring, tests, documentation, other) that \
+occurs and a bullet point per notable change, naming the files involved:\n\n```diff\n{}\n```";
+
+/// User prompt for merging digests written for parts of a large diff
+pub const DIGEST_COMBINE_PROMPT: &str = "The following digests were written for separate parts of \
+the same uncommitted work. Merge them into a single digest with the same kind of headings, merging \
+sections with the same heading and removing redundancy:\n\n{}";
+
+/// System prompt for pull request descriptions (`pr`)
+pub const PR_SYSTEM_PROMPT: &str = "You are an expert at writing pull request descriptions for \
+code review. Write GitHub-flavored Markdown with exactly these sections, in this order: \
+`## Summary` (a short paragraph on what the pull request does and why), `## Changes` (a bulleted \
+list of the notable changes, grouped by area) and `## Testing` (how the changes were or can be \
+tested, based on the tests and code in the diff). Do not add a title or any other sections.";
+
+/// User prompt for pull request descriptions of a branch diff
+pub const PR_USER_PROMPT: &str = "Write a pull request description for the following changes \
+made on a branch:\n\n```diff\n{}\n```";
+
+/// User prompt for merging pull request descriptions written for parts of a large branch
+pub const PR_COMBINE_PROMPT: &str = "The following pull request descriptions were written for \
+separate parts of the same branch. Merge them into a single description with the same Summary, \
+Changes and Testing sections, removing redundancy:\n\n{}";
+
+/// Build the system prompt, adding instructions for the configured output format
+pub fn build_system_prompt(config: &Config) -> String {
+    with_format_instructions(config.get_system_prompt(), config)
+}
+
+/// System prompt for combining the messages of a split diff: `combine_system_prompt`, or
+/// the generation system prompt when it isn't set, with the same format instructions
+pub fn build_combine_system_prompt(config: &Config) -> String {
+    let base = config
+        .get_combine_system_prompt()
+        .unwrap_or(config.get_system_prompt());
+    let mut system_prompt = with_format_instructions(base, config);
+    if config.get_combine_include_files() && !config.get_subject_only() {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(COMBINE_FILES_INSTRUCTION);
+    }
+    system_prompt
+}
+
+/// Append the subject-only or body style instructions to a system prompt
+fn with_format_instructions(base: &str, config: &Config) -> String {
+    let mut instructions = Vec::new();
+    if config.get_subject_only() {
+        instructions.push(SUBJECT_ONLY_INSTRUCTION);
+    } else {
+        match config.get_body_style() {
+            Some(BodyStyle::Bullets) => instructions.push(BULLETS_INSTRUCTION),
+            Some(BodyStyle::Paragraph) => instructions.push(PARAGRAPH_INSTRUCTION),
+            None => {}
+        }
+    }
+
+    let mut system_prompt = base.to_string();
+    for instruction in instructions {
+        system_prompt.push_str("\n\n");
+        system_prompt.push_str(instruction);
+    }
+    system_prompt
+}
+
+/// Fill a user prompt template with the context and the diff
+///
+/// The template is split once, at its first diff placeholder, so a `{}` in the context or
+/// the diff is left alone. The context goes before the line holding the placeholder, or
+/// before the opening fence if it sits inside a fenced block (```` ```diff ````). Without a
+/// placeholder the context is appended and the diff left out.
+pub fn build_user_prompt(template: &str, context: &PromptContext, diff: &str) -> String {
+    let Some(placeholder) = template.find(DIFF_PLACEHOLDER) else {
+        if context.is_empty() {
+            return template.to_string();
+        }
+        return format!("{}\n\n{}", template.trim_end(), context.render());
+    };
+    let (prefix, suffix) = (&template[..placeholder], &template[placeholder + DIFF_PLACEHOLDER.len()..]);
+    if context.is_empty() {
+        return format!("{}{}{}", prefix, diff, suffix);
+    }
+
+    // Start of the line holding the placeholder
+    let mut insert_at = prefix.rfind('\n').map_or(0, |i| i + 1);
+
+    // Move before an opening code fence directly above the placeholder
+    if insert_at > 0 {
+        let previous_start = prefix[..insert_at - 1].rfind('\n').map_or(0, |i| i + 1);
+        if prefix[previous_start..insert_at].trim_start().starts_with("```") {
+            insert_at = previous_start;
+        }
+    }
+
+    format!(
+        "{}{}\n\n{}{}{}",
+        &prefix[..insert_at],
+        context.render(),
+        &prefix[insert_at..],
+        diff,
+        suffix
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::BlameCommit;
+
+    #[test]
+    fn test_build_system_prompt() {
+        let mut config = Config {
+            system_prompt: Some("Write commits.".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(build_system_prompt(&config), "Write commits.");
+
+        config.body_style = Some(BodyStyle::Bullets);
+        assert_eq!(
+            build_system_prompt(&config),
+            format!("Write commits.\n\n{}", BULLETS_INSTRUCTION)
+        );
+
+        // Subject-only output has no body to style
+        config.subject_only = Some(true);
+        assert_eq!(
+            build_system_prompt(&config),
+            format!("Write commits.\n\n{}", SUBJECT_ONLY_INSTRUCTION)
+        );
+    }
+
+    #[test]
+    fn test_add_issue() {
+        let mut context = PromptContext::new();
+        context.add_issue(&Issue {
+            number: 12,
+            title: "Login fails with expired tokens".to_string(),
+            body: Some("Users are logged out.\n".to_string()),
+        });
+        assert_eq!(
+            context.render(),
+            "Referenced issue (the intent behind the change):\n\
+             #12: Login fails with expired tokens\n\nUsers are logged out."
+        );
+    }
+
+    #[test]
+    fn test_build_combine_system_prompt() {
+        let mut config = Config {
+            system_prompt: Some("Write commits.".to_string()),
+            body_style: Some(BodyStyle::Bullets),
+            ..Default::default()
+        };
+        assert_eq!(build_combine_system_prompt(&config), build_system_prompt(&config));
+
+        config.combine_system_prompt = Some("Merge these into one commit.".to_string());
+        assert_eq!(
+            build_combine_system_prompt(&config),
+            format!("Merge these into one commit.\n\n{}", BULLETS_INSTRUCTION)
+        );
+
+        config.combine.include_files = Some(true);
+        assert_eq!(
+            build_combine_system_prompt(&config),
+            format!(
+                "Merge these into one commit.\n\n{}\n\n{}",
+                BULLETS_INSTRUCTION, COMBINE_FILES_INSTRUCTION
+            )
+        );
+        assert!(!build_system_prompt(&config).contains(COMBINE_FILES_INSTRUCTION));
+    }
+
+    #[test]
+    fn test_build_user_prompt_without_context() {
+        let template = "Generate a message:\n```diff\n{}\n```";
+        assert_eq!(
+            build_user_prompt(template, &PromptContext::new(), "+a"),
+            "Generate a message:\n```diff\n+a\n```"
+        );
+    }
+
+    #[test]
+    fn test_build_user_prompt_with_hints() {
+        let mut context = PromptContext::new();
+        context.add_hints(&[
+            "this reverts the A/B test".to_string(),
+            "  ".to_string(),
+            "keep it short".to_string(),
+        ]);
+
+        let template = "Generate a message:\n\n```diff\n{}\n```";
+        assert_eq!(
+            build_user_prompt(template, &context, "+a"),
+            "Generate a message:\n\n\
+             Additional context from the author:\n\
+             this reverts the A/B test\n\
+             keep it short\n\n\
+             ```diff\n+a\n```"
+        );
+    }
+
+    #[test]
+    fn test_build_user_prompt_braces_in_context() {
+        let mut context = PromptContext::new();
+        context.add_hints(&["the stub is now `fn main() {}`".to_string()]);
+
+        // Only the template's placeholder is replaced, once, even with braces in the diff
+        let prompt = build_user_prompt("Hint:\n{}\nEnd {}", &context, "+fn a() {}");
+        assert_eq!(
+            prompt,
+            "Hint:\nAdditional context from the author:\nthe stub is now `fn main() {}`\n\n+fn a() {}\nEnd {}"
+        );
+        assert_eq!(prompt.matches("+fn a()").count(), 1);
+    }
+
+    #[test]
+    fn test_build_user_prompt_with_category_hint() {
+        let mut context = PromptContext::new();
+        context.add_category_hint(&CommitCategory::Fix);
+
+        let prompt = build_user_prompt("Generate a message:\n{}", &context, "+a");
+        assert!(prompt.contains("Suggested commit type:\nThese changes appear to be bug fixes"));
+        assert!(prompt.contains("use the `fix` type unless the diff clearly shows otherwise"));
+        assert!(prompt.ends_with("\n+a"));
+
+        let mut context = PromptContext::new();
+        context.add_category_hint(&CommitCategory::Unknown);
+        assert!(context.is_empty());
+    }
+
+    #[test]
+    fn test_add_untracked_files() {
+        let mut context = PromptContext::new();
+        context.add_untracked_files(&[]);
+        assert!(context.is_empty());
+
+        context.add_untracked_files(&["src/new.rs".to_string(), "README.md".to_string()]);
+        assert_eq!(
+            context.render(),
+            "Untracked files (not staged, not in the diff):\nnew file: src/new.rs\nnew file: README.md"
+        );
+    }
+
+    #[test]
+    fn test_add_blame() {
+        let mut context = PromptContext::new();
+        context.add_blame(&[]);
+        assert!(context.is_empty());
+
+        let commit = |hash: &str, author: &str, summary: &str, lines| BlameCommit {
+            hash: hash.to_string(),
+            author: author.to_string(),
+            summary: summary.to_string(),
+            lines,
+        };
+        context.add_blame(&[FileBlame {
+            path: "src/auth.rs".to_string(),
+            commits: vec![
+                commit("1a2b3c4d5e", "Jane Doe", "fix token refresh", 3),
+                commit("5d6e7f8a9b", "Sam Lee", "add login", 1),
+            ],
+        }]);
+        assert_eq!(
+            context.render(),
+            "Prior authors of the changed lines (git blame):\nsrc/auth.rs:\n  \
+             1a2b3c4 Jane Doe, 3 lines: fix token refresh\n  5d6e7f8 Sam Lee, 1 line: add login"
+        );
+    }
+
+    #[test]
+    fn test_build_user_prompt_inline_placeholder() {
+        let mut context = PromptContext::new();
+        context.add("Branch", "feature/login");
+
+        assert_eq!(
+            build_user_prompt("Diff: {}", &context, "+a"),
+            "Branch:\nfeature/login\n\nDiff: +a"
+        );
+        assert_eq!(
+            build_user_prompt("No placeholder", &context, "+a"),
+            "No placeholder\n\nBranch:\nfeature/login"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Git trailer handling for generated commit messages
+//!
+//! Trailers (`Co-authored-by:`, `Refs:`, ...) are appended after the message has been
+//! generated and post-processed, so they never pass through the LLM output filters.
+
+use crate::emotes::CommitCategory;
+use color_eyre::eyre::{eyre, Result};
+
+/// A single `Token: value` git trailer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trailer {
+    pub token: String,
+    pub value: String,
+}
+
+impl Trailer {
+    pub fn new(token: &str, value: &str) -> Self {
+        Self {
+            token: token.to_string(),
+            value: value.trim().to_string(),
+        }
+    }
+
+    /// Render the trailer as a single message line
+    pub fn to_line(&self) -> String {
+        format!("{}: {}", self.token, self.value)
+    }
+}
+
+/// Build a `Co-authored-by` trailer, validating the `Name <email>` format
+pub fn co_author(identity: &str) -> Result<Trailer> {
+    validate_identity(identity)?;
+    Ok(Trailer::new("Co-authored-by", identity))
+}
+
+/// Token of the Developer Certificate of Origin trailer that `git commit -s` adds
+pub const SIGNED_OFF_BY: &str = "Signed-off-by";
+
+/// Build a `Signed-off-by` trailer for an identity
+pub fn sign_off(identity: &str) -> Trailer {
+    Trailer::new(SIGNED_OFF_BY, identity)
+}
+
+/// Token of the trailer naming the category of the change
+pub const COMMIT_TYPE: &str = "Commit-Type";
+
+/// Build a `Commit-Type` trailer with the category's lowercase name, e.g. `Commit-Type: feat`
+pub fn commit_type(category: &CommitCategory) -> Trailer {
+    Trailer::new(COMMIT_TYPE, category.name())
+}
+
+/// Loosely validate a `Name <email>` identity
+pub fn validate_identity(identity: &str) -> Result<()> {
+    let identity = identity.trim();
+    let valid = match (identity.find('<'), identity.rfind('>')) {
+        (Some(start), Some(end)) => start > 0 && end > start + 1 && end == identity.len() - 1,
+        _ => false,
+    };
+
+    if !valid {
+        return Err(eyre!(
+            "Invalid identity '{}': expected format \"Name <email>\"",
+            identity
+        ));
+    }
+    Ok(())
+}
+
+/// Check if a line looks like a git trailer (`Token: value` with no spaces in the token)
+fn is_trailer_line(line: &str) -> bool {
+    match line.split_once(": ") {
+        Some((token, value)) => {
+            !token.is_empty()
+                && !value.trim().is_empty()
+                && token
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        }
+        None => false,
+    }
+}
+
+/// Append trailers to a commit message, keeping them in a single trailer block
+///
+/// A blank line separates the body from the trailer block. If the message already ends
+/// with a trailer block, new trailers are added to it, and trailers that are already
+/// present are not duplicated.
+pub fn append_trailers(message: &str, trailers: &[Trailer]) -> String {
+    let message = message.trim_end();
+    let existing: Vec<&str> = message.lines().map(str::trim).collect();
+
+    let new_lines: Vec<String> = trailers
+        .iter()
+        .map(Trailer::to_line)
+        .filter(|line| !existing.contains(&line.as_str()))
+        .fold(Vec::new(), |mut acc, line| {
+            if !acc.contains(&line) {
+                acc.push(line);
+            }
+            acc
+        });
+
+    if new_lines.is_empty() {
+        return message.to_string();
+    }
+
+    // Does the message already end with a trailer block (after the subject)?
+    let last_paragraph = message.rsplit("\n\n").next().unwrap_or("");
+    let has_trailer_block = message.contains("\n\n")
+        && !last_paragraph.trim().is_empty()
+        && last_paragraph.lines().all(|line| is_trailer_line(line.trim()));
+
+    let separator = if message.is_empty() {
+        ""
+    } else if has_trailer_block {
+        "\n"
+    } else {
+        "\n\n"
+    };
+
+    format!("{}{}{}", message, separator, new_lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_trailers_subject_only() {
+        let trailers = vec![
+            co_author("Jane Doe <jane@example.com>").unwrap(),
+            Trailer::new("Refs", "#123"),
+        ];
+        assert_eq!(
+            append_trailers("feat: add login", &trailers),
+            "feat: add login\n\nCo-authored-by: Jane Doe <jane@example.com>\nRefs: #123"
+        );
+    }
+
+    #[test]
+    fn test_append_sign_off() {
+        let trailers = vec![
+            co_author("Jane Doe <jane@example.com>").unwrap(),
+            sign_off("John Roe <john@example.com>"),
+        ];
+        let message = append_trailers("feat: add login\n\nImplement OAuth2 flow.", &trailers);
+        assert_eq!(
+            message,
+            "feat: add login\n\nImplement OAuth2 flow.\n\n\
+             Co-authored-by: Jane Doe <jane@example.com>\n\
+             Signed-off-by: John Roe <john@example.com>"
+        );
+
+        // An edited message keeps a single sign-off
+        assert_eq!(append_trailers(&format!("{}\n", message), &trailers[1..]), message);
+    }
+
+    #[test]
+    fn test_append_trailers_with_body() {
+        let message = "feat: add login\n\nImplement OAuth2 flow.\n";
+        let trailers = vec![
+            co_author("Jane Doe <jane@example.com>").unwrap(),
+            co_author("John Roe <john@example.com>").unwrap(),
+        ];
+        assert_eq!(
+            append_trailers(message, &trailers),
+            "feat: add login\n\nImplement OAuth2 flow.\n\n\
+             Co-authored-by: Jane Doe <jane@example.com>\n\
+             Co-authored-by: John Roe <john@example.com>"
+        );
+    }
+
+    #[test]
+    fn test_append_trailers_extends_existing_block() {
+        let message = "feat: add login\n\nCo-authored-by: Jane Doe <jane@example.com>";
+        let trailers = vec![
+            co_author("Jane Doe <jane@example.com>").unwrap(),
+            Trailer::new("Refs", "#42"),
+        ];
+        assert_eq!(
+            append_trailers(message, &trailers),
+            "feat: add login\n\nCo-authored-by: Jane Doe <jane@example.com>\nRefs: #42"
+        );
+    }
+
+    #[test]
+    fn test_append_no_trailers() {
+        assert_eq!(append_trailers("fix: typo\n", &[]), "fix: typo");
+    }
+
+    #[test]
+    fn test_validate_identity() {
+        assert!(validate_identity("Jane Doe <jane@example.com>").is_ok());
+        assert!(validate_identity("jane@example.com").is_err());
+        assert!(validate_identity("<jane@example.com>").is_err());
+        assert!(validate_identity("Jane <>").is_err());
+    }
+}
@@ -1,7 +1,13 @@
-use crate::config::Config;
+use crate::config::{Config, PricingConfig, ENV_OVERRIDES};
+use crate::emotes::CommitCategory;
+use crate::git::{DiffStats, SplitDiffResult};
+use crate::llm::Usage;
+use crate::pipeline::{BenchRun, CallStats};
 use colored::Colorize;
 use prettytable::{row, Table};
+use std::env;
 use std::path::Path;
+use std::time::Duration;
 
 /// Print the application header
 pub fn print_header() {
@@ -14,26 +20,27 @@ pub fn print_header() {
     );
 }
 
+/// Mask all but the first characters of a token
+fn masked_token(token: Option<String>) -> String {
+    match token {
+        Some(token) if token.len() > 8 => format!("{}•••••", &token[0..4]),
+        Some(_) => "•••••••".to_string(),
+        None => "<not set>".to_string(),
+    }
+}
+
 /// Print configuration in a formatted table
 pub fn print_config_table(config: &Config) {
     let mut table = Table::new();
     table.add_row(row!["Setting", "Value"]);
 
     // API Token (with masking for security)
-    let token_display = if let Some(token) = config.get("api_token") {
-        if token.len() > 8 {
-            format!("{}•••••", &token[0..4])
-        } else {
-            "•••••••".to_string()
-        }
-    } else {
-        "<not set>".to_string()
-    };
-    table.add_row(row!["api_token", token_display]);
+    table.add_row(row!["api_token", masked_token(config.get("api_token"))]);
 
     // Other settings
     table.add_row(row!["api_base_url", config.get_api_base_url()]);
     table.add_row(row!["model", config.get_model()]);
+    table.add_row(row!["provider", config.get_provider().unwrap_or("-")]);
 
     // System prompt (truncated if too long)
     let system_prompt = config.get_system_prompt();
@@ -66,6 +73,248 @@ pub fn print_config_table(config: &Config) {
     };
     table.add_row(row!["user_prompt", display_user_prompt]);
 
+    let combine_system_prompt = config.get_combine_system_prompt().map_or("-".to_string(), |prompt| {
+        if prompt.chars().count() > MAX_PROMPT_LENGTH {
+            format!("{}...", prompt.chars().take(MAX_PROMPT_LENGTH).collect::<String>())
+        } else {
+            prompt.to_string()
+        }
+    });
+    table.add_row(row!["combine_system_prompt", combine_system_prompt]);
+    table.add_row(row!["combine.strategy", config.get_combine_strategy()]);
+    table.add_row(row!["combine.include_files", config.get_combine_include_files()]);
+    table.add_row(row!["combine.separator", config.get_combine_separator()]);
+
+    table.add_row(row!["cache_enabled", config.get_cache_enabled()]);
+    let max_body_lines = config
+        .get_max_body_lines()
+        .map_or("unlimited".to_string(), |n| n.to_string());
+    table.add_row(row!["max_body_lines", max_body_lines]);
+    let max_subject_length = config
+        .get_max_subject_length()
+        .map_or("unlimited".to_string(), |n| n.to_string());
+    table.add_row(row!["max_subject_length", max_subject_length]);
+    table.add_row(row!["min_message_length", config.get_min_message_length()]);
+    table.add_row(row!["subject_only", config.get_subject_only()]);
+    let body_style = config
+        .get_body_style()
+        .map_or("-".to_string(), |style| style.to_string());
+    table.add_row(row!["body_style", body_style]);
+    table.add_row(row!["body.append_stats", config.get_append_stats()]);
+    table.add_row(row!["message.trailing_newline", config.get_trailing_newline()]);
+    table.add_row(row!["emote_placement", config.get_emote_placement()]);
+    table.add_row(row!["scope_style", config.get_scope_style()]);
+    table.add_row(row!["emote.replace_type_prefix", config.get_replace_type_prefix()]);
+    let skip_categories = config.emote.skip_categories.as_deref().unwrap_or_default();
+    let skip_categories = if skip_categories.is_empty() { "-".to_string() } else { skip_categories.join(", ") };
+    table.add_row(row!["emote.skip_categories", skip_categories]);
+    table.add_row(row!["emote.prepend_after_emote", config.get_prepend_after_emote()]);
+    table.add_row(row!["emote.multi", config.get_emote_multi()]);
+    table.add_row(row!["emote.max_emotes", config.get_max_emotes()]);
+    table.add_row(row!["emote.style", config.get_emote_style()]);
+    let allowed_types = config.get_allowed_types().map_or("any".to_string(), |types| types.join(", "));
+    table.add_row(row!["conventional.allowed_types", allowed_types]);
+    table.add_row(row!["subject.pattern", config.subject.pattern.as_deref().unwrap_or("any")]);
+    table.add_row(row!["categorize.mode", config.get_categorize_mode()]);
+    let priority = config.categorize.priority.as_deref().unwrap_or_default();
+    let priority = if priority.is_empty() { "-".to_string() } else { priority.join(", ") };
+    table.add_row(row!["categorize.priority", priority]);
+    let templates: Vec<&str> = config.templates.keys().map(String::as_str).collect();
+    let templates = if templates.is_empty() { "-".to_string() } else { templates.join(", ") };
+    table.add_row(row!["templates", templates]);
+    let branch_prefix_map: Vec<String> = config
+        .get_branch_prefix_map()
+        .iter()
+        .map(|(prefix, category)| format!("{}={}", prefix, category))
+        .collect();
+    table.add_row(row!["branch_prefix_map", branch_prefix_map.join(", ")]);
+    let default_scopes: Vec<String> = config
+        .category_default_scope
+        .iter()
+        .flatten()
+        .map(|(category, scope)| format!("{}={}", category, scope))
+        .collect();
+    let default_scopes = if default_scopes.is_empty() { "-".to_string() } else { default_scopes.join(", ") };
+    table.add_row(row!["category_default_scope", default_scopes]);
+    table.add_row(row!["commit.signoff", config.get_signoff()]);
+    table.add_row(row!["trailers.commit_type", config.get_commit_type_trailer()]);
+    table.add_row(row!["require_conventional", config.get_require_conventional()]);
+    table.add_row(row!["structured_output", config.get_structured_output()]);
+    table.add_row(row!["deterministic", config.get_deterministic()]);
+    let max_api_calls = config
+        .get_max_api_calls()
+        .map_or("unlimited".to_string(), |n| n.to_string());
+    table.add_row(row!["max_api_calls", max_api_calls]);
+    let min_request_interval = config
+        .get_min_request_interval()
+        .map_or("-".to_string(), |interval| format!("{} ms", interval.as_millis()));
+    table.add_row(row!["min_request_interval_ms", min_request_interval]);
+    table.add_row(row!["http2_only", config.get_http2_only()]);
+    let pool_idle_timeout = config
+        .get_pool_idle_timeout()
+        .map_or("-".to_string(), |timeout| format!("{} s", timeout.as_secs()));
+    table.add_row(row!["pool_idle_timeout_secs", pool_idle_timeout]);
+    let ca_cert_path = config
+        .ca_cert_path
+        .as_ref()
+        .map_or("-".to_string(), |path| path.display().to_string());
+    table.add_row(row!["ca_cert_path", ca_cert_path]);
+    table.add_row(row!["filter.enabled", config.get_line_filter().enabled]);
+    table.add_row(row!["filter.strip_leading_reasoning", config.get_line_filter().strip_leading_reasoning]);
+    table.add_row(row![
+        "post_generate_command",
+        config.get_post_generate_command().unwrap_or("-")
+    ]);
+    table.add_row(row!["context.fetch_issue", config.get_fetch_issue()]);
+    table.add_row(row!["context.include_branch", config.get_include_branch()]);
+    table.add_row(row!["context.include_remote", config.get_include_remote()]);
+    table.add_row(row!["context.include_blame", config.get_include_blame()]);
+    let price = |price: Option<f64>| price.map_or("-".to_string(), |price| price.to_string());
+    table.add_row(row!["pricing.input_per_million", price(config.get_pricing().input_per_million)]);
+    table.add_row(row!["pricing.output_per_million", price(config.get_pricing().output_per_million)]);
+    table.add_row(row!["github_token", masked_token(config.get("github_token"))]);
+    table.add_row(row!["gitlab_token", masked_token(config.get("gitlab_token"))]);
+
+    table.printstd();
+}
+
+/// Print the footprint of the staged changes and the category their paths suggest
+pub fn print_diff_stats(stats: &DiffStats, category: Option<CommitCategory>) {
+    let category = category.map_or("-".to_string(), |category| {
+        format!("{} {}", category.emote(), category.description())
+    });
+    println!(
+        "{} {} (inferred category: {})",
+        "📈 Staged:".blue(),
+        stats.summary().bright_blue(),
+        category
+    );
+}
+
+/// Print the diff that will be sent and how it will be chunked, to stderr
+pub fn print_diff_preview(diff: &str, split_result: &SplitDiffResult, stats: Option<&DiffStats>) {
+    eprintln!("{}", "🔎 Diff preview:".blue().bold());
+    eprintln!("{}", diff.trim_end());
+    eprintln!();
+    eprintln!(
+        "{} {} chars, split method: {}, {} chunk(s)",
+        "🧩 Chunking:".blue().bold(),
+        split_result.total_size,
+        split_result.split_method.bright_blue(),
+        split_result.chunks.len()
+    );
+    if let Some(stats) = stats {
+        eprintln!("{} {}", "📈 Staged:".blue().bold(), stats.summary());
+    }
+    for (i, chunk) in split_result.chunks.iter().enumerate() {
+        eprintln!(
+            "   {}. {} ({} chars)",
+            i + 1,
+            chunk.description,
+            chunk.content.len()
+        );
+    }
+    eprintln!();
+}
+
+/// Print how a diff was split: the method and each chunk's description and size
+pub fn print_split_result(split_result: &SplitDiffResult, show_content: bool) {
+    println!(
+        "{} {} ({} bytes, {} chunk(s))",
+        "🧩 Split method:".blue().bold(),
+        split_result.split_method.bright_blue(),
+        split_result.total_size,
+        split_result.chunks.len()
+    );
+    for (i, chunk) in split_result.chunks.iter().enumerate() {
+        println!(
+            "   {}. {} ({} bytes)",
+            i + 1,
+            chunk.description,
+            chunk.content.len()
+        );
+        if show_content {
+            println!("{}", chunk.content.trim_end().dimmed());
+            println!();
+        }
+    }
+}
+
+/// Print one row per API call with its latency, tokens and estimated cost, and a total
+pub fn print_call_report(calls: &[CallStats], pricing: &PricingConfig) {
+    let tokens = |tokens: Option<u64>| tokens.map_or("-".to_string(), |tokens| tokens.to_string());
+    let cost = |usage: Option<Usage>| {
+        usage
+            .and_then(|usage| pricing.cost(&usage))
+            .map_or("-".to_string(), |cost| format!("{:.4}", cost))
+    };
+
+    let mut table = Table::new();
+    table.add_row(row!["Call", "Latency", "Prompt tokens", "Completion tokens", "Cost"]);
+    for call in calls {
+        table.add_row(row![
+            call.description,
+            format!("{:.2}s", call.latency.as_secs_f64()),
+            tokens(call.usage.map(|usage| usage.prompt_tokens)),
+            tokens(call.usage.map(|usage| usage.completion_tokens)),
+            cost(call.usage)
+        ]);
+    }
+
+    let latency: Duration = calls.iter().map(|call| call.latency).sum();
+    let total = total_usage(calls);
+    table.add_row(row![
+        b->"Total",
+        format!("{:.2}s", latency.as_secs_f64()),
+        tokens(total.map(|usage| usage.prompt_tokens)),
+        tokens(total.map(|usage| usage.completion_tokens)),
+        cost(total)
+    ]);
+
+    println!("{}", "📊 API calls:".blue().bold());
+    table.printstd();
+}
+
+/// The token usage of some calls added up, `None` when none of them reported any
+///
+/// Calls without reported usage count as zero tokens.
+fn total_usage(calls: &[CallStats]) -> Option<Usage> {
+    calls
+        .iter()
+        .filter_map(|call| call.usage)
+        .reduce(|total, usage| Usage {
+            prompt_tokens: total.prompt_tokens + usage.prompt_tokens,
+            completion_tokens: total.completion_tokens + usage.completion_tokens,
+        })
+}
+
+/// Print the messages that `bench` generated with each model side by side, with the
+/// latency, tokens and cost of each run
+pub fn print_bench_report(runs: &[BenchRun], pricing: &PricingConfig) {
+    let tokens = |tokens: Option<u64>| tokens.map_or("-".to_string(), |tokens| tokens.to_string());
+
+    let mut table = Table::new();
+    table.add_row(row!["Model", "Latency", "Prompt tokens", "Completion tokens", "Cost", "Message"]);
+    for run in runs {
+        let latency: Duration = run.calls.iter().map(|call| call.latency).sum();
+        let total = total_usage(&run.calls);
+        let message = match &run.message {
+            Ok(message) => message.clone(),
+            Err(err) => format!("error: {}", err).red().to_string(),
+        };
+        table.add_row(row![
+            b->run.model,
+            format!("{:.2}s", latency.as_secs_f64()),
+            tokens(total.map(|usage| usage.prompt_tokens)),
+            tokens(total.map(|usage| usage.completion_tokens)),
+            total
+                .and_then(|usage| pricing.cost(&usage))
+                .map_or("-".to_string(), |cost| format!("{:.4}", cost)),
+            message
+        ]);
+    }
+
+    println!("{}", "📊 Models compared:".blue().bold());
     table.printstd();
 }
 
@@ -93,6 +342,20 @@ pub fn print_config_sources(global_config_path: &Path, project_config_path: &Opt
     } else {
         println!("   Project config: {}", "None".dimmed());
     }
+
+    // Show environment variables overriding the files
+    let env_vars: Vec<&str> = ENV_OVERRIDES
+        .iter()
+        .map(|(var, _)| *var)
+        .filter(|var| env::var(var).is_ok_and(|value| !value.trim().is_empty()))
+        .collect();
+    if !env_vars.is_empty() {
+        println!("   Environment: {}", env_vars.join(", ").bright_blue());
+        println!(
+            "   {} Environment variables override both config files",
+            "ℹ️".blue()
+        );
+    }
     println!();
 
     println!("{}", "⚙️  Settings:".blue());
@@ -196,6 +459,35 @@ mod tests {
         assert_eq!(display_user_prompt, "Another long...");
     }
 
+    #[test]
+    fn test_print_call_report() {
+        let calls = vec![
+            CallStats {
+                description: "Chunk 1".to_string(),
+                latency: Duration::from_millis(1200),
+                usage: Some(Usage {
+                    prompt_tokens: 1000,
+                    completion_tokens: 50,
+                }),
+            },
+            CallStats {
+                description: "Combine".to_string(),
+                latency: Duration::from_millis(800),
+                usage: None,
+            },
+        ];
+
+        // Verify the report prints with and without pricing
+        print_call_report(&calls, &PricingConfig::default());
+        print_call_report(
+            &calls,
+            &PricingConfig {
+                input_per_million: Some(2.5),
+                output_per_million: Some(10.0),
+            },
+        );
+    }
+
     #[test]
     fn test_chinese_display() {
         let (_temp_dir, mut config) = setup_test_env();